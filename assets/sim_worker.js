@@ -0,0 +1,14 @@
+// Simulates boids on the CPU for adapters without compute shaders, so that the page stays responsive.
+// See `cpu_simulation.rs` for the other side.
+import init, { step_boids } from "./wgpu_automata.js";
+
+const ready = init();
+
+self.onmessage = async (event) => {
+  await ready;
+  const { id, params, particles, info, numSteps } = event.data;
+  // updates `particles` and `info` in place
+  step_boids(params, particles, info, numSteps);
+  // the id tells the results of a job apart from the ones of jobs that were sent before it
+  self.postMessage({ id, particles, info }, [particles.buffer, info.buffer]);
+};
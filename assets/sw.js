@@ -4,6 +4,7 @@ var filesToCache = [
   './index.html',
   './wgpu_automata.js',
   './wgpu_automata_bg.wasm',
+  './sim_worker.js',
 ];
 
 /* Start the service worker and cache all of the app's content */
@@ -1,14 +1,86 @@
 //! Since I mostly want to do my own rendering, very little actually happens in the top level `App` struct. Instead,
 //! most of the rendering logic actually happens in `renderer.rs`
 
-mod particles;
-mod profiler;
+mod boid_settings;
+mod camera;
+mod color_settings;
+#[cfg(not(target_arch = "wasm32"))]
+mod data_dir;
+mod debug_view;
+mod device;
+mod diagnostics;
+mod emitter_settings;
+mod flock_settings;
+#[cfg(not(target_arch = "wasm32"))]
+mod frame_export;
+mod inspector;
+mod kernel_settings;
+mod log_console;
+mod palette;
+mod persistence;
+#[cfg(not(target_arch = "wasm32"))]
+mod pipeline_cache;
+#[cfg(not(target_arch = "wasm32"))]
+mod presets;
+mod profiler_window;
+#[cfg(not(target_arch = "wasm32"))]
+mod recorder;
+mod render_callback;
+mod scheduler;
+mod sprite_settings;
+mod startup;
+mod stats;
+mod timeline;
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+mod winit_frontend;
 
+use eframe::egui_wgpu::RenderState;
 use egui::{Vec2, Widget};
 use puffin::profile_function;
 
-use crate::shaders::boids;
-use particles::{ParticleSystem, RenderCallback};
+use crate::{
+    shaders::boids,
+    sim::{FrameInput, ParticleSystem, MAX_PARTICLES},
+};
+
+use boid_settings::BoidSettings;
+use camera::Camera;
+use color_settings::ColorSettings;
+use debug_view::DebugView;
+#[cfg(not(target_arch = "wasm32"))]
+pub use device::enumerate_adapters;
+pub use device::{get_wgpu_options, AdapterSelection, ForcedBackend};
+use diagnostics::DiagnosticsWindow;
+use emitter_settings::EmitterSettings;
+use flock_settings::FlockSettings;
+#[cfg(not(target_arch = "wasm32"))]
+use frame_export::FrameExport;
+use inspector::BoidInspector;
+use kernel_settings::KernelSettings;
+pub use log_console::init_logger;
+use log_console::LogConsole;
+#[cfg(not(target_arch = "wasm32"))]
+use pipeline_cache::PipelineCacheFile;
+use profiler_window::ProfilerWindow;
+#[cfg(not(target_arch = "wasm32"))]
+use recorder::Recorder;
+use render_callback::RenderCallback;
+use scheduler::SimScheduler;
+use sprite_settings::SpriteSettings;
+#[cfg(not(target_arch = "wasm32"))]
+pub use startup::WindowOptions;
+pub use startup::{SimMode, StartupOptions};
+use stats::FlockStatsWindow;
+use timeline::{Property, Timeline};
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+pub use winit_frontend::{run_winit, WinitError};
+
+/// Same as the app name that the eframe frontend runs with, which it stores its state under.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+const APP_ID: &str = "wgpu automatas";
+
+/// Simulated seconds per step that can be chosen, in the UI and on the command line.
+pub const SIM_DELTA_TIME_RANGE: std::ops::RangeInclusive<f32> = 0.004..=0.1;
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -17,8 +89,42 @@ pub struct RendererApp {
     is_playing: bool,
     sim_delta_time: f32,
     sim_speed: f32,
-    leftover_sim_frames: f32,
     num_particles: u32,
+    flock: FlockSettings,
+    boid_settings: BoidSettings,
+    emitter_settings: EmitterSettings,
+    kernel_settings: KernelSettings,
+    color_settings: ColorSettings,
+    sprite_settings: SpriteSettings,
+    debug_view: DebugView,
+    scheduler: SimScheduler,
+    flock_stats: FlockStatsWindow,
+    camera: Camera,
+    inspector: BoidInspector,
+    diagnostics: DiagnosticsWindow,
+    profiler: ProfilerWindow,
+    log_console: LogConsole,
+    timeline: Timeline,
+    #[cfg(not(target_arch = "wasm32"))]
+    frame_export: FrameExport,
+    #[cfg(not(target_arch = "wasm32"))]
+    recorder: Recorder,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pipeline_cache: Option<PipelineCacheFile>,
+    /// The document the state was loaded from, with the settings that this version doesn't know
+    #[serde(skip)]
+    saved_state: toml::Table,
+    /// Settings of `saved_state` that couldn't be read, which are saved as they were unless they're changed
+    #[serde(skip)]
+    unread_settings: Vec<persistence::UnreadSetting>,
+    /// Tells the user that the saved state was migrated or couldn't be read completely, until it is dismissed
+    #[serde(skip)]
+    state_notice: Option<String>,
+    /// Where the last preset was saved to, or why that failed
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    preset_status: Option<Result<String, String>>,
 }
 
 impl Default for RendererApp {
@@ -27,62 +133,319 @@ impl Default for RendererApp {
             is_playing: true,
             sim_delta_time: 1. / 120.,
             sim_speed: 1.,
-            leftover_sim_frames: 0.,
             num_particles: 10000,
+            flock: FlockSettings::default(),
+            boid_settings: BoidSettings::default(),
+            emitter_settings: EmitterSettings::default(),
+            kernel_settings: KernelSettings::default(),
+            color_settings: ColorSettings::default(),
+            sprite_settings: SpriteSettings::default(),
+            debug_view: DebugView::default(),
+            scheduler: SimScheduler::default(),
+            flock_stats: FlockStatsWindow::default(),
+            camera: Camera::default(),
+            inspector: BoidInspector::default(),
+            diagnostics: DiagnosticsWindow::default(),
+            profiler: ProfilerWindow::default(),
+            log_console: LogConsole::default(),
+            timeline: Timeline::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            frame_export: FrameExport::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            recorder: Recorder::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_cache: None,
+            saved_state: toml::Table::new(),
+            unread_settings: Vec::new(),
+            state_notice: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            preset_status: None,
         }
     }
 }
 
+/// Who draws the boids.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SceneTarget {
+    /// A paint callback inside of egui's render pass
+    Egui,
+    /// The frontend, in its own render pass before egui. The canvas is left transparent for it.
+    #[cfg_attr(not(feature = "winit"), allow(dead_code))]
+    Frontend,
+}
+
+/// What happened in a frame of the UI, that the frontend has to take care of.
+struct UiOutput {
+    /// The boids to draw into this rectangle, if the frontend draws them itself
+    #[cfg_attr(not(feature = "winit"), allow(dead_code))]
+    scene: Option<(egui::Rect, RenderCallback)>,
+    /// Restart on this adapter
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    restart: Option<AdapterSelection>,
+}
+
 impl RendererApp {
-    /// Called once before the first frame.
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // Initialize the renderer
+    /// Called once before the first frame. `adapter_selection` is what the wgpu options were created from, and
+    /// `startup` overrides the saved state.
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        adapter_selection: AdapterSelection,
+        startup: StartupOptions,
+    ) -> Self {
         let wgpu_render_state = cc
             .wgpu_render_state
             .as_ref()
             .expect("WGPU is not properly initialized");
-        wgpu_render_state
+        Self::with_render_state(wgpu_render_state, cc.storage, adapter_selection, startup)
+    }
+
+    fn with_render_state(
+        render_state: &RenderState,
+        storage: Option<&dyn eframe::Storage>,
+        adapter_selection: AdapterSelection,
+        startup: StartupOptions,
+    ) -> Self {
+        log_console::capture_uncaptured_errors(&render_state.device);
+        #[cfg(not(target_arch = "wasm32"))]
+        let pipeline_cache = PipelineCacheFile::load(&render_state.adapter, &render_state.device);
+        #[cfg(not(target_arch = "wasm32"))]
+        let cache = pipeline_cache.as_ref().map(PipelineCacheFile::cache);
+        #[cfg(target_arch = "wasm32")]
+        let cache = None;
+
+        // Initialize the renderer
+        render_state
             .renderer
             .write()
             .callback_resources
             .insert(ParticleSystem::init(
-                &wgpu_render_state.device,
-                wgpu_render_state.target_format,
+                &render_state.device,
+                &render_state.queue,
+                render_state.target_format,
+                cache,
             ));
 
         // Load previous app state (if any).
-        if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+        let (mut app, state_notice) = storage.map(persistence::load).unwrap_or_default();
+        app.state_notice = state_notice;
+        app.apply_startup(startup);
+        app.diagnostics.selection = adapter_selection;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.pipeline_cache = pipeline_cache;
+        }
+        app
+    }
+
+    fn apply_startup(&mut self, startup: StartupOptions) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &startup.preset {
+            match presets::load(path) {
+                Ok((preset, notice)) => {
+                    log::info!("Loaded the preset {}", path.display());
+                    *self = preset;
+                    self.state_notice = notice;
+                }
+                Err(err) => log::error!("Failed to load the preset: {err}"),
+            }
+        }
+        if let Some(sim) = startup.sim {
+            self.emitter_settings.enabled = sim == SimMode::Emitters;
+        }
+        if let Some(paused) = startup.paused {
+            self.is_playing = !paused;
+        }
+        if let Some(num_particles) = startup.num_particles {
+            self.num_particles = num_particles.min(MAX_PARTICLES as u32);
         }
+        if let Some(seed) = startup.seed {
+            self.flock.seed = seed;
+            self.emitter_settings.seed = seed;
+        }
+        if let Some(delta_time) = startup.delta_time {
+            self.sim_delta_time = delta_time;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &startup.snapshot {
+            self.flock.load_snapshot(path);
+        }
+    }
+
+    fn show_state_notice(&mut self, ctx: &egui::Context) {
+        let Some(notice) = &self.state_notice else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::Window::new("Saved Settings")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(notice);
+                dismissed = ui.button("OK").clicked();
+            });
+        if dismissed {
+            self.state_notice = None;
+        }
+    }
 
-        Default::default()
+    /// Starts another instance of the app on `selection` and closes this one.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restart(
+        &mut self,
+        ctx: &egui::Context,
+        storage: Option<&mut (dyn eframe::Storage + 'static)>,
+        selection: &AdapterSelection,
+    ) {
+        // the new instance loads the state as it starts, before this one saves it on exit
+        if let Some(storage) = storage {
+            eframe::App::save(self, storage);
+            storage.flush();
+        }
+        match device::restart(selection) {
+            Ok(()) => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            Err(err) => {
+                log::error!("Failed to restart: {err}");
+                self.diagnostics.error = Some(format!("Failed to restart: {err}"));
+            }
+        }
     }
 }
 
 impl eframe::App for RendererApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+        persistence::save(self, storage);
+        // pipelines are also created while running, like the ones for other workgroup sizes
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(pipeline_cache) = &self.pipeline_cache {
+            pipeline_cache.save();
+        }
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+        let output = self.ui(ctx, frame.wgpu_render_state(), SceneTarget::Egui);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(selection) = output.restart {
+            self.restart(ctx, frame.storage_mut(), &selection);
+        }
+    }
+}
+
+impl RendererApp {
+    /// The whole UI, shared by every frontend.
+    fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        render_state: Option<&RenderState>,
+        scene_target: SceneTarget,
+    ) -> UiOutput {
         profile_function!();
 
+        let mut step_gpu_time_ms = None;
+        let mut wireframe_supported = false;
+        let mut simulates_on_cpu = false;
+        if let Some(render_state) = render_state {
+            self.sprite_settings.handle_dropped_files(ctx, render_state);
+
+            let mut renderer = render_state.renderer.write();
+            if let Some(particles) = renderer.callback_resources.get_mut::<ParticleSystem>() {
+                step_gpu_time_ms = particles.gpu_time_per_step_ms();
+                // follows the steps that actually ran, which can be fewer than requested on the CPU
+                self.timeline.advance(particles.take_finished_sim_time());
+                wireframe_supported = particles.supports_wireframe();
+                simulates_on_cpu = particles.simulates_on_cpu();
+                self.kernel_settings
+                    .update(particles, &render_state.adapter.get_info());
+                if let Some(num_particles) = self.flock.update(particles) {
+                    // a snapshot is a fixed flock
+                    self.num_particles = num_particles;
+                    self.emitter_settings.enabled = false;
+                }
+                self.profiler.update(particles);
+                self.flock_stats.extend(particles.take_stats_samples());
+                for picked in particles.take_pick_results() {
+                    self.inspector.handle_pick_result(picked);
+                }
+                self.inspector
+                    .update(particles.inspected(), self.num_particles);
+            }
+        }
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 #[cfg(not(target_arch = "wasm32"))]
                 {
                     ui.menu_button("File", |ui| {
+                        if ui
+                            .button("Save Preset")
+                            .on_hover_text(
+                                "Saves every setting to the presets in the data directory, which --preset starts with",
+                            )
+                            .clicked()
+                        {
+                            self.preset_status = Some(match presets::save(self) {
+                                Ok(path) => {
+                                    log::info!("Saved the preset {}", path.display());
+                                    Ok(format!("Saved {}", path.display()))
+                                }
+                                Err(err) => {
+                                    log::error!("Failed to save the preset: {err}");
+                                    Err(format!("Failed to save the preset: {err}"))
+                                }
+                            });
+                        }
+                        match &self.preset_status {
+                            Some(Ok(status)) => {
+                                ui.label(status);
+                            }
+                            Some(Err(error)) => {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
+                            None => {}
+                        }
+                        ui.separator();
+                        self.frame_export.menu_ui(ui);
+                        ui.separator();
                         if ui.button("Quit").clicked() {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
                     });
                 }
 
+                ui.menu_button("View", |ui| {
+                    ui.checkbox(&mut self.flock_stats.open, "Flock Statistics");
+                    ui.checkbox(&mut self.diagnostics.open, "GPU Diagnostics");
+                    ui.checkbox(&mut self.profiler.open, "Profiler");
+                    ui.checkbox(&mut self.timeline.open, "Timeline");
+                    let wgpu_errors = self.log_console.wgpu_errors();
+                    ui.checkbox(
+                        &mut self.log_console.open,
+                        if wgpu_errors > 0 {
+                            format!("Log Console ({wgpu_errors} wgpu errors)")
+                        } else {
+                            "Log Console".to_owned()
+                        },
+                    );
+                });
+
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
                     if ui.button("reset UI").clicked() {
+                        // the pipeline cache belongs to the device, not to the UI, and settings that this version
+                        // doesn't know aren't shown in it
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let pipeline_cache = self.pipeline_cache.take();
+                        let saved_state = std::mem::take(&mut self.saved_state);
+                        let unread_settings = std::mem::take(&mut self.unread_settings);
                         *self = Default::default();
+                        self.saved_state = saved_state;
+                        self.unread_settings = unread_settings;
+                        #[cfg(not(target_arch = "wasm32"))]
+                        {
+                            self.pipeline_cache = pipeline_cache;
+                        }
                         ui.ctx().memory_mut(|w| *w = Default::default());
                     }
                 });
@@ -95,57 +458,206 @@ impl eframe::App for RendererApp {
             if !self.is_playing {
                 single_step = ui.button("Step").clicked();
             }
-            egui::Slider::new(&mut self.sim_delta_time, 0.004..=0.1)
+            if simulates_on_cpu {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "Simulating on the CPU, since the GPU can't run compute shaders",
+                );
+            }
+            egui::Slider::new(&mut self.sim_delta_time, SIM_DELTA_TIME_RANGE)
                 .text("Simulation Delta Time (s)")
                 .ui(ui);
             egui::Slider::new(&mut self.sim_speed, 0. ..=10.)
                 .text("Simulation Speed Multiplier")
                 .ui(ui);
-            egui::Slider::new(&mut self.num_particles, 0..=particles::MAX_PARTICLES as u32)
-                .text("Number of Boids")
+            let emitting = self.emitter_settings.enabled && !simulates_on_cpu;
+            egui::Slider::new(&mut self.num_particles, 0..=MAX_PARTICLES as u32)
+                .text(if emitting {
+                    "Maximum Number of Boids"
+                } else {
+                    "Number of Boids"
+                })
                 .ui(ui);
+            self.debug_view.ui(ui, wireframe_supported);
+
+            ui.collapsing("Starting Flock", |ui| {
+                self.flock.ui(ui, emitting);
+            });
+            ui.collapsing("Flocking", |ui| {
+                self.boid_settings.ui(ui);
+            });
+            ui.collapsing("Emitters", |ui| {
+                self.emitter_settings.ui(ui, simulates_on_cpu);
+            });
+            ui.collapsing("Colors", |ui| {
+                self.color_settings.ui(ui);
+            });
+            ui.collapsing("Sprites", |ui| {
+                self.sprite_settings.ui(ui, render_state);
+            });
+            ui.collapsing("Scheduler", |ui| {
+                self.scheduler.ui(ui, step_gpu_time_ms);
+            });
+            ui.collapsing("Compute Kernel", |ui| {
+                self.kernel_settings
+                    .ui(ui, step_gpu_time_ms, simulates_on_cpu);
+            });
+            if let Some(render_state) = render_state {
+                ui.collapsing("GPU Features", |ui| {
+                    device::features_ui(ui, render_state.device.features());
+                });
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.collapsing("Recording", |ui| {
+                self.recorder.ui(ui, self.sim_delta_time * self.sim_speed);
+                if self.recorder.take_start_over() {
+                    self.flock.start_over();
+                    self.timeline.play_from_start();
+                }
+            });
+            ui.collapsing("Camera", |ui| {
+                egui::Slider::new(&mut self.camera.zoom, 0.1..=100.)
+                    .logarithmic(true)
+                    .text("Zoom")
+                    .ui(ui);
+                if ui.button("Reset camera").clicked() {
+                    self.camera = Camera::default();
+                }
+            });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            egui::Frame::canvas(ui.style()).show(ui, |ui| {
-                let min_size = ui.available_size().min_elem();
-                let (rect, _response) =
-                    ui.allocate_exact_size(Vec2::splat(min_size), egui::Sense::click_and_drag());
-
-                let num_sim_updates = if self.is_playing {
-                    let render_dt = ui.ctx().input(|input| input.stable_dt);
-                    let sim_frames = self.leftover_sim_frames + render_dt / self.sim_delta_time;
-                    self.leftover_sim_frames = sim_frames.fract();
-                    sim_frames as u32
-                } else if single_step {
-                    1
-                } else {
-                    0
-                };
-                let sim_params = boids::SimParams {
-                    num_particles: self.num_particles,
-                    delta_time: self.sim_delta_time * self.sim_speed,
-                    separation_distance: 0.025,
-                    separation_scale: 0.05,
-                    alignment_distance: 0.025,
-                    alignment_scale: 0.005,
-                    cohesion_distance: 0.1,
-                    cohesion_scale: 0.02,
-                };
-
-                ui.painter()
-                    .add(eframe::egui_wgpu::Callback::new_paint_callback(
-                        rect,
-                        RenderCallback {
+        let mut scene = None;
+        let mut central_panel = egui::Frame::central_panel(&ctx.style());
+        let mut canvas = egui::Frame::canvas(&ctx.style());
+        if scene_target == SceneTarget::Frontend {
+            // the boids are already drawn underneath egui
+            central_panel = central_panel.fill(egui::Color32::TRANSPARENT);
+            canvas = canvas.fill(egui::Color32::TRANSPARENT);
+        }
+        egui::CentralPanel::default()
+            .frame(central_panel)
+            .show(ctx, |ui| {
+                canvas.show(ui, |ui| {
+                    let min_size = ui.available_size().min_elem();
+                    let (rect, response) = ui
+                        .allocate_exact_size(Vec2::splat(min_size), egui::Sense::click_and_drag());
+
+                    self.camera.handle_input(rect, &response);
+                    if let Some(target) = self.inspector.follow_target() {
+                        self.camera.center = target;
+                    }
+                    for (property, value) in self.timeline.values() {
+                        property.set(self, value);
+                    }
+                    let pick_position = response
+                        .interact_pointer_pos()
+                        .filter(|_| response.clicked())
+                        .map(|click_pos| self.inspector.pick(&self.camera, rect, click_pos));
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    let recording_steps = self.recorder.recording_steps();
+                    #[cfg(target_arch = "wasm32")]
+                    let recording_steps = None;
+                    let num_sim_updates = if let Some(steps) = recording_steps {
+                        // the frames of a recording are always the same simulation time apart, however long capturing
+                        // them takes
+                        self.scheduler.reset_leftover();
+                        steps
+                    } else if self.is_playing {
+                        let render_dt = ui.ctx().input(|input| input.stable_dt);
+                        self.scheduler.steps_for_frame(
+                            render_dt,
+                            self.sim_delta_time,
+                            step_gpu_time_ms,
+                        )
+                    } else {
+                        self.scheduler.reset_leftover();
+                        single_step as u32
+                    };
+                    let sim_params = self
+                        .boid_settings
+                        .sim_params(self.num_particles, self.sim_delta_time * self.sim_speed);
+                    let mut render_params = boids::RenderParams {
+                        camera_center: self.camera.center,
+                        camera_zoom: self.camera.zoom,
+                        selected_index: self.inspector.selected().unwrap_or(boids::NO_SELECTION),
+                        num_particles: self.num_particles,
+                        sprite_size: self.sprite_settings.sprite_size,
+                        clip_scale: [1., 1.],
+                        ..bytemuck::Zeroable::zeroed()
+                    };
+                    self.color_settings.apply(&mut render_params);
+
+                    let callback = RenderCallback {
+                        frame: FrameInput {
                             sim_params,
-                            num_sim_updates,
+                            num_steps: num_sim_updates,
+                            max_steps: self.scheduler.max_steps(step_gpu_time_ms),
+                            compute_stats: self.flock_stats.open,
+                            render_params,
+                            pick_position,
+                            inspect_index: self.inspector.selected(),
+                            emitters: self.emitter_settings.emit_params(),
+                            kernel: self.kernel_settings.kernel(),
+                            workgroup_size: self.kernel_settings.workgroup_size(),
                         },
-                    ));
+                        color_lut: self.color_settings.take_changed_lut(),
+                        draw_options: self.debug_view.supported(wireframe_supported).draw_options(
+                            self.sprite_settings.draw_sprites(),
+                            self.sprite_settings.draw_background(),
+                        ),
+                    };
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(render_state) = render_state {
+                        let canvas_size = (rect.size() * ui.ctx().pixels_per_point()).round();
+                        self.frame_export.update(
+                            render_state,
+                            [canvas_size.x as u32, canvas_size.y as u32],
+                            callback.frame.render_params,
+                            callback.draw_options,
+                            ui.visuals().extreme_bg_color,
+                        );
+                        self.recorder.update(
+                            render_state,
+                            callback.frame.render_params,
+                            callback.draw_options,
+                            ui.visuals().extreme_bg_color,
+                        );
+                    }
+                    match scene_target {
+                        SceneTarget::Egui => {
+                            ui.painter()
+                                .add(eframe::egui_wgpu::Callback::new_paint_callback(
+                                    rect, callback,
+                                ));
+                        }
+                        SceneTarget::Frontend => scene = Some((rect, callback)),
+                    }
+                    self.inspector.paint_overlay(
+                        &ui.painter_at(rect),
+                        rect,
+                        &self.camera,
+                        &self.boid_settings,
+                    );
+                });
             });
-        });
 
-        if self.is_playing {
+        self.flock_stats.show(ctx);
+        self.inspector.show(ctx);
+        self.profiler.show(ctx);
+        self.log_console.show(ctx);
+        let current_values = Property::ALL.map(|property| property.get(self));
+        self.timeline.show(ctx, &current_values);
+        self.show_state_notice(ctx);
+        let restart = self.diagnostics.show(ctx, render_state);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let recording = self.recorder.recording_steps().is_some();
+        #[cfg(target_arch = "wasm32")]
+        let recording = false;
+        if self.is_playing || recording || self.inspector.needs_repaint() {
             ctx.request_repaint();
         }
+        UiOutput { scene, restart }
     }
 }
@@ -0,0 +1,133 @@
+use egui::Widget;
+
+use crate::shaders::boids;
+
+/// How `boids_cs` advances each boid by one step.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum Integrator {
+    ExplicitEuler,
+    SemiImplicitEuler,
+    VelocityVerlet,
+    Rk2,
+}
+
+impl Integrator {
+    pub const ALL: [Self; 4] = [
+        Self::ExplicitEuler,
+        Self::SemiImplicitEuler,
+        Self::VelocityVerlet,
+        Self::Rk2,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ExplicitEuler => "Explicit Euler",
+            Self::SemiImplicitEuler => "Semi-implicit Euler",
+            Self::VelocityVerlet => "Velocity Verlet",
+            Self::Rk2 => "RK2 (midpoint)",
+        }
+    }
+
+    fn shader_value(self) -> u32 {
+        match self {
+            Self::ExplicitEuler => boids::INTEGRATOR_EXPLICIT_EULER,
+            Self::SemiImplicitEuler => boids::INTEGRATOR_SEMI_IMPLICIT_EULER,
+            Self::VelocityVerlet => boids::INTEGRATOR_VELOCITY_VERLET,
+            Self::Rk2 => boids::INTEGRATOR_RK2,
+        }
+    }
+}
+
+/// The flocking rules. The steering scales are accelerations, so they don't depend on the size of the time step.
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BoidSettings {
+    pub separation_distance: f32,
+    pub separation_scale: f32,
+    pub alignment_distance: f32,
+    pub alignment_scale: f32,
+    pub cohesion_distance: f32,
+    pub cohesion_scale: f32,
+    pub integrator: Integrator,
+    pub damping: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+}
+
+impl Default for BoidSettings {
+    fn default() -> Self {
+        // These used to be velocity changes applied once per step at the default 120Hz, hence the multiplication
+        Self {
+            separation_distance: 0.025,
+            separation_scale: 0.05 * 120.,
+            alignment_distance: 0.025,
+            alignment_scale: 0.005 * 120.,
+            cohesion_distance: 0.1,
+            cohesion_scale: 0.02 * 120.,
+            integrator: Integrator::SemiImplicitEuler,
+            damping: 0.,
+            min_speed: 0.,
+            max_speed: 0.1,
+        }
+    }
+}
+
+impl BoidSettings {
+    pub fn sim_params(&self, num_particles: u32, delta_time: f32) -> boids::SimParams {
+        boids::SimParams {
+            num_particles,
+            delta_time,
+            separation_distance: self.separation_distance,
+            alignment_distance: self.alignment_distance,
+            cohesion_distance: self.cohesion_distance,
+            separation_scale: self.separation_scale,
+            alignment_scale: self.alignment_scale,
+            cohesion_scale: self.cohesion_scale,
+            integrator: self.integrator.shader_value(),
+            damping: self.damping,
+            // the shaders clamp the speed between the two, which isn't defined the other way around, and the settings
+            // can also come from saved state, a preset or the timeline
+            min_speed: self.min_speed.min(self.max_speed),
+            max_speed: self.max_speed,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Integrator")
+            .selected_text(self.integrator.label())
+            .show_ui(ui, |ui| {
+                for integrator in Integrator::ALL {
+                    ui.selectable_value(&mut self.integrator, integrator, integrator.label());
+                }
+            });
+
+        egui::Slider::new(&mut self.separation_distance, 0. ..=0.2)
+            .text("Separation Distance")
+            .ui(ui);
+        egui::Slider::new(&mut self.separation_scale, 0. ..=20.)
+            .text("Separation Acceleration")
+            .ui(ui);
+        egui::Slider::new(&mut self.alignment_distance, 0. ..=0.2)
+            .text("Alignment Distance")
+            .ui(ui);
+        egui::Slider::new(&mut self.alignment_scale, 0. ..=5.)
+            .text("Alignment Acceleration")
+            .ui(ui);
+        egui::Slider::new(&mut self.cohesion_distance, 0. ..=0.2)
+            .text("Cohesion Distance")
+            .ui(ui);
+        egui::Slider::new(&mut self.cohesion_scale, 0. ..=10.)
+            .text("Cohesion Acceleration")
+            .ui(ui);
+
+        egui::Slider::new(&mut self.damping, 0. ..=10.)
+            .text("Damping")
+            .ui(ui);
+        egui::Slider::new(&mut self.min_speed, 0. ..=self.max_speed)
+            .text("Min Speed")
+            .ui(ui);
+        egui::Slider::new(&mut self.max_speed, 0. ..=1.)
+            .text("Max Speed")
+            .ui(ui);
+    }
+}
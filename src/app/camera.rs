@@ -0,0 +1,60 @@
+/// Which part of the simulation is visible on the canvas. At a zoom of 1 the whole [-1, 1] box fills the canvas.
+#[derive(Clone, Copy, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Camera {
+    pub center: [f32; 2],
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            center: [0., 0.],
+            zoom: 1.,
+        }
+    }
+}
+
+impl Camera {
+    pub const MIN_ZOOM: f32 = 0.1;
+    pub const MAX_ZOOM: f32 = 100.;
+
+    /// How many points on the canvas one unit of the simulation covers.
+    pub fn points_per_unit(&self, rect: egui::Rect) -> f32 {
+        0.5 * rect.width() * self.zoom
+    }
+
+    pub fn screen_to_world(&self, rect: egui::Rect, pos: egui::Pos2) -> [f32; 2] {
+        let offset = (pos - rect.center()) / self.points_per_unit(rect);
+        [self.center[0] + offset.x, self.center[1] - offset.y]
+    }
+
+    pub fn world_to_screen(&self, rect: egui::Rect, pos: [f32; 2]) -> egui::Pos2 {
+        let offset = egui::vec2(pos[0] - self.center[0], self.center[1] - pos[1]);
+        rect.center() + offset * self.points_per_unit(rect)
+    }
+
+    /// Pans by dragging, and zooms towards the cursor with the scroll wheel or a pinch gesture.
+    pub fn handle_input(&mut self, rect: egui::Rect, response: &egui::Response) {
+        if response.dragged() {
+            let delta = response.drag_delta() / self.points_per_unit(rect);
+            self.center[0] -= delta.x;
+            self.center[1] += delta.y;
+        }
+
+        if let Some(hover_pos) = response.hover_pos() {
+            let (zoom_delta, scroll_delta) = response
+                .ctx
+                .input(|input| (input.zoom_delta(), input.smooth_scroll_delta.y));
+            let factor = zoom_delta * (scroll_delta * 0.002).exp();
+            if factor != 1. {
+                // keep the point under the cursor in place
+                let before = self.screen_to_world(rect, hover_pos);
+                self.zoom = (self.zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+                let after = self.screen_to_world(rect, hover_pos);
+                self.center[0] += before[0] - after[0];
+                self.center[1] += before[1] - after[1];
+            }
+        }
+    }
+}
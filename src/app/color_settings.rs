@@ -0,0 +1,150 @@
+//! What boids are colored by, and with which gradient.
+
+use egui::Widget;
+
+use crate::{
+    app::palette::{Gradient, Palette},
+    shaders::boids,
+};
+
+/// The value of each boid that is mapped through the gradient.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum ColorSource {
+    Heading,
+    Speed,
+    /// Boids per unit area, estimated from the distance to the nearest neighbor
+    Density,
+    NeighborCount,
+    /// Boids are split into species by their index
+    Species,
+    Index,
+}
+
+impl ColorSource {
+    pub const ALL: [Self; 6] = [
+        Self::Heading,
+        Self::Speed,
+        Self::Density,
+        Self::NeighborCount,
+        Self::Species,
+        Self::Index,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Heading => "Heading",
+            Self::Speed => "Speed",
+            Self::Density => "Local density",
+            Self::NeighborCount => "Neighbor count",
+            Self::Species => "Species",
+            Self::Index => "Particle index",
+        }
+    }
+
+    pub fn shader_value(self) -> u32 {
+        match self {
+            Self::Heading => boids::COLOR_SOURCE_HEADING,
+            Self::Speed => boids::COLOR_SOURCE_SPEED,
+            Self::Density => boids::COLOR_SOURCE_DENSITY,
+            Self::NeighborCount => boids::COLOR_SOURCE_NEIGHBOR_COUNT,
+            Self::Species => boids::COLOR_SOURCE_SPECIES,
+            Self::Index => boids::COLOR_SOURCE_INDEX,
+        }
+    }
+
+    /// The range of values that is stretched over the gradient, if the source isn't already normalized.
+    fn default_range(self) -> Option<[f32; 2]> {
+        match self {
+            Self::Speed => Some([0., 0.1]),
+            Self::Density => Some([0., 20_000.]),
+            Self::NeighborCount => Some([0., 50.]),
+            Self::Heading | Self::Species | Self::Index => None,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ColorSettings {
+    pub source: ColorSource,
+    /// Values mapped to the start and end of the gradient, for sources that have a range
+    pub range: [f32; 2],
+    pub num_species: u32,
+    pub gradient: Gradient,
+    /// Whether the gradient changed since it was last uploaded
+    #[serde(skip)]
+    gradient_dirty: bool,
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        Self {
+            source: ColorSource::Heading,
+            range: [0., 1.],
+            num_species: 3,
+            gradient: Gradient::default(),
+            gradient_dirty: true,
+        }
+    }
+}
+
+impl ColorSettings {
+    /// Fills in the color fields of `render_params`.
+    pub fn apply(&self, render_params: &mut boids::RenderParams) {
+        render_params.color_source = self.source.shader_value();
+        [render_params.color_range_min, render_params.color_range_max] = self.range;
+        render_params.num_species = self.num_species;
+    }
+
+    /// The lookup texture data, if the gradient changed since the last call.
+    pub fn take_changed_lut(&mut self) -> Option<Vec<[u8; 4]>> {
+        std::mem::take(&mut self.gradient_dirty).then(|| self.gradient.bake())
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Color by")
+            .selected_text(self.source.label())
+            .show_ui(ui, |ui| {
+                for source in ColorSource::ALL {
+                    if ui
+                        .selectable_value(&mut self.source, source, source.label())
+                        .changed()
+                    {
+                        if let Some(range) = source.default_range() {
+                            self.range = range;
+                        }
+                    }
+                }
+            });
+
+        if let Some(default_range) = self.source.default_range() {
+            let speed = (default_range[1] - default_range[0]) / 200.;
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.range[0]).speed(speed));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut self.range[1]).speed(speed));
+                if ui.button("Reset").clicked() {
+                    self.range = default_range;
+                }
+            });
+        }
+        if self.source == ColorSource::Species {
+            egui::Slider::new(&mut self.num_species, 1..=16)
+                .text("Number of species")
+                .ui(ui);
+        }
+
+        ui.separator();
+        egui::ComboBox::from_label("Palette")
+            .selected_text("Load palette…")
+            .show_ui(ui, |ui| {
+                for palette in Palette::ALL {
+                    if ui.selectable_label(false, palette.label()).clicked() {
+                        self.gradient = palette.gradient();
+                        self.gradient_dirty = true;
+                    }
+                }
+            });
+        self.gradient_dirty |= self.gradient.editor_ui(ui);
+    }
+}
@@ -0,0 +1,68 @@
+//! Files that are saved into the data directory on request, like presets, snapshots, images and recordings.
+//!
+//! Every kind of file has its own directory, and the files are named after the time they were saved at. Files that are
+//! saved within the same second get a number after the time, so that none of them replaces another one.
+
+use std::{fs::File, io::ErrorKind, path::PathBuf};
+
+use super::APP_ID;
+
+/// Creates a new file in the `kind` directory, named after the current time and `tag`, like `1700000000-cpu.puffin`
+/// for the tag `-cpu` and the extension `puffin`.
+pub fn create_file(kind: &str, tag: &str, extension: &str) -> Result<(PathBuf, File), String> {
+    let [file] = create_files(kind, tag, [extension])?;
+    Ok(file)
+}
+
+/// Creates new files that only differ in their extensions, like [`create_file`] does. Either all of them are created,
+/// or none.
+pub fn create_files<const N: usize>(
+    kind: &str,
+    tag: &str,
+    extensions: [&str; N],
+) -> Result<[(PathBuf, File); N], String> {
+    let dir = eframe::storage_dir(APP_ID)
+        .ok_or("there is no data directory")?
+        .join(kind);
+    std::fs::create_dir_all(&dir)
+        .map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+    let unix_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    for attempt in 0u32.. {
+        let name = match attempt {
+            0 => format!("{unix_time}{tag}"),
+            _ => format!("{unix_time}{tag}-{attempt}"),
+        };
+        let mut files = Vec::with_capacity(N);
+        for extension in extensions {
+            let path = dir.join(format!("{name}.{extension}"));
+            match File::create_new(&path) {
+                Ok(file) => files.push((path, file)),
+                // taken by a file that was saved before, so try the next name
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => break,
+                Err(err) => {
+                    remove(files);
+                    return Err(format!("failed to create {}: {err}", path.display()));
+                }
+            }
+        }
+        match files.try_into() {
+            Ok(files) => return Ok(files),
+            Err(files) => remove(files),
+        }
+    }
+    Err(format!("every name is taken in {}", dir.display()))
+}
+
+/// Removes files that were created for a name that turned out to be taken.
+fn remove(files: Vec<(PathBuf, File)>) {
+    for (path, file) in files {
+        drop(file);
+        if let Err(err) = std::fs::remove_file(&path) {
+            log::warn!("Failed to remove {}: {err}", path.display());
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::sim::{BoidStyle, DrawOptions, Overlay};
+
+/// Alternative ways of drawing the boids that help with debugging the simulation and rendering.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub enum DebugView {
+    #[default]
+    Filled,
+    /// The outlines of the boids' triangles, only if the device supports `POLYGON_MODE_LINE`
+    Wireframe,
+    VelocityVectors,
+    /// Separation, alignment and cohesion distances around every boid
+    PerceptionRadii,
+}
+
+impl DebugView {
+    pub const ALL: [Self; 4] = [
+        Self::Filled,
+        Self::Wireframe,
+        Self::VelocityVectors,
+        Self::PerceptionRadii,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Filled => "Filled",
+            Self::Wireframe => "Wireframe",
+            Self::VelocityVectors => "Velocity vectors",
+            Self::PerceptionRadii => "Perception radii",
+        }
+    }
+
+    /// The view that is actually drawn, falling back to filled boids if wireframes aren't supported.
+    pub fn supported(self, wireframe_supported: bool) -> Self {
+        if self == Self::Wireframe && !wireframe_supported {
+            Self::Filled
+        } else {
+            self
+        }
+    }
+
+    /// How [`crate::sim::ParticleSystem::draw`] draws this view. Wireframes take precedence over sprites.
+    pub fn draw_options(self, draw_sprites: bool, draw_background: bool) -> DrawOptions {
+        let style = match self {
+            Self::Wireframe => BoidStyle::Wireframe,
+            _ if draw_sprites => BoidStyle::Sprites,
+            _ => BoidStyle::Triangles,
+        };
+        let overlay = match self {
+            Self::VelocityVectors => Overlay::VelocityVectors,
+            Self::PerceptionRadii => Overlay::PerceptionRadii,
+            Self::Filled | Self::Wireframe => Overlay::None,
+        };
+        DrawOptions {
+            style,
+            background: draw_background,
+            overlay,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, wireframe_supported: bool) {
+        let supported = self.supported(wireframe_supported);
+        egui::ComboBox::from_label("Debug view")
+            .selected_text(supported.label())
+            .show_ui(ui, |ui| {
+                for view in Self::ALL {
+                    if view.supported(wireframe_supported) == view {
+                        ui.selectable_value(self, view, view.label());
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,310 @@
+//! How the wgpu device is created. Optional features are only requested if the adapter offers them, and everything
+//! that depends on them is turned off otherwise.
+
+use std::sync::Arc;
+
+use eframe::egui_wgpu::WgpuConfiguration;
+
+/// Features that are used if available, but aren't needed to run.
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_BC)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC)
+    .union(wgpu::Features::PIPELINE_CACHE)
+    .union(wgpu_profiler::GpuProfiler::ALL_WGPU_TIMER_FEATURES);
+
+/// What an optional feature is used for.
+fn feature_use(feature: wgpu::Features) -> &'static str {
+    match feature {
+        wgpu::Features::POLYGON_MODE_LINE => "Wireframe debug view",
+        wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES => {
+            "Adapter-specific texture formats"
+        }
+        wgpu::Features::TEXTURE_COMPRESSION_BC => "BC compressed textures",
+        wgpu::Features::TEXTURE_COMPRESSION_ETC2 => "ETC2 compressed textures",
+        wgpu::Features::TEXTURE_COMPRESSION_ASTC => "ASTC compressed textures",
+        wgpu::Features::PIPELINE_CACHE => "Keeping compiled pipelines between runs",
+        wgpu::Features::TIMESTAMP_QUERY => "GPU profiling and the scheduler's GPU budget",
+        wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS => "GPU profiling of whole encoders",
+        wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES => "GPU profiling inside of passes",
+        _ => "",
+    }
+}
+
+/// A backend to use instead of letting wgpu choose one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(clap::ValueEnum))]
+pub enum ForcedBackend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+    /// A CPU implementation of one of the other backends, like lavapipe, llvmpipe or WARP
+    Software,
+}
+
+impl ForcedBackend {
+    pub const ALL: [Self; 5] = [
+        Self::Vulkan,
+        Self::Metal,
+        Self::Dx12,
+        Self::Gl,
+        Self::Software,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Vulkan => "Vulkan",
+            Self::Metal => "Metal",
+            Self::Dx12 => "DirectX 12",
+            Self::Gl => "OpenGL",
+            Self::Software => "Software",
+        }
+    }
+
+    /// The name of the value on the command line.
+    pub fn arg(self) -> &'static str {
+        match self {
+            Self::Vulkan => "vulkan",
+            Self::Metal => "metal",
+            Self::Dx12 => "dx12",
+            Self::Gl => "gl",
+            Self::Software => "software",
+        }
+    }
+
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            Self::Vulkan => wgpu::Backends::VULKAN,
+            Self::Metal => wgpu::Backends::METAL,
+            Self::Dx12 => wgpu::Backends::DX12,
+            Self::Gl => wgpu::Backends::GL,
+            Self::Software => wgpu::Backends::all(),
+        }
+    }
+
+    fn matches(self, info: &wgpu::AdapterInfo) -> bool {
+        match self {
+            Self::Software => info.device_type == wgpu::DeviceType::Cpu,
+            _ => self.backends().contains(info.backend.into()),
+        }
+    }
+}
+
+/// Which adapter to run on. Chosen on the command line, or in the diagnostics window, which restarts the app with it.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct AdapterSelection {
+    pub backend: Option<ForcedBackend>,
+    /// Part of the adapter's name, ignoring case
+    pub adapter: Option<String>,
+}
+
+impl AdapterSelection {
+    /// Runs on exactly this adapter.
+    pub fn for_adapter(info: &wgpu::AdapterInfo) -> Self {
+        Self {
+            backend: ForcedBackend::ALL
+                .into_iter()
+                .find(|backend| *backend != ForcedBackend::Software && backend.matches(info)),
+            adapter: Some(info.name.clone()),
+        }
+    }
+
+    pub fn matches(&self, info: &wgpu::AdapterInfo) -> bool {
+        self.backend.is_none_or(|backend| backend.matches(info))
+            && self
+                .adapter
+                .as_ref()
+                .is_none_or(|name| info.name.to_lowercase().contains(&name.to_lowercase()))
+    }
+
+    /// Command line arguments that make the app run on this adapter again.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(backend) = self.backend {
+            args.extend(["--backend".to_owned(), backend.arg().to_owned()]);
+        }
+        if let Some(adapter) = &self.adapter {
+            args.extend(["--adapter".to_owned(), adapter.clone()]);
+        }
+        args
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.backend, &self.adapter) {
+            (None, None) => "automatic".to_owned(),
+            (Some(backend), None) => backend.label().to_owned(),
+            (None, Some(adapter)) => format!("\"{adapter}\""),
+            (Some(backend), Some(adapter)) => format!("\"{adapter}\" on {}", backend.label()),
+        }
+    }
+}
+
+/// Every adapter wgpu can find on any backend. Adapters can't be enumerated on the web.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn enumerate_adapters() -> Vec<wgpu::AdapterInfo> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .iter()
+        .map(|adapter| adapter.get_info())
+        .collect()
+}
+
+/// The limits the device is requested with on `adapter`.
+pub fn required_limits(adapter: &wgpu::AdapterInfo) -> wgpu::Limits {
+    let base_limits = if adapter.backend == wgpu::Backend::Gl {
+        wgpu::Limits::downlevel_webgl2_defaults()
+    } else {
+        wgpu::Limits::default()
+    };
+    wgpu::Limits {
+        // When using a depth buffer, we have to be able to create a texture
+        // large enough for the entire surface, and we want to support 4k+ displays.
+        max_texture_dimension_2d: 8192,
+        ..base_limits
+    }
+}
+
+/// eframe asks wgpu for an adapter instead of taking one from us, so a specific adapter is chosen by only allowing its
+/// backend, and preferring its kind of GPU. That picks the right one unless a backend has several adapters of the same
+/// kind, which is warned about once the device is created.
+pub fn get_wgpu_options(selection: &AdapterSelection) -> WgpuConfiguration {
+    let mut supported_backends = selection
+        .backend
+        .map_or(wgpu::Backends::all(), ForcedBackend::backends);
+    let mut power_preference = wgpu::PowerPreference::default();
+    #[cfg(not(target_arch = "wasm32"))]
+    if *selection != AdapterSelection::default() {
+        match enumerate_adapters()
+            .into_iter()
+            .find(|info| selection.matches(info))
+        {
+            Some(info) => {
+                supported_backends = info.backend.into();
+                power_preference = match info.device_type {
+                    wgpu::DeviceType::DiscreteGpu => wgpu::PowerPreference::HighPerformance,
+                    wgpu::DeviceType::IntegratedGpu => wgpu::PowerPreference::LowPower,
+                    _ => wgpu::PowerPreference::None,
+                };
+            }
+            None => log::warn!(
+                "No adapter matches {}, letting wgpu choose one",
+                selection.describe()
+            ),
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    if selection.backend == Some(ForcedBackend::Gl) {
+        supported_backends = wgpu::Backends::GL;
+    }
+
+    let selection = selection.clone();
+    WgpuConfiguration {
+        supported_backends,
+        power_preference,
+        device_descriptor: Arc::new(move |adapter| {
+            let info = adapter.get_info();
+            if !selection.matches(&info) {
+                log::warn!(
+                    "Running on {} ({:?}) instead of the requested adapter {}",
+                    info.name,
+                    info.backend,
+                    selection.describe()
+                );
+            }
+            let missing_features = OPTIONAL_FEATURES - adapter.features();
+            if !missing_features.is_empty() {
+                log::warn!(
+                    "The adapter doesn't support these optional features: {missing_features:?}"
+                );
+            }
+            wgpu::DeviceDescriptor {
+                label: Some("egui wgpu device"),
+                required_features: adapter.features() & OPTIONAL_FEATURES,
+                required_limits: required_limits(&info),
+                ..Default::default()
+            }
+        }),
+        ..Default::default()
+    }
+}
+
+/// Lists the optional features and whether the device was created with them.
+pub fn features_ui(ui: &mut egui::Ui, device_features: wgpu::Features) {
+    egui::Grid::new("optional features")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (name, feature) in OPTIONAL_FEATURES.iter_names() {
+                if device_features.contains(feature) {
+                    ui.label("✔");
+                } else {
+                    ui.colored_label(ui.visuals().warn_fg_color, "✖");
+                }
+                ui.label(name).on_hover_text(feature_use(feature));
+                ui.end_row();
+            }
+        });
+}
+
+/// Options on the command line that the restarted app doesn't get again, and whether they take a value after them.
+/// These are the adapter, which is replaced, and the overrides of the saved state, since the state this instance saves
+/// before restarting is what should carry over.
+#[cfg(not(target_arch = "wasm32"))]
+const RESTART_DROPPED_ARGS: [(&str, bool); 9] = [
+    ("--backend", true),
+    ("--adapter", true),
+    ("--sim", true),
+    ("--paused", false),
+    ("--num-particles", true),
+    ("--seed", true),
+    ("--delta-time", true),
+    ("--preset", true),
+    ("--snapshot", true),
+];
+
+/// The command line of this instance without [`RESTART_DROPPED_ARGS`], so that the frontend, the config file, the
+/// window and the profiler servers stay the same.
+#[cfg(not(target_arch = "wasm32"))]
+fn restart_args(args: impl IntoIterator<Item = std::ffi::OsString>) -> Vec<std::ffi::OsString> {
+    let mut kept = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let dropped = arg.to_str().and_then(|arg| {
+            RESTART_DROPPED_ARGS
+                .iter()
+                .find_map(|&(name, takes_value)| {
+                    if arg == name {
+                        Some(takes_value)
+                    } else {
+                        // the value is part of the argument
+                        arg.strip_prefix(name)
+                            .is_some_and(|rest| rest.starts_with('='))
+                            .then_some(false)
+                    }
+                })
+        });
+        match dropped {
+            Some(true) => _ = args.next(),
+            Some(false) => {}
+            None => kept.push(arg),
+        }
+    }
+    kept
+}
+
+/// Starts another instance of the app on `selection`, with the same command line otherwise. The caller has to close
+/// this one.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn restart(selection: &AdapterSelection) -> std::io::Result<()> {
+    std::process::Command::new(std::env::current_exe()?)
+        .args(restart_args(std::env::args_os().skip(1)))
+        .args(selection.to_args())
+        .spawn()?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Shows which adapter the app runs on, which other adapters there are, and what the device was created with, so
+//! issues can be reproduced on a specific adapter.
+
+use eframe::egui_wgpu::RenderState;
+
+use crate::app::device::{self, AdapterSelection, ForcedBackend, OPTIONAL_FEATURES};
+
+struct Limit {
+    name: &'static str,
+    value: fn(&wgpu::Limits) -> u64,
+}
+
+macro_rules! limits {
+    ($($field:ident),* $(,)?) => {
+        &[$(Limit {
+            name: stringify!($field),
+            value: |limits| u64::from(limits.$field),
+        }),*]
+    };
+}
+
+/// Every limit there is.
+const LIMITS: &[Limit] = limits!(
+    max_texture_dimension_1d,
+    max_texture_dimension_2d,
+    max_texture_dimension_3d,
+    max_texture_array_layers,
+    max_bind_groups,
+    max_bindings_per_bind_group,
+    max_dynamic_uniform_buffers_per_pipeline_layout,
+    max_dynamic_storage_buffers_per_pipeline_layout,
+    max_sampled_textures_per_shader_stage,
+    max_samplers_per_shader_stage,
+    max_storage_buffers_per_shader_stage,
+    max_storage_textures_per_shader_stage,
+    max_uniform_buffers_per_shader_stage,
+    max_uniform_buffer_binding_size,
+    max_storage_buffer_binding_size,
+    max_vertex_buffers,
+    max_buffer_size,
+    max_vertex_attributes,
+    max_vertex_buffer_array_stride,
+    min_uniform_buffer_offset_alignment,
+    min_storage_buffer_offset_alignment,
+    max_inter_stage_shader_components,
+    max_color_attachments,
+    max_color_attachment_bytes_per_sample,
+    max_compute_workgroup_storage_size,
+    max_compute_invocations_per_workgroup,
+    max_compute_workgroup_size_x,
+    max_compute_workgroup_size_y,
+    max_compute_workgroup_size_z,
+    max_compute_workgroups_per_dimension,
+    min_subgroup_size,
+    max_subgroup_size,
+    max_push_constant_size,
+    max_non_sampler_bindings,
+);
+
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct DiagnosticsWindow {
+    pub open: bool,
+    /// The adapter the app was asked to run on when it started
+    #[serde(skip)]
+    pub selection: AdapterSelection,
+    /// Why the last restart failed
+    #[serde(skip)]
+    pub error: Option<String>,
+    #[serde(skip)]
+    forced_backend: Option<ForcedBackend>,
+    /// Enumerated the first time the window is shown, since that creates another wgpu instance
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    adapters: Option<Vec<wgpu::AdapterInfo>>,
+}
+
+fn check_mark(ui: &mut egui::Ui, supported: bool) {
+    if supported {
+        ui.label("✔");
+    } else {
+        ui.colored_label(ui.visuals().warn_fg_color, "✖");
+    }
+}
+
+fn adapter_row(ui: &mut egui::Ui, info: &wgpu::AdapterInfo) {
+    ui.label(&info.name);
+    ui.label(format!("{:?}", info.backend));
+    ui.label(format!("{:?}", info.device_type));
+    ui.label(
+        format!("{} {}", info.driver, info.driver_info)
+            .trim()
+            .to_owned(),
+    );
+}
+
+impl DiagnosticsWindow {
+    /// Shows the window. Returns the adapter to restart on, if one was chosen.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        render_state: Option<&RenderState>,
+    ) -> Option<AdapterSelection> {
+        let mut open = self.open;
+        let mut restart = None;
+        egui::Window::new("GPU Diagnostics")
+            .open(&mut open)
+            .default_width(480.)
+            .vscroll(true)
+            .show(ctx, |ui| restart = self.ui(ui, render_state));
+        self.open = open;
+        restart
+    }
+
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        render_state: Option<&RenderState>,
+    ) -> Option<AdapterSelection> {
+        let mut restart = None;
+        let Some(render_state) = render_state else {
+            ui.label("Not running on wgpu");
+            return None;
+        };
+        let current = render_state.adapter.get_info();
+
+        ui.heading("Current adapter");
+        egui::Grid::new("current adapter")
+            .num_columns(4)
+            .show(ui, |ui| adapter_row(ui, &current));
+        ui.label(format!("Requested: {}", self.selection.describe()));
+        if !self.selection.matches(&current) {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "wgpu chose a different adapter than the requested one, since there is no way to ask for a specific \
+                 one. It only picks from the requested backend, preferring the same kind of GPU.",
+            );
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ui.separator();
+            ui.heading("Available adapters");
+            let adapters = self.adapters.get_or_insert_with(device::enumerate_adapters);
+            egui::Grid::new("adapters")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    for info in adapters.iter() {
+                        adapter_row(ui, info);
+                        if ui
+                            .add_enabled(*info != current, egui::Button::new("Restart on this"))
+                            .clicked()
+                        {
+                            restart = Some(AdapterSelection::for_adapter(info));
+                        }
+                        ui.end_row();
+                    }
+                });
+            if ui.button("Refresh").clicked() {
+                self.adapters = None;
+            }
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Backend")
+                    .selected_text(
+                        self.forced_backend
+                            .map_or("Automatic", ForcedBackend::label),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.forced_backend, None, "Automatic");
+                        for backend in ForcedBackend::ALL {
+                            ui.selectable_value(
+                                &mut self.forced_backend,
+                                Some(backend),
+                                backend.label(),
+                            );
+                        }
+                    });
+                if ui.button("Restart").clicked() {
+                    restart = Some(AdapterSelection {
+                        backend: self.forced_backend,
+                        adapter: None,
+                    });
+                }
+            });
+            if let Some(error) = &self.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        ui.label("The browser chooses the adapter, so it can't be changed here.");
+
+        ui.separator();
+        let requested_limits = device::required_limits(&current);
+        let adapter_limits = render_state.adapter.limits();
+        let device_limits = render_state.device.limits();
+        ui.collapsing("Limits", |ui| {
+            egui::Grid::new("limits")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Limit");
+                    ui.strong("Requested");
+                    ui.strong("Device");
+                    ui.strong("Adapter");
+                    ui.end_row();
+                    for limit in LIMITS {
+                        let device_limit = (limit.value)(&device_limits);
+                        ui.label(limit.name);
+                        ui.label((limit.value)(&requested_limits).to_string());
+                        ui.label(device_limit.to_string());
+                        let adapter_limit = (limit.value)(&adapter_limits);
+                        // the device is only created with what was requested, so this is what it could have had
+                        if adapter_limit == device_limit {
+                            ui.label(adapter_limit.to_string());
+                        } else {
+                            ui.strong(adapter_limit.to_string());
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+
+        let adapter_features = render_state.adapter.features();
+        let device_features = render_state.device.features();
+        ui.collapsing("Features", |ui| {
+            egui::Grid::new("features")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Feature");
+                    ui.strong("Device");
+                    ui.strong("Adapter");
+                    ui.end_row();
+                    for (name, feature) in OPTIONAL_FEATURES.iter_names() {
+                        ui.label(name);
+                        check_mark(ui, device_features.contains(feature));
+                        // optional features are requested whenever the adapter has them
+                        check_mark(ui, adapter_features.contains(feature));
+                        ui.end_row();
+                    }
+                });
+            let other_features = adapter_features - OPTIONAL_FEATURES;
+            if !other_features.is_empty() {
+                ui.label(format!(
+                    "Not requested, since nothing uses them: {other_features:?}"
+                ));
+            }
+        });
+
+        restart
+    }
+}
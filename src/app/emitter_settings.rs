@@ -0,0 +1,219 @@
+//! Emitters that spawn boids with a limited lifetime, instead of a fixed flock that lives forever.
+
+use egui::Widget;
+
+use crate::shaders::boids;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum EmitterShape {
+    Point,
+    Line,
+    /// An axis-aligned rectangle
+    Area,
+}
+
+impl EmitterShape {
+    pub const ALL: [Self; 3] = [Self::Point, Self::Line, Self::Area];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Point => "Point",
+            Self::Line => "Line",
+            Self::Area => "Area",
+        }
+    }
+
+    fn shader_value(self) -> u32 {
+        match self {
+            Self::Point => boids::EMITTER_POINT,
+            Self::Line => boids::EMITTER_LINE,
+            Self::Area => boids::EMITTER_AREA,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Emitter {
+    pub shape: EmitterShape,
+    /// The point, the start of the line or a corner of the area
+    pub start: [f32; 2],
+    /// The end of the line or the opposite corner of the area
+    pub end: [f32; 2],
+    /// Boids per second
+    pub rate: f32,
+    /// Seconds until an emitted boid dies
+    pub lifetime: f32,
+    pub speed: f32,
+    /// Counterclockwise from the x axis
+    pub direction_degrees: f32,
+    /// New boids fly off up to half of this angle to either side of the direction
+    pub spread_degrees: f32,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            shape: EmitterShape::Point,
+            start: [0., 0.],
+            end: [0.5, 0.5],
+            rate: 1000.,
+            lifetime: 10.,
+            speed: 0.1,
+            direction_degrees: 90.,
+            spread_degrees: 360.,
+        }
+    }
+}
+
+impl Emitter {
+    fn shader_value(&self) -> boids::Emitter {
+        boids::Emitter {
+            start: self.start,
+            end: self.end,
+            shape: self.shape.shader_value(),
+            rate: self.rate,
+            lifetime: self.lifetime,
+            speed: self.speed,
+            direction: self.direction_degrees.to_radians(),
+            spread: self.spread_degrees.to_radians(),
+            _padding: [0.; 2],
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Shape")
+            .selected_text(self.shape.label())
+            .show_ui(ui, |ui| {
+                for shape in EmitterShape::ALL {
+                    ui.selectable_value(&mut self.shape, shape, shape.label());
+                }
+            });
+        let (start_label, end_label) = match self.shape {
+            EmitterShape::Point => ("Position", None),
+            EmitterShape::Line => ("Start", Some("End")),
+            EmitterShape::Area => ("Corner", Some("Opposite Corner")),
+        };
+        position_ui(ui, &mut self.start, start_label);
+        if let Some(end_label) = end_label {
+            position_ui(ui, &mut self.end, end_label);
+        }
+
+        egui::Slider::new(&mut self.rate, 1. ..=100_000.)
+            .logarithmic(true)
+            .text("Boids per Second")
+            .ui(ui);
+        egui::Slider::new(&mut self.lifetime, 0.1..=60.)
+            .logarithmic(true)
+            .text("Lifetime (s)")
+            .ui(ui);
+        egui::Slider::new(&mut self.speed, 0. ..=1.)
+            .text("Speed")
+            .ui(ui);
+        egui::Slider::new(&mut self.direction_degrees, -180. ..=180.)
+            .text("Direction (°)")
+            .ui(ui);
+        egui::Slider::new(&mut self.spread_degrees, 0. ..=360.)
+            .text("Spread (°)")
+            .ui(ui);
+    }
+}
+
+fn position_ui(ui: &mut egui::Ui, position: &mut [f32; 2], label: &str) {
+    ui.horizontal(|ui| {
+        egui::DragValue::new(&mut position[0])
+            .speed(0.01)
+            .range(-1. ..=1.)
+            .prefix("x: ")
+            .ui(ui);
+        egui::DragValue::new(&mut position[1])
+            .speed(0.01)
+            .range(-1. ..=1.)
+            .prefix("y: ")
+            .ui(ui);
+        ui.label(label);
+    });
+}
+
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct EmitterSettings {
+    pub enabled: bool,
+    /// At most `boids::MAX_EMITTERS`
+    pub emitters: Vec<Emitter>,
+    /// Seeds the positions and directions of the emitted boids
+    pub seed: u32,
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            emitters: vec![Emitter::default()],
+            seed: 0,
+        }
+    }
+}
+
+impl EmitterSettings {
+    /// What the simulation emits with, or `None` to simulate a fixed flock.
+    pub fn emit_params(&self) -> Option<boids::EmitParams> {
+        if !self.enabled {
+            return None;
+        }
+
+        let mut emit_params: boids::EmitParams = bytemuck::Zeroable::zeroed();
+        for (shader_emitter, emitter) in emit_params.emitters.iter_mut().zip(&self.emitters) {
+            *shader_emitter = emitter.shader_value();
+        }
+        emit_params.num_emitters = self.emitters.len().min(boids::MAX_EMITTERS as usize) as u32;
+        emit_params.seed = self.seed;
+        Some(emit_params)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, simulates_on_cpu: bool) {
+        if simulates_on_cpu {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Emitters need compute shaders, which the GPU can't run",
+            );
+            return;
+        }
+
+        ui.checkbox(&mut self.enabled, "Spawn boids from emitters")
+            .on_hover_text(
+                "The number of boids becomes the maximum number of boids that are alive at once. Boids that die \
+                make room for the others, so an inspected boid can be replaced by another one.",
+            );
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+                egui::DragValue::new(&mut self.seed).ui(ui);
+                ui.label("Seed");
+            });
+
+            let mut removed = None;
+            for (i, emitter) in self.emitters.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.strong(format!("Emitter {}", i + 1));
+                        if ui.small_button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                    emitter.ui(ui);
+                });
+            }
+            if let Some(i) = removed {
+                self.emitters.remove(i);
+            }
+
+            ui.separator();
+            ui.add_enabled_ui(self.emitters.len() < boids::MAX_EMITTERS as usize, |ui| {
+                if ui.button("Add Emitter").clicked() {
+                    self.emitters.push(Emitter::default());
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,165 @@
+//! The fixed flock that the simulation starts with: the one that a seed places at random, or one that was saved to a
+//! snapshot file.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+use egui::Widget;
+
+use crate::{
+    shaders::boids,
+    sim::{ParticleSystem, DEFAULT_FLOCK_SEED},
+};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FlockSettings {
+    /// Places the boids of the fixed flock
+    pub seed: u32,
+    /// Whether the flock starts over with `seed` in the next frame. Also set when the app starts, so the simulation
+    /// starts with the saved seed.
+    #[serde(skip)]
+    reset: bool,
+    /// Replaces the flock in the next frame, once it was loaded from a snapshot
+    #[serde(skip)]
+    loaded: Option<Vec<boids::Particle>>,
+    #[serde(skip)]
+    snapshot_requested: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    snapshot_path: String,
+    /// What happened to the last snapshot that was saved or loaded, or why that failed
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl Default for FlockSettings {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_FLOCK_SEED,
+            reset: true,
+            loaded: None,
+            snapshot_requested: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            snapshot_path: String::new(),
+            status: None,
+        }
+    }
+}
+
+impl FlockSettings {
+    /// Passes the flock on to `particles`, and saves the snapshot it read back. Returns the number of boids in the
+    /// snapshot that replaces the flock, if one was loaded.
+    pub fn update(&mut self, particles: &mut ParticleSystem) -> Option<u32> {
+        if std::mem::take(&mut self.reset) {
+            particles.reset_flock(self.seed);
+        }
+        if std::mem::take(&mut self.snapshot_requested) {
+            particles.request_snapshot();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(snapshot) = particles.take_snapshot() {
+            self.status = Some(match save_snapshot(&snapshot) {
+                Ok(path) => {
+                    log::info!("Saved {} boids to {}", snapshot.len(), path.display());
+                    Ok(format!("Saved {}", path.display()))
+                }
+                Err(err) => {
+                    log::error!("Failed to save the snapshot: {err}");
+                    Err(format!("Failed to save the snapshot: {err}"))
+                }
+            });
+        }
+
+        let loaded = self.loaded.take()?;
+        let num_particles = loaded.len() as u32;
+        particles.load_particles(loaded);
+        Some(num_particles)
+    }
+
+    /// Places the boids with the seed again in the next frame.
+    pub fn start_over(&mut self) {
+        self.reset = true;
+    }
+
+    /// Starts the flock over with the boids of the snapshot at `path`, once it is passed on in `update`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_snapshot(&mut self, path: &Path) {
+        let result = std::fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| crate::sim::decode_snapshot(&bytes).map_err(|err| err.to_string()));
+        match result {
+            Ok(particles) => {
+                log::info!("Loaded {} boids from {}", particles.len(), path.display());
+                self.status = Some(Ok(format!("Loaded {}", path.display())));
+                self.loaded = Some(particles);
+            }
+            Err(err) => {
+                log::error!("Failed to load the snapshot {}: {err}", path.display());
+                self.status = Some(Err(format!("Failed to load {}: {err}", path.display())));
+            }
+        }
+    }
+
+    /// `emitting` is whether the simulation spawns boids from emitters instead of simulating the fixed flock.
+    pub fn ui(&mut self, ui: &mut egui::Ui, emitting: bool) {
+        ui.horizontal(|ui| {
+            egui::DragValue::new(&mut self.seed).ui(ui);
+            ui.label("Seed");
+        });
+        if ui
+            .add_enabled(!emitting, egui::Button::new("Start Over"))
+            .on_hover_text("Places the boids at random with the seed again")
+            .clicked()
+        {
+            self.start_over();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ui.separator();
+            if ui
+                .add_enabled(!emitting, egui::Button::new("Save Snapshot"))
+                .on_hover_text("Saves the flock to the snapshots in the data directory")
+                .on_disabled_hover_text("Only the fixed flock can be saved, not emitted boids")
+                .clicked()
+            {
+                self.snapshot_requested = true;
+            }
+            ui.horizontal(|ui| {
+                egui::TextEdit::singleline(&mut self.snapshot_path)
+                    .hint_text("Snapshot file")
+                    .desired_width(160.)
+                    .ui(ui);
+                if ui
+                    .add_enabled(!self.snapshot_path.is_empty(), egui::Button::new("Load"))
+                    .on_hover_text("Turns off the emitters and replaces the flock")
+                    .clicked()
+                {
+                    self.load_snapshot(&PathBuf::from(self.snapshot_path.trim()));
+                }
+            });
+        }
+
+        match &self.status {
+            Some(Ok(status)) => {
+                ui.label(status);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Writes a snapshot of `particles` into a new file in the data directory.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_snapshot(particles: &[boids::Particle]) -> Result<PathBuf, String> {
+    use std::io::Write as _;
+
+    let (path, mut file) = super::data_dir::create_file("snapshots", "", "boids")?;
+    file.write_all(&crate::sim::encode_snapshot(particles))
+        .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+    Ok(path)
+}
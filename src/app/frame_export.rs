@@ -0,0 +1,185 @@
+//! Saves the boids to PNG files without the UI: screenshots at the size of the canvas, and posters at any size, which
+//! are rendered in tiles if they are larger than a texture can be.
+
+use std::{
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use eframe::egui_wgpu::RenderState;
+use egui::Widget;
+
+use super::data_dir;
+use crate::{
+    shaders::boids,
+    sim::{DrawOptions, ImageRequest, ParticleSystem},
+};
+
+/// Poster sizes that can be picked with a single click.
+const POSTER_SIZES: [(&str, [u32; 2]); 3] = [
+    ("4K", [3840, 2160]),
+    ("8K", [7680, 4320]),
+    ("16K", [15360, 8640]),
+];
+
+/// Posters are at most this large on each side, which is already far more than the boids have detail for.
+const MAX_POSTER_SIZE: u32 = 1 << 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ImageKind {
+    /// The canvas as it is on screen
+    Screenshot,
+    Poster,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FrameExport {
+    poster_size: [u32; 2],
+    /// Rendered once the canvas knows what it draws this frame
+    #[serde(skip)]
+    requested: Option<ImageKind>,
+    /// Where the last image was saved to, or why that failed
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl Default for FrameExport {
+    fn default() -> Self {
+        Self {
+            poster_size: POSTER_SIZES[1].1,
+            requested: None,
+            status: None,
+        }
+    }
+}
+
+impl FrameExport {
+    /// Entries of the File menu.
+    pub fn menu_ui(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .button("Save Screenshot")
+            .on_hover_text(
+                "Saves the canvas without the UI to the screenshots in the data directory",
+            )
+            .clicked()
+        {
+            self.requested = Some(ImageKind::Screenshot);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Poster");
+            egui::DragValue::new(&mut self.poster_size[0])
+                .range(1..=MAX_POSTER_SIZE)
+                .suffix(" px")
+                .ui(ui);
+            ui.label("×");
+            egui::DragValue::new(&mut self.poster_size[1])
+                .range(1..=MAX_POSTER_SIZE)
+                .suffix(" px")
+                .ui(ui);
+        });
+        ui.horizontal(|ui| {
+            for (name, size) in POSTER_SIZES {
+                ui.selectable_value(&mut self.poster_size, size, name);
+            }
+        });
+        if ui
+            .button("Render Poster")
+            .on_hover_text(
+                "Renders the boids without the UI at the poster size and saves them to the posters in the data \
+                directory. The camera's view covers the shorter side, and shows more of the flock along the longer one.",
+            )
+            .clicked()
+        {
+            self.requested = Some(ImageKind::Poster);
+        }
+
+        match &self.status {
+            Some(Ok(status)) => {
+                ui.label(status);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            None => {}
+        }
+    }
+
+    /// Renders and saves the image that was requested, if any. `canvas_size` is the size of the canvas in pixels, and
+    /// the other arguments are what the canvas is drawn with this frame.
+    pub fn update(
+        &mut self,
+        render_state: &RenderState,
+        canvas_size: [u32; 2],
+        render_params: boids::RenderParams,
+        draw_options: DrawOptions,
+        background: egui::Color32,
+    ) {
+        let Some(kind) = self.requested.take() else {
+            return;
+        };
+        let [width, height] = match kind {
+            ImageKind::Screenshot => canvas_size,
+            // the size can also come from saved state or a preset
+            ImageKind::Poster => self.poster_size.map(|side| side.clamp(1, MAX_POSTER_SIZE)),
+        };
+        let image = ImageRequest {
+            width,
+            height,
+            render_params,
+            draw_options,
+            background: background.to_array(),
+        };
+        let renderer = render_state.renderer.read();
+        let Some(particles) = renderer.callback_resources.get::<ParticleSystem>() else {
+            return;
+        };
+
+        let start = web_time::Instant::now();
+        self.status = Some(match save_image(render_state, particles, kind, &image) {
+            Ok(path) => {
+                log::info!(
+                    "Saved a {width}x{height} image to {} in {:.1}s",
+                    path.display(),
+                    start.elapsed().as_secs_f32()
+                );
+                Ok(format!("Saved {}", path.display()))
+            }
+            Err(err) => {
+                log::error!("Failed to save the image: {err}");
+                Err(format!("Failed to save the image: {err}"))
+            }
+        });
+    }
+}
+
+/// Renders `image` into a new PNG in the data directory.
+fn save_image(
+    render_state: &RenderState,
+    particles: &ParticleSystem,
+    kind: ImageKind,
+    image: &ImageRequest,
+) -> Result<PathBuf, String> {
+    let kind = match kind {
+        ImageKind::Screenshot => "screenshots",
+        ImageKind::Poster => "posters",
+    };
+    let (path, file) = data_dir::create_file(kind, "", "png")?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut png = encoder
+        .write_header()
+        .and_then(png::Writer::into_stream_writer)
+        .map_err(|err| err.to_string())?;
+    // the rows are compressed as they are rendered, so a poster is never in memory as a whole
+    particles
+        .render_image(&render_state.device, &render_state.queue, image, |rows| {
+            png.write_all(rows)
+        })
+        .map_err(|err| err.to_string())?;
+    png.finish().map_err(|err| err.to_string())?;
+    Ok(path)
+}
@@ -0,0 +1,196 @@
+//! Shows everything about a single boid that was picked by clicking on it.
+
+use egui::Color32;
+
+use crate::{
+    app::{boid_settings::BoidSettings, camera::Camera},
+    shaders::boids,
+    sim::PickedBoid,
+};
+
+/// How close to a boid a click has to be to select it, in points.
+const PICK_RADIUS: f32 = 16.;
+
+const SEPARATION_COLOR: Color32 = Color32::from_rgb(230, 80, 80);
+const ALIGNMENT_COLOR: Color32 = Color32::from_rgb(80, 200, 80);
+const COHESION_COLOR: Color32 = Color32::from_rgb(80, 140, 240);
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BoidInspector {
+    pub follow: bool,
+    pub show_radii: bool,
+    #[serde(skip)]
+    selected: Option<u32>,
+    /// The most recent data of the selected boid
+    #[serde(skip)]
+    inspected: Option<boids::InspectedBoid>,
+    /// Largest distance in simulation units between a pick and the boid it selects, set when the pick is sent
+    #[serde(skip)]
+    pick_distance: f32,
+    #[serde(skip)]
+    awaiting_pick: bool,
+}
+
+impl Default for BoidInspector {
+    fn default() -> Self {
+        Self {
+            follow: false,
+            show_radii: true,
+            selected: None,
+            inspected: None,
+            pick_distance: 0.,
+            awaiting_pick: false,
+        }
+    }
+}
+
+impl BoidInspector {
+    pub fn selected(&self) -> Option<u32> {
+        self.selected
+    }
+
+    /// Whether the UI has to keep repainting to receive the results from the GPU.
+    pub fn needs_repaint(&self) -> bool {
+        self.awaiting_pick || self.selected.is_some()
+    }
+
+    /// Where the camera should move to if it follows the selected boid.
+    pub fn follow_target(&self) -> Option<[f32; 2]> {
+        self.inspected
+            .filter(|_| self.follow)
+            .map(|inspected| inspected.particle.pos)
+    }
+
+    /// Called when the canvas was clicked, returns the position in the simulation that should be picked.
+    pub fn pick(&mut self, camera: &Camera, rect: egui::Rect, click_pos: egui::Pos2) -> [f32; 2] {
+        self.pick_distance = PICK_RADIUS / camera.points_per_unit(rect);
+        self.awaiting_pick = true;
+        camera.screen_to_world(rect, click_pos)
+    }
+
+    pub fn handle_pick_result(&mut self, picked: Option<PickedBoid>) {
+        self.awaiting_pick = false;
+        let picked = picked.filter(|picked| picked.distance <= self.pick_distance);
+        self.select(picked.map(|picked| picked.index));
+    }
+
+    /// Updates the data of the selected boid, if `inspected` was gathered for it.
+    pub fn update(&mut self, inspected: Option<(u32, boids::InspectedBoid)>, num_particles: u32) {
+        if self.selected.is_some_and(|index| index >= num_particles) {
+            self.select(None);
+        }
+        if let Some((index, inspected)) = inspected {
+            if Some(index) == self.selected {
+                self.inspected = Some(inspected);
+            }
+        }
+    }
+
+    fn select(&mut self, selected: Option<u32>) {
+        if selected != self.selected {
+            self.selected = selected;
+            self.inspected = None;
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let Some(index) = self.selected else {
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new(format!("Boid #{index}"))
+            .id(egui::Id::new("Boid Inspector"))
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| self.ui(ui));
+        if !open {
+            self.select(None);
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.follow, "Camera follows boid");
+        ui.checkbox(&mut self.show_radii, "Show perception radii");
+        ui.separator();
+
+        let Some(inspected) = &self.inspected else {
+            ui.spinner();
+            return;
+        };
+        let particle = &inspected.particle;
+        let steering = &inspected.steering;
+        let vec2 = |v: [f32; 2]| format!("({:+.4}, {:+.4})", v[0], v[1]);
+
+        egui::Grid::new("boid inspector")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Position");
+                ui.monospace(vec2(particle.pos));
+                ui.end_row();
+                ui.label("Velocity");
+                ui.monospace(vec2(particle.vel));
+                ui.end_row();
+                ui.label("Speed");
+                ui.monospace(format!("{:.4}", egui::Vec2::from(particle.vel).length()));
+                ui.end_row();
+                ui.label("Neighbors");
+                ui.monospace(steering.neighbor_count.to_string());
+                ui.end_row();
+                ui.label("Nearest neighbor");
+                ui.monospace(format!("{:.4}", steering.nearest_distance));
+                ui.end_row();
+
+                ui.colored_label(
+                    SEPARATION_COLOR,
+                    format!("Separation ({})", steering.separation_count),
+                );
+                ui.monospace(vec2(steering.separation));
+                ui.end_row();
+                ui.colored_label(
+                    ALIGNMENT_COLOR,
+                    format!("Alignment ({})", steering.alignment_count),
+                );
+                ui.monospace(vec2(steering.alignment));
+                ui.end_row();
+                ui.colored_label(
+                    COHESION_COLOR,
+                    format!("Cohesion ({})", steering.cohesion_count),
+                );
+                ui.monospace(vec2(steering.cohesion));
+                ui.end_row();
+                ui.label("Total acceleration");
+                ui.monospace(vec2(steering.acceleration));
+                ui.end_row();
+            });
+    }
+
+    /// Draws the perception radii of the selected boid on top of the canvas.
+    pub fn paint_overlay(
+        &self,
+        painter: &egui::Painter,
+        rect: egui::Rect,
+        camera: &Camera,
+        settings: &BoidSettings,
+    ) {
+        let Some(inspected) = self.inspected.filter(|_| self.show_radii) else {
+            return;
+        };
+
+        let center = camera.world_to_screen(rect, inspected.particle.pos);
+        let points_per_unit = camera.points_per_unit(rect);
+        for (distance, color) in [
+            (settings.separation_distance, SEPARATION_COLOR),
+            (settings.alignment_distance, ALIGNMENT_COLOR),
+            (settings.cohesion_distance, COHESION_COLOR),
+        ] {
+            painter.circle_stroke(
+                center,
+                distance * points_per_unit,
+                egui::Stroke::new(1.5, color),
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Which compute shader simulates the boids, so they can be compared against each other in the GPU profiler.
+
+use std::collections::BTreeMap;
+
+use egui::Widget;
+
+use crate::sim::{BoidsKernel, ParticleSystem, DEFAULT_WORKGROUP_SIZE, MAX_TILE_SIZE};
+
+/// Tile sizes that can be picked, all of them powers of two up to `MAX_TILE_SIZE`.
+const TILE_SIZES: [u32; 5] = [32, 64, 128, 256, 512];
+
+const _: () = assert!(TILE_SIZES[TILE_SIZES.len() - 1] <= MAX_TILE_SIZE);
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct KernelSettings {
+    pub tiled: bool,
+    pub tile_size: u32,
+    /// Workgroup size of the all pairs kernel on every adapter it was picked or autotuned on, see `adapter_key`
+    pub workgroup_sizes: BTreeMap<String, u32>,
+    /// Key of the adapter the app runs on
+    #[serde(skip)]
+    adapter: String,
+    /// Whether autotuning was started, or found unnecessary, since the app started
+    #[serde(skip)]
+    checked_autotune: bool,
+    /// The autotune button was clicked, and autotuning starts with the next `update`
+    #[serde(skip)]
+    autotune_requested: bool,
+    /// What the particle system reported during the last `update`
+    #[serde(skip)]
+    supported_workgroup_sizes: Vec<u32>,
+    #[serde(skip)]
+    can_autotune: bool,
+    #[serde(skip)]
+    autotune_progress: Option<f32>,
+}
+
+impl Default for KernelSettings {
+    fn default() -> Self {
+        Self {
+            tiled: false,
+            tile_size: 256,
+            workgroup_sizes: BTreeMap::new(),
+            adapter: String::new(),
+            checked_autotune: false,
+            autotune_requested: false,
+            supported_workgroup_sizes: Vec::new(),
+            can_autotune: false,
+            autotune_progress: None,
+        }
+    }
+}
+
+/// Identifies an adapter together with its driver, since a driver update can change which workgroup size is fastest.
+fn adapter_key(info: &wgpu::AdapterInfo) -> String {
+    format!(
+        "{} ({:?}, {} {})",
+        info.name, info.backend, info.driver, info.driver_info
+    )
+}
+
+impl KernelSettings {
+    pub fn kernel(&self) -> BoidsKernel {
+        if self.tiled {
+            BoidsKernel::Tiled {
+                tile_size: self.tile_size,
+            }
+        } else {
+            BoidsKernel::AllPairs
+        }
+    }
+
+    /// Workgroup size of the all pairs kernel on the current adapter.
+    pub fn workgroup_size(&self) -> u32 {
+        self.workgroup_sizes
+            .get(&self.adapter)
+            .copied()
+            .unwrap_or(DEFAULT_WORKGROUP_SIZE)
+    }
+
+    /// Remembers what `particles` autotuned, and starts autotuning if it was requested, or if the workgroup size was
+    /// never picked on `adapter`.
+    pub fn update(&mut self, particles: &mut ParticleSystem, adapter: &wgpu::AdapterInfo) {
+        self.adapter = adapter_key(adapter);
+        if let Some(workgroup_size) = particles.take_autotuned_workgroup_size() {
+            self.workgroup_sizes
+                .insert(self.adapter.clone(), workgroup_size);
+        }
+
+        self.supported_workgroup_sizes = particles.workgroup_sizes().to_vec();
+        self.can_autotune = particles.can_autotune();
+        if !self.checked_autotune && !self.workgroup_sizes.contains_key(&self.adapter) {
+            self.autotune_requested = true;
+        }
+        self.checked_autotune = true;
+        if std::mem::take(&mut self.autotune_requested) {
+            particles.start_autotune();
+        }
+        self.autotune_progress = particles.autotune_progress();
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, step_gpu_time_ms: Option<f32>, simulates_on_cpu: bool) {
+        if simulates_on_cpu {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "The boids are simulated on the CPU, since the GPU can't run compute shaders",
+            );
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.tiled, false, "All pairs")
+                .on_hover_text("Every boid reads the others straight from the particle buffer");
+            ui.selectable_value(&mut self.tiled, true, "Tiled")
+                .on_hover_text("Workgroups load the flock into shared memory one tile at a time");
+        });
+        ui.add_enabled_ui(!self.tiled && self.autotune_progress.is_none(), |ui| {
+            let mut workgroup_size = self.workgroup_size();
+            egui::ComboBox::from_label("Workgroup Size")
+                .selected_text(workgroup_size.to_string())
+                .show_ui(ui, |ui| {
+                    for &size in &self.supported_workgroup_sizes {
+                        ui.selectable_value(&mut workgroup_size, size, size.to_string());
+                    }
+                });
+            if workgroup_size != self.workgroup_size() {
+                self.workgroup_sizes
+                    .insert(self.adapter.clone(), workgroup_size);
+            }
+        });
+        match self.autotune_progress {
+            Some(progress) => {
+                egui::ProgressBar::new(progress)
+                    .text("Autotuning the workgroup size")
+                    .ui(ui);
+            }
+            None => {
+                ui.add_enabled_ui(self.can_autotune, |ui| {
+                    if ui
+                        .button("Autotune")
+                        .on_hover_text(
+                            "Simulate with every workgroup size for a while, and keep the fastest one for this adapter",
+                        )
+                        .on_disabled_hover_text("Measuring the workgroup sizes needs timer queries")
+                        .clicked()
+                    {
+                        self.autotune_requested = true;
+                    }
+                });
+            }
+        }
+        ui.add_enabled_ui(self.tiled, |ui| {
+            egui::ComboBox::from_label("Tile Size")
+                .selected_text(self.tile_size.to_string())
+                .show_ui(ui, |ui| {
+                    for tile_size in TILE_SIZES {
+                        ui.selectable_value(&mut self.tile_size, tile_size, tile_size.to_string());
+                    }
+                });
+        });
+        match step_gpu_time_ms {
+            Some(step_gpu_time_ms) => {
+                ui.label(format!("GPU time per step: {step_gpu_time_ms:.3} ms"))
+            }
+            None => ui.label("GPU time per step: not measured"),
+        };
+    }
+}
@@ -0,0 +1,273 @@
+//! Keeps the `log` records of the whole app, including wgpu's uncaptured errors, and shows them in a console window.
+//!
+//! [`init_logger`] installs a logger that hands every record to the platform's logger as before, and keeps the ones
+//! that are at least [`log::Level::Info`] in memory, so they can be read without a terminal or the browser's console.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        LazyLock, Mutex,
+    },
+};
+
+use egui::Widget;
+use log::{Level, LevelFilter};
+
+/// Records that are kept in memory, dropping the oldest ones first.
+const MAX_RECORDS: usize = 10_000;
+
+/// Target of the records of uncaptured wgpu errors.
+const WGPU_ERROR_TARGET: &str = "wgpu uncaptured error";
+
+static RECORDS: LazyLock<Mutex<VecDeque<Record>>> = LazyLock::new(Default::default);
+
+/// Counts the uncaptured wgpu errors, so the console can be opened when there is a new one.
+static WGPU_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+static START: LazyLock<web_time::Instant> = LazyLock::new(web_time::Instant::now);
+
+#[derive(Clone)]
+struct Record {
+    /// Seconds since the logger was installed
+    time: f64,
+    level: Level,
+    target: String,
+    message: String,
+    /// How often the same message was logged in a row, e.g. by an error that happens every frame
+    count: usize,
+}
+
+struct ConsoleLogger {
+    inner: Box<dyn log::Log>,
+    inner_filter: LevelFilter,
+}
+
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= Level::Info || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+        if record.level() > Level::Info && record.level() > self.inner_filter {
+            return;
+        }
+
+        let message = record.args().to_string();
+        let mut records = RECORDS.lock().unwrap();
+        if let Some(last) = records.back_mut().filter(|last| {
+            last.level == record.level()
+                && last.target == record.target()
+                && last.message == message
+        }) {
+            last.count += 1;
+            return;
+        }
+        if records.len() == MAX_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(Record {
+            time: START.elapsed().as_secs_f64(),
+            level: record.level(),
+            target: record.target().to_owned(),
+            message,
+            count: 1,
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs the logger that the console reads from. Every record is also passed on to `inner` if it is enabled there,
+/// and `inner_filter` is the most verbose level that `inner` logs.
+pub fn init_logger(
+    inner: Box<dyn log::Log>,
+    inner_filter: LevelFilter,
+) -> Result<(), log::SetLoggerError> {
+    LazyLock::force(&START);
+    log::set_logger(Box::leak(Box::new(ConsoleLogger {
+        inner,
+        inner_filter,
+    })))?;
+    log::set_max_level(inner_filter.max(LevelFilter::Info));
+    Ok(())
+}
+
+/// Logs the errors of `device` that aren't captured by an error scope, instead of panicking on them. Their messages
+/// name the labels of the objects they are about.
+pub fn capture_uncaptured_errors(device: &wgpu::Device) {
+    device.on_uncaptured_error(Box::new(|err| {
+        WGPU_ERRORS.fetch_add(1, Ordering::Relaxed);
+        log::error!(target: WGPU_ERROR_TARGET, "{err}");
+    }));
+}
+
+fn level_color(visuals: &egui::Visuals, level: Level) -> egui::Color32 {
+    match level {
+        Level::Error => visuals.error_fg_color,
+        Level::Warn => visuals.warn_fg_color,
+        Level::Info => visuals.text_color(),
+        Level::Debug | Level::Trace => visuals.weak_text_color(),
+    }
+}
+
+fn serialize_level<S: serde::Serializer>(
+    level: &LevelFilter,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
+fn deserialize_level<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<LevelFilter, D::Error> {
+    let level = <String as serde::Deserialize>::deserialize(deserializer)?;
+    level.parse().map_err(serde::de::Error::custom)
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct LogConsole {
+    pub open: bool,
+    /// The most verbose level that is shown
+    #[serde(
+        serialize_with = "serialize_level",
+        deserialize_with = "deserialize_level"
+    )]
+    level: LevelFilter,
+    /// Only records whose target or message contain this are shown, ignoring case
+    search: String,
+    /// Whether the console opens by itself when wgpu reports an error
+    open_on_wgpu_error: bool,
+    /// Uncaptured wgpu errors that were already seen
+    #[serde(skip)]
+    wgpu_errors_seen: usize,
+}
+
+impl Default for LogConsole {
+    fn default() -> Self {
+        Self {
+            open: false,
+            level: LevelFilter::Info,
+            search: String::new(),
+            open_on_wgpu_error: true,
+            wgpu_errors_seen: 0,
+        }
+    }
+}
+
+impl LogConsole {
+    /// The number of uncaptured wgpu errors since the app started.
+    pub fn wgpu_errors(&self) -> usize {
+        WGPU_ERRORS.load(Ordering::Relaxed)
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let wgpu_errors = self.wgpu_errors();
+        if wgpu_errors > self.wgpu_errors_seen {
+            self.wgpu_errors_seen = wgpu_errors;
+            if self.open_on_wgpu_error {
+                self.open = true;
+            }
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Log Console")
+            .open(&mut open)
+            .default_size([640., 320.])
+            .show(ctx, |ui| self.ui(ui));
+        self.open = open;
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("log level")
+                .selected_text(self.level.as_str())
+                .show_ui(ui, |ui| {
+                    for level in LevelFilter::iter().skip(1) {
+                        ui.selectable_value(&mut self.level, level, level.as_str());
+                    }
+                });
+            egui::TextEdit::singleline(&mut self.search)
+                .hint_text("Search")
+                .desired_width(160.)
+                .ui(ui);
+            if ui.button("Clear").clicked() {
+                RECORDS.lock().unwrap().clear();
+            }
+            ui.checkbox(&mut self.open_on_wgpu_error, "Open on wgpu errors");
+        });
+        if log::max_level() < self.level {
+            ui.weak(format!(
+                "Only {} and more severe records are kept, set RUST_LOG to keep more",
+                log::max_level().as_str()
+            ));
+        }
+        ui.separator();
+
+        let search = self.search.to_lowercase();
+        let shown: Vec<usize> = RECORDS
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.level <= self.level)
+            .filter(|(_, record)| {
+                search.is_empty()
+                    || record.message.to_lowercase().contains(&search)
+                    || record.target.to_lowercase().contains(&search)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                // not locked while the rows are laid out, which can log too
+                let visible: Vec<Record> = {
+                    let records = RECORDS.lock().unwrap();
+                    shown[rows]
+                        .iter()
+                        .filter_map(|&i| records.get(i).cloned())
+                        .collect()
+                };
+                for record in &visible {
+                    let color = level_color(ui.visuals(), record.level);
+                    let repeated = if record.count > 1 {
+                        format!(" (×{})", record.count)
+                    } else {
+                        String::new()
+                    };
+                    // rows have to be a single line, wgpu's errors list their causes on the lines below
+                    let message = record
+                        .message
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" | ");
+                    let response = ui.add(
+                        egui::Label::new(
+                            egui::RichText::new(format!(
+                                "{:>9.3} {:<5} {}: {message}{repeated}",
+                                record.time, record.level, record.target
+                            ))
+                            .monospace()
+                            .color(color),
+                        )
+                        .extend(),
+                    );
+                    if record.message.contains('\n') {
+                        response.on_hover_text(egui::RichText::new(&record.message).monospace());
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,314 @@
+//! Color gradients that boids are colored with, and an editor for them.
+
+use egui::Color32;
+
+use crate::sim::LUT_WIDTH;
+
+#[derive(Clone, Copy, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct GradientStop {
+    /// Where in the gradient this color is, from 0 to 1
+    pub position: f32,
+    pub color: Color32,
+}
+
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Gradient {
+    /// Always sorted by position
+    pub stops: Vec<GradientStop>,
+    /// Whether to blend between the stops, or to use the color of the previous stop until the next one starts
+    pub smooth: bool,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Palette::Rainbow.gradient()
+    }
+}
+
+impl Gradient {
+    /// A smooth gradient through `colors`, spaced evenly.
+    fn evenly_spaced(colors: &[Color32]) -> Self {
+        let last = (colors.len() - 1).max(1) as f32;
+        Self {
+            stops: colors
+                .iter()
+                .enumerate()
+                .map(|(i, &color)| GradientStop {
+                    position: i as f32 / last,
+                    color,
+                })
+                .collect(),
+            smooth: true,
+        }
+    }
+
+    /// A gradient with a band of equal width for each of `colors`.
+    fn bands(colors: &[Color32]) -> Self {
+        Self {
+            stops: colors
+                .iter()
+                .enumerate()
+                .map(|(i, &color)| GradientStop {
+                    position: i as f32 / colors.len() as f32,
+                    color,
+                })
+                .collect(),
+            smooth: false,
+        }
+    }
+
+    fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    pub fn sample(&self, t: f32) -> Color32 {
+        let next = self.stops.partition_point(|stop| stop.position <= t);
+        let (Some(before), Some(after)) =
+            (self.stops.get(next.wrapping_sub(1)), self.stops.get(next))
+        else {
+            // before the first or after the last stop
+            return match self.stops.first() {
+                Some(first) if t < first.position => first.color,
+                _ => self.stops.last().map_or(Color32::WHITE, |last| last.color),
+            };
+        };
+        if !self.smooth {
+            return before.color;
+        }
+
+        let fraction = (t - before.position) / (after.position - before.position);
+        let lerp = |a: u8, b: u8| egui::lerp(a as f32..=b as f32, fraction).round() as u8;
+        Color32::from_rgba_unmultiplied(
+            lerp(before.color.r(), after.color.r()),
+            lerp(before.color.g(), after.color.g()),
+            lerp(before.color.b(), after.color.b()),
+            lerp(before.color.a(), after.color.a()),
+        )
+    }
+
+    /// sRGB colors for a lookup texture that is `LUT_WIDTH` texels wide.
+    pub fn bake(&self) -> Vec<[u8; 4]> {
+        (0..LUT_WIDTH)
+            .map(|i| {
+                let t = (i as f32 + 0.5) / LUT_WIDTH as f32;
+                self.sample(t).to_srgba_unmultiplied()
+            })
+            .collect()
+    }
+
+    /// Draws the gradient into `rect`.
+    fn paint(&self, painter: &egui::Painter, rect: egui::Rect) {
+        const SEGMENTS: usize = 64;
+        let mut mesh = egui::Mesh::default();
+        for i in 0..=SEGMENTS {
+            let t = i as f32 / SEGMENTS as f32;
+            // sample just before t so that bands end at the right place
+            let color = self.sample((t - 0.5 / SEGMENTS as f32).max(0.));
+            let x = egui::lerp(rect.x_range(), t);
+            mesh.colored_vertex(egui::pos2(x, rect.top()), color);
+            mesh.colored_vertex(egui::pos2(x, rect.bottom()), color);
+            if i > 0 {
+                let idx = 2 * i as u32;
+                mesh.add_triangle(idx - 2, idx - 1, idx);
+                mesh.add_triangle(idx - 1, idx, idx + 1);
+            }
+        }
+        painter.add(mesh);
+    }
+
+    /// Shows a preview of the gradient. Clicking on it adds a stop, and the stops below it can be dragged around.
+    /// Returns whether the gradient changed.
+    pub fn editor_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        const HEIGHT: f32 = 24.;
+        const HANDLE_SIZE: f32 = 6.;
+
+        let mut changed = false;
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), HEIGHT + 2. * HANDLE_SIZE),
+            egui::Sense::click(),
+        );
+        let bar = rect.with_max_y(rect.top() + HEIGHT);
+        self.paint(&ui.painter_at(bar), bar);
+        ui.painter()
+            .rect_stroke(bar, 0., ui.visuals().widgets.noninteractive.bg_stroke);
+
+        if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+        {
+            let position = egui::remap_clamp(pos.x, bar.x_range(), 0.0..=1.);
+            let color = self.sample(position);
+            self.stops.push(GradientStop { position, color });
+            self.sort();
+            changed = true;
+        }
+
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            let x = egui::lerp(bar.x_range(), stop.position);
+            let handle = egui::Rect::from_center_size(
+                egui::pos2(x, bar.bottom() + HANDLE_SIZE),
+                egui::Vec2::splat(2. * HANDLE_SIZE),
+            );
+            let handle_response = ui.interact(handle, response.id.with(i), egui::Sense::drag());
+            if handle_response.dragged() {
+                stop.position =
+                    (stop.position + handle_response.drag_delta().x / bar.width()).clamp(0., 1.);
+                changed = true;
+            }
+            let stroke = ui.style().interact(&handle_response).fg_stroke;
+            ui.painter().add(egui::Shape::convex_polygon(
+                vec![
+                    egui::pos2(x, bar.bottom()),
+                    handle.right_bottom(),
+                    handle.left_bottom(),
+                ],
+                stop.color,
+                stroke,
+            ));
+        }
+
+        let mut remove = None;
+        let can_remove = self.stops.len() > 1;
+        egui::Grid::new("gradient stops")
+            .num_columns(3)
+            .show(ui, |ui| {
+                for (i, stop) in self.stops.iter_mut().enumerate() {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut stop.position)
+                                .range(0.0..=1.)
+                                .speed(0.005)
+                                .fixed_decimals(3),
+                        )
+                        .changed();
+                    changed |= ui.color_edit_button_srgba(&mut stop.color).changed();
+                    if ui
+                        .add_enabled(can_remove, egui::Button::new("🗑"))
+                        .on_hover_text("Remove this stop")
+                        .clicked()
+                    {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = remove {
+            self.stops.remove(i);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            changed |= ui.checkbox(&mut self.smooth, "Smooth").changed();
+            if ui.button("Reverse").clicked() {
+                for stop in &mut self.stops {
+                    stop.position = 1. - stop.position;
+                }
+                changed = true;
+            }
+        });
+
+        if changed {
+            self.sort();
+        }
+        changed
+    }
+}
+
+/// Built-in gradients.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Palette {
+    /// The cyclic gradient boids were originally colored with by heading
+    Rainbow,
+    Viridis,
+    Magma,
+    /// Perceptually uniform and readable with red-green color blindness
+    Cividis,
+    /// Distinct colors that stay distinguishable with all common kinds of color blindness, for categories
+    OkabeIto,
+}
+
+impl Palette {
+    pub const ALL: [Self; 5] = [
+        Self::Rainbow,
+        Self::Viridis,
+        Self::Magma,
+        Self::Cividis,
+        Self::OkabeIto,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Rainbow => "Rainbow (cyclic)",
+            Self::Viridis => "Viridis",
+            Self::Magma => "Magma",
+            Self::Cividis => "Cividis (colorblind-safe)",
+            Self::OkabeIto => "Okabe-Ito (colorblind-safe, categorical)",
+        }
+    }
+
+    pub fn gradient(self) -> Gradient {
+        const fn hex(rgb: u32) -> Color32 {
+            Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+        }
+
+        match self {
+            Self::Rainbow => {
+                // sampled from the cosine formula boids used to be colored with
+                let colors: Vec<_> = (0..=12)
+                    .map(|i| {
+                        let angle = i as f32 / 12. * std::f32::consts::TAU;
+                        let channel = |offset: f32| {
+                            ((2. * (angle - offset).cos()).clamp(0., 1.) * 255.).round() as u8
+                        };
+                        Color32::from_rgb(
+                            channel(0.),
+                            channel(std::f32::consts::TAU / 3.),
+                            channel(2. * std::f32::consts::TAU / 3.),
+                        )
+                    })
+                    .collect();
+                Gradient::evenly_spaced(&colors)
+            }
+            Self::Viridis => Gradient::evenly_spaced(&[
+                hex(0x440154),
+                hex(0x472d7b),
+                hex(0x3b528b),
+                hex(0x2c728e),
+                hex(0x21918c),
+                hex(0x28ae80),
+                hex(0x5ec962),
+                hex(0xaddc30),
+                hex(0xfde725),
+            ]),
+            Self::Magma => Gradient::evenly_spaced(&[
+                hex(0x000004),
+                hex(0x1c1044),
+                hex(0x4f127b),
+                hex(0x812581),
+                hex(0xb5367a),
+                hex(0xe55064),
+                hex(0xfb8761),
+                hex(0xfec287),
+                hex(0xfcfdbf),
+            ]),
+            Self::Cividis => Gradient::evenly_spaced(&[
+                hex(0x00224e),
+                hex(0x414d6b),
+                hex(0x7c7b78),
+                hex(0xbcaf6f),
+                hex(0xfee838),
+            ]),
+            // black is left out since it doesn't show up on the dark background
+            Self::OkabeIto => Gradient::bands(&[
+                hex(0xe69f00),
+                hex(0x56b4e9),
+                hex(0x009e73),
+                hex(0xf0e442),
+                hex(0x0072b2),
+                hex(0xd55e00),
+                hex(0xcc79a7),
+            ]),
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! The app state is saved as a versioned TOML document, so a change to the settings that older state can't be read with
+//! comes with a migration, instead of resetting every setting.
+//!
+//! Loading migrates the saved document step by step up to [`STATE_VERSION`], and only then deserializes the app from
+//! it. Settings that this version doesn't know, like the ones a newer version saved, stay in the document and are
+//! written back when saving, so switching between versions of the app doesn't lose them. The same goes for settings
+//! that this version knows but can't read, like a choice that only a newer version has, as long as they aren't changed.
+//!
+//! To change the settings in a way that older state can't be read with, increment `STATE_VERSION` and add a migration
+//! to `MIGRATIONS` that rewrites documents of the previous version. A migration has to leave settings alone that are
+//! already in the newer form, since an older version that ran in between saves the settings it kept under its own
+//! version.
+
+use super::RendererApp;
+
+/// The state is stored under this key since it is versioned. Before that, it was stored as RON under
+/// `eframe::APP_KEY`, which is still written for older versions of the app.
+const STATE_KEY: &str = "app_state";
+
+/// Key of the version in the document, next to the settings.
+const VERSION_KEY: &str = "version";
+
+/// Version of the documents that this version of the app saves.
+pub const STATE_VERSION: u32 = 1;
+
+/// A setting that couldn't be read from the saved document, so its saved value is written back unless it's changed.
+pub struct UnreadSetting {
+    /// Keys of the tables the setting is in, followed by its own key.
+    path: Vec<String>,
+    /// The value the setting was reset to, or `None` if it isn't saved.
+    reset_to: Option<toml::Value>,
+}
+
+/// Rewrites a document of the version at its index in `MIGRATIONS` into one of the next version.
+type Migration = fn(&mut toml::Table);
+
+const MIGRATIONS: [Migration; STATE_VERSION as usize] = [
+    // 0 is the unversioned RON state, which is converted into a document of the same settings while loading
+    |_| {},
+];
+
+/// Loads the state that was saved in `storage`, or the default state if there is none. Also returns a notice for the
+/// user if the state was migrated or couldn't be read completely.
+pub fn load(storage: &dyn eframe::Storage) -> (RendererApp, Option<String>) {
+    if let Some(toml) = storage.get_string(STATE_KEY) {
+        return match toml.parse() {
+            Ok(document) => from_document(document),
+            Err(err) => {
+                log::error!("Failed to parse the saved state: {err}");
+                (
+                    RendererApp::default(),
+                    Some(format!(
+                        "The saved settings couldn't be read and were reset: {err}"
+                    )),
+                )
+            }
+        };
+    }
+
+    let Some(legacy) = eframe::get_value::<RendererApp>(storage, eframe::APP_KEY) else {
+        return (RendererApp::default(), None);
+    };
+    match toml::Table::try_from(&legacy) {
+        Ok(mut document) => {
+            document.insert(VERSION_KEY.to_owned(), 0.into());
+            from_document(document)
+        }
+        Err(err) => {
+            log::error!("Failed to convert the unversioned state: {err}");
+            (legacy, None)
+        }
+    }
+}
+
+/// Saves the state of `app`, together with the settings that it didn't know when it was loaded.
+pub fn save(app: &RendererApp, storage: &mut dyn eframe::Storage) {
+    match to_document(app) {
+        Ok(document) => storage.set_string(STATE_KEY, document.to_string()),
+        Err(err) => log::error!("Failed to save the state: {err}"),
+    }
+    // versions from before the state was versioned only read this
+    eframe::set_value(storage, eframe::APP_KEY, app);
+}
+
+/// Migrates `document` to the current version and reads the app from it. Settings that can't be read keep their
+/// defaults. Also returns a notice for the user if the document was migrated or couldn't be read completely.
+pub fn from_document(mut document: toml::Table) -> (RendererApp, Option<String>) {
+    let version = match document.remove(VERSION_KEY) {
+        Some(toml::Value::Integer(version)) => u32::try_from(version).unwrap_or(u32::MAX),
+        // documents without a version are presets that were written by hand, which are read as they are
+        _ => STATE_VERSION,
+    };
+    let mut notices = Vec::new();
+    if version < STATE_VERSION {
+        migrate(&mut document, version, &MIGRATIONS);
+        log::info!("Migrated the state from version {version} to version {STATE_VERSION}");
+        notices.push(format!(
+            "The settings of an older version of the app (version {version}) were migrated to version \
+            {STATE_VERSION}."
+        ));
+    } else if version > STATE_VERSION {
+        log::warn!(
+            "The state is of version {version}, which is newer than version {STATE_VERSION}"
+        );
+        notices.push(format!(
+            "The settings were saved by a newer version of the app (version {version}). Settings that this version \
+            doesn't know are kept, so the newer version still finds them."
+        ));
+    }
+
+    let mut app = match toml::Value::Table(document.clone()).try_into::<RendererApp>() {
+        Ok(app) => app,
+        Err(err) => {
+            log::warn!("Failed to read the whole state: {err}");
+            // read every setting on its own, down into the tables of settings, so one that can't be read doesn't
+            // reset the others
+            let mut readable = document.clone();
+            let mut unread = Vec::new();
+            remove_unreadable(&mut readable, &mut Vec::new(), &mut unread);
+            let names: Vec<String> = unread.iter().map(|path| path.join(".")).collect();
+            log::warn!("Reset the settings that couldn't be read: {names:?}");
+            notices.push(format!(
+                "These settings couldn't be read and were reset: {}.",
+                names.join(", ")
+            ));
+            let mut app: RendererApp = toml::Value::Table(readable).try_into().unwrap_or_default();
+            let reset = toml::Table::try_from(&app).unwrap_or_default();
+            app.unread_settings = unread
+                .into_iter()
+                .map(|path| UnreadSetting {
+                    reset_to: get(&reset, &path).cloned(),
+                    path,
+                })
+                .collect();
+            app
+        }
+    };
+    app.saved_state = document;
+    (app, (!notices.is_empty()).then(|| notices.join("\n")))
+}
+
+/// Removes the settings in `table`, which is at `path` in the document, that the app can't be read from, and adds
+/// their paths to `unread`.
+fn remove_unreadable(
+    table: &mut toml::Table,
+    path: &mut Vec<String>,
+    unread: &mut Vec<Vec<String>>,
+) {
+    let keys: Vec<String> = table.keys().cloned().collect();
+    for key in keys {
+        path.push(key.clone());
+        if !is_readable(path, &table[&key]) {
+            if let Some(toml::Value::Table(nested)) = table.get_mut(&key) {
+                remove_unreadable(nested, path, unread);
+            }
+            // a table that still can't be read without them isn't a table of settings, so it's reset as a whole
+            if !is_readable(path, &table[&key]) {
+                table.remove(&key);
+                unread.retain(|unread| !unread.starts_with(path));
+                unread.push(path.clone());
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Whether the app can be read from a document with nothing but `value` at `path`.
+fn is_readable(path: &[String], value: &toml::Value) -> bool {
+    let document = path.iter().rev().fold(value.clone(), |value, key| {
+        toml::Value::Table(toml::Table::from_iter([(key.clone(), value)]))
+    });
+    document.try_into::<RendererApp>().is_ok()
+}
+
+/// The value at `path` in `table`.
+fn get<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (key, tables) = path.split_last()?;
+    tables
+        .iter()
+        .try_fold(table, |table, key| table.get(key)?.as_table())?
+        .get(key)
+}
+
+/// Rewrites a document of `version` with every migration in `migrations` from the one of that version on.
+fn migrate(document: &mut toml::Table, version: u32, migrations: &[Migration]) {
+    for migration in migrations.iter().skip(version as usize) {
+        migration(document);
+    }
+}
+
+/// The document that `app` is saved as.
+pub fn to_document(app: &RendererApp) -> Result<toml::Table, toml::ser::Error> {
+    let mut document = toml::Table::try_from(app)?;
+    keep_unread(app, &mut document);
+    keep_unknown(&app.saved_state, &mut document);
+    document.insert(VERSION_KEY.to_owned(), i64::from(STATE_VERSION).into());
+    Ok(document)
+}
+
+/// Puts the saved values of the settings that couldn't be read back into `document`, unless they were changed since.
+fn keep_unread(app: &RendererApp, document: &mut toml::Table) {
+    for setting in &app.unread_settings {
+        let Some(saved) = get(&app.saved_state, &setting.path) else {
+            continue;
+        };
+        if get(document, &setting.path) != setting.reset_to.as_ref() {
+            continue;
+        }
+        let Some((key, tables)) = setting.path.split_last() else {
+            continue;
+        };
+        let parent = tables.iter().try_fold(&mut *document, |table, key| {
+            table.get_mut(key)?.as_table_mut()
+        });
+        if let Some(parent) = parent {
+            parent.insert(key.clone(), saved.clone());
+        }
+    }
+}
+
+/// Adds everything in `saved` that `current` doesn't have to it, looking into the tables they both have.
+fn keep_unknown(saved: &toml::Table, current: &mut toml::Table) {
+    for (key, saved_value) in saved {
+        match (current.get_mut(key), saved_value) {
+            (Some(toml::Value::Table(current)), toml::Value::Table(saved)) => {
+                keep_unknown(saved, current);
+            }
+            (Some(_), _) => {}
+            (None, _) => {
+                current.insert(key.clone(), saved_value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::boid_settings::Integrator;
+
+    fn parse(toml: &str) -> toml::Table {
+        toml.parse()
+            .expect("the test document should be valid TOML")
+    }
+
+    #[test]
+    fn migrations_run_from_the_documents_version_on() {
+        let migrations: [Migration; 3] = [
+            |document| {
+                let old = document.remove("old_name").unwrap();
+                document.insert("new_name".to_owned(), old);
+            },
+            |document| {
+                let value = document["new_name"].as_integer().unwrap();
+                document.insert("new_name".to_owned(), (value * 2).into());
+            },
+            |document| {
+                document.insert("added".to_owned(), true.into());
+            },
+        ];
+
+        let mut document = parse("old_name = 21");
+        migrate(&mut document, 0, &migrations);
+        assert_eq!(document, parse("new_name = 42\nadded = true"));
+
+        let mut document = parse("new_name = 21");
+        migrate(&mut document, 1, &migrations);
+        assert_eq!(document, parse("new_name = 42\nadded = true"));
+
+        let mut document = parse("new_name = 21");
+        migrate(&mut document, 3, &migrations);
+        assert_eq!(document, parse("new_name = 21"));
+    }
+
+    #[test]
+    fn older_documents_are_migrated() {
+        let (app, notice) = from_document(parse(
+            "version = 0\nsim_speed = 2.5\n[boid_settings]\nseparation_distance = 0.04",
+        ));
+        assert_eq!(app.sim_speed, 2.5);
+        assert_eq!(app.boid_settings.separation_distance, 0.04);
+        assert!(notice.is_some_and(|notice| notice.contains("migrated")));
+
+        let document = to_document(&app).unwrap();
+        assert_eq!(
+            document[VERSION_KEY].as_integer(),
+            Some(i64::from(STATE_VERSION))
+        );
+    }
+
+    #[test]
+    fn unknown_settings_survive_a_round_trip() {
+        let (app, notice) = from_document(parse(&format!(
+            "version = {STATE_VERSION}\nfuture_setting = 3\n[boid_settings]\nseparation_distance = 0.04\nfuture_rule = \"x\"",
+        )));
+        assert_eq!(notice, None);
+        assert_eq!(app.boid_settings.separation_distance, 0.04);
+
+        let document = to_document(&app).unwrap();
+        assert_eq!(document["future_setting"].as_integer(), Some(3));
+        assert_eq!(document["boid_settings"]["future_rule"].as_str(), Some("x"));
+        assert_eq!(
+            document["boid_settings"]["separation_distance"].as_float(),
+            Some(f64::from(0.04f32))
+        );
+    }
+
+    #[test]
+    fn newer_documents_are_read_and_kept() {
+        let newer = STATE_VERSION + 1;
+        let (app, notice) = from_document(parse(&format!(
+            "version = {newer}\nsim_speed = 2.5\nnewer_setting = [1, 2]",
+        )));
+        assert_eq!(app.sim_speed, 2.5);
+        assert!(notice.is_some_and(|notice| notice.contains("newer version")));
+
+        let document = to_document(&app).unwrap();
+        assert_eq!(document["newer_setting"], toml::Value::from(vec![1, 2]));
+    }
+
+    #[test]
+    fn newer_choices_are_kept_unless_changed() {
+        let newer = STATE_VERSION + 1;
+        let (mut app, notice) = from_document(parse(&format!(
+            "version = {newer}\nsim_speed = 2.5\n[boid_settings]\nintegrator = \"FutureIntegrator\"\n\
+            separation_distance = 0.04",
+        )));
+        assert_eq!(app.sim_speed, 2.5);
+        assert_eq!(app.boid_settings.separation_distance, 0.04);
+        assert!(notice.is_some_and(|notice| notice.contains("boid_settings.integrator")));
+
+        let document = to_document(&app).unwrap();
+        assert_eq!(
+            document["boid_settings"]["integrator"].as_str(),
+            Some("FutureIntegrator")
+        );
+        assert_eq!(
+            document["boid_settings"]["separation_distance"].as_float(),
+            Some(f64::from(0.04f32))
+        );
+
+        app.boid_settings.integrator = Integrator::Rk2;
+        let document = to_document(&app).unwrap();
+        assert_eq!(
+            document["boid_settings"]["integrator"].as_str(),
+            Some("Rk2")
+        );
+    }
+}
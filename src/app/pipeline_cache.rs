@@ -0,0 +1,121 @@
+//! Keeps the compiled pipelines in the data directory between runs, for adapters that support `PIPELINE_CACHE`.
+//!
+//! Every adapter gets its own file, named after `wgpu::util::pipeline_cache_key` and a hash of everything else that
+//! makes the data stale: the driver version and the shader source. A file of an adapter that doesn't match the hash is
+//! deleted the next time the cache is saved.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use super::APP_ID;
+
+/// The shader that every pipeline is created from. The pipelines are compiled from this, not from the generated Rust.
+const SHADER_SOURCE: &str = include_str!("../shaders/boids.wgsl");
+
+/// A pipeline cache, together with the file it was loaded from and is saved to.
+pub struct PipelineCacheFile {
+    cache: Arc<wgpu::PipelineCache>,
+    path: PathBuf,
+    /// Removed from the file names of stale caches of the same adapter
+    adapter_key: String,
+}
+
+impl PipelineCacheFile {
+    /// Creates a pipeline cache with the data that was saved for `adapter`, if there is any. Returns `None` if
+    /// `device` doesn't support pipeline caches, or there is no data directory to save it in.
+    pub fn load(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return None;
+        }
+        let info = adapter.get_info();
+        let adapter_key = wgpu::util::pipeline_cache_key(&info)?;
+        let Some(dir) = eframe::storage_dir(APP_ID) else {
+            log::warn!("Pipeline cache disabled: there is no data directory");
+            return None;
+        };
+
+        // `DefaultHasher::new` always hashes the same way within the same Rust version, and a different hash only
+        // means a cache miss
+        let mut hasher = DefaultHasher::new();
+        info.driver.hash(&mut hasher);
+        info.driver_info.hash(&mut hasher);
+        SHADER_SOURCE.hash(&mut hasher);
+        let path = dir
+            .join("pipeline_cache")
+            .join(format!("{adapter_key}_{:016x}.bin", hasher.finish()));
+
+        let data = std::fs::read(&path).ok();
+        // SAFETY: the data was only ever written by `save` with the data of a cache of the same adapter and driver.
+        // wgpu and the driver validate it again, and `fallback` creates an empty cache if they reject it.
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("pipeline cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        log::info!(
+            "{} the pipeline cache at {}",
+            if data.is_some() { "Loaded" } else { "Created" },
+            path.display()
+        );
+        Some(Self {
+            cache: Arc::new(cache),
+            path,
+            adapter_key,
+        })
+    }
+
+    pub fn cache(&self) -> Arc<wgpu::PipelineCache> {
+        self.cache.clone()
+    }
+
+    /// Writes the cache to its file, and deletes the stale files of the same adapter.
+    pub fn save(&self) {
+        let Some(data) = self.cache.get_data() else {
+            return;
+        };
+        let Some(dir) = self.path.parent() else {
+            return;
+        };
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            log::warn!("Failed to create {}: {err}", dir.display());
+            return;
+        }
+
+        // write a temporary file first, so a crash while saving doesn't leave a truncated cache behind
+        let temp_path = self.path.with_extension("tmp");
+        if let Err(err) =
+            std::fs::write(&temp_path, data).and_then(|()| std::fs::rename(&temp_path, &self.path))
+        {
+            log::warn!(
+                "Failed to save the pipeline cache to {}: {err}",
+                self.path.display()
+            );
+            return;
+        }
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let prefix = format!("{}_", self.adapter_key);
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_stale = path != self.path
+                && path.extension().is_some_and(|extension| extension == "bin")
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix));
+            if is_stale {
+                log::info!("Deleting the stale pipeline cache {}", path.display());
+                if let Err(err) = std::fs::remove_file(&path) {
+                    log::warn!("Failed to delete {}: {err}", path.display());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Presets are the whole app state in a TOML file, which `--preset` starts with instead of the state the last run saved.
+//! They are the same versioned documents that the state is saved as, see `persistence`. Settings that are missing from
+//! a preset keep their defaults, so presets can also be written by hand.
+
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use super::{data_dir, persistence, RendererApp};
+
+/// Also returns a notice for the user if the preset was migrated or couldn't be read completely.
+pub fn load(path: &Path) -> Result<(RendererApp, Option<String>), String> {
+    let toml = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+    let document = toml
+        .parse()
+        .map_err(|err| format!("invalid preset {}: {err}", path.display()))?;
+    Ok(persistence::from_document(document))
+}
+
+/// Writes the state of `app` into a new preset in the data directory.
+pub fn save(app: &RendererApp) -> Result<PathBuf, String> {
+    let document = persistence::to_document(app).map_err(|err| err.to_string())?;
+    let toml = toml::to_string_pretty(&document).map_err(|err| err.to_string())?;
+    let (path, mut file) = data_dir::create_file("presets", "", "toml")?;
+    file.write_all(toml.as_bytes())
+        .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+    Ok(path)
+}
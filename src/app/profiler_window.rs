@@ -0,0 +1,495 @@
+//! Flamegraphs of the CPU and GPU profilers inside of the app, without attaching puffin_viewer to the puffin servers.
+//! On native, the frames can also be recorded into `.puffin` captures, which can be opened again later without running
+//! the simulation, or into Chrome traces of both profilers for Perfetto and `chrome://tracing`.
+
+use std::sync::{Arc, Mutex};
+
+use puffin::{FrameSink, FrameSinkId, FrameView};
+use puffin_egui::{MaybeMutRef, ProfilerUi};
+
+use crate::{sim::ParticleSystem, PUFFIN_GPU_PROFILER};
+
+/// A `GlobalProfiler` that frames can be collected from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+enum LiveProfiler {
+    /// `puffin::GlobalProfiler`, which the `profile_function!` and `profile_scope!` scopes are reported to
+    Cpu,
+    /// `PUFFIN_GPU_PROFILER`, which the GPU timer queries are reported to
+    Gpu,
+}
+
+impl LiveProfiler {
+    const ALL: [Self; 2] = [Self::Cpu, Self::Gpu];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Cpu => "CPU",
+            Self::Gpu => "GPU",
+        }
+    }
+
+    fn add_sink(self, sink: FrameSink) -> FrameSinkId {
+        match self {
+            Self::Cpu => puffin::GlobalProfiler::lock().add_sink(sink),
+            Self::Gpu => PUFFIN_GPU_PROFILER.lock().unwrap().add_sink(sink),
+        }
+    }
+
+    fn remove_sink(self, id: FrameSinkId) {
+        match self {
+            Self::Cpu => _ = puffin::GlobalProfiler::lock().remove_sink(id),
+            Self::Gpu => _ = PUFFIN_GPU_PROFILER.lock().unwrap().remove_sink(id),
+        }
+    }
+}
+
+/// Collects the frames of a profiler for as long as it exists.
+struct LiveView {
+    profiler: LiveProfiler,
+    sink_id: FrameSinkId,
+    view: Arc<Mutex<FrameView>>,
+}
+
+impl LiveView {
+    fn new(profiler: LiveProfiler, view: FrameView) -> Self {
+        let view = Arc::new(Mutex::new(view));
+        let sink_view = view.clone();
+        let sink_id = profiler.add_sink(Box::new(move |frame| {
+            sink_view.lock().unwrap().add_frame(frame)
+        }));
+        Self {
+            profiler,
+            sink_id,
+            view,
+        }
+    }
+}
+
+impl Drop for LiveView {
+    fn drop(&mut self) {
+        self.profiler.remove_sink(self.sink_id);
+    }
+}
+
+/// What the flamegraph shows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+enum Source {
+    Live(LiveProfiler),
+    /// The capture that was opened last
+    #[cfg(not(target_arch = "wasm32"))]
+    Capture,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ProfilerWindow {
+    pub open: bool,
+    source: Source,
+    #[serde(skip)]
+    profiler_ui: ProfilerUi,
+    /// Only exists while the window is open, since it keeps the frames of the selected profiler around
+    #[serde(skip)]
+    live: Option<LiveView>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    captures: captures::Captures,
+}
+
+impl Default for ProfilerWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            source: Source::Live(LiveProfiler::Cpu),
+            profiler_ui: ProfilerUi::default(),
+            live: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            captures: Default::default(),
+        }
+    }
+}
+
+impl ProfilerWindow {
+    /// Hands the GPU frames to a Chrome trace that is being recorded, and writes a recording once it has enough frames.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    pub fn update(&mut self, particles: &mut ParticleSystem) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.captures.update(particles);
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Profiler")
+            .open(&mut open)
+            .default_size([800., 600.])
+            .show(ctx, |ui| self.ui(ui));
+        self.open = open;
+
+        if !self.open {
+            self.live = None;
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let previous_source = self.source;
+        ui.horizontal(|ui| {
+            for profiler in LiveProfiler::ALL {
+                ui.selectable_value(&mut self.source, Source::Live(profiler), profiler.label());
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(name) = self.captures.opened_name() {
+                ui.selectable_value(&mut self.source, Source::Capture, name);
+            }
+
+            ui.separator();
+            let mut scopes_on = puffin::are_scopes_on();
+            if ui.checkbox(&mut scopes_on, "Profile CPU scopes").changed() {
+                puffin::set_scopes_on(scopes_on);
+            }
+        });
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let live_profiler = match self.source {
+                Source::Live(profiler) => Some(profiler),
+                Source::Capture => None,
+            };
+            ui.separator();
+            if self.captures.ui(ui, live_profiler) {
+                self.source = Source::Capture;
+            }
+        }
+        ui.separator();
+
+        if self.source != previous_source {
+            // the selection and zoom of one profiler don't make sense for another
+            self.profiler_ui.reset();
+        }
+        match self.source {
+            Source::Live(profiler) => {
+                if !matches!(&self.live, Some(live) if live.profiler == profiler) {
+                    self.live = Some(LiveView::new(profiler, FrameView::default()));
+                }
+                let Some(live) = &self.live else {
+                    return;
+                };
+                let mut view = live.view.lock().unwrap();
+                self.profiler_ui.ui(ui, &mut MaybeMutRef::MutRef(&mut view));
+                // keep the flamegraph moving
+                ui.ctx().request_repaint();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::Capture => match self.captures.opened_view() {
+                Some(view) => self.profiler_ui.ui(ui, &mut MaybeMutRef::Ref(view)),
+                None => {
+                    ui.label("No capture is open");
+                }
+            },
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod captures {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use egui::Widget;
+    use puffin::FrameView;
+
+    use super::{LiveProfiler, LiveView};
+    use crate::{
+        app::{data_dir, APP_ID},
+        sim::{self, GpuFrameTrace, ParticleSystem},
+    };
+
+    /// What a recording is written as.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    enum CaptureFormat {
+        /// The selected profiler, which can be opened again in the profiler window
+        Puffin,
+        /// The CPU scopes and the GPU timer queries on one timeline, for Perfetto and `chrome://tracing`
+        ChromeTrace,
+    }
+
+    impl CaptureFormat {
+        const ALL: [Self; 2] = [Self::Puffin, Self::ChromeTrace];
+
+        fn label(self) -> &'static str {
+            match self {
+                Self::Puffin => ".puffin",
+                Self::ChromeTrace => "Chrome trace",
+            }
+        }
+    }
+
+    /// A capture that collects frames until it has enough of them to be written.
+    struct Recording {
+        format: CaptureFormat,
+        live: LiveView,
+        num_frames: usize,
+        /// Only collected for Chrome traces
+        gpu_frames: Vec<GpuFrameTrace>,
+    }
+
+    /// Records captures into the data directory, and opens them again.
+    pub struct Captures {
+        /// How many frames the next recording records
+        num_frames: usize,
+        format: CaptureFormat,
+        recording: Option<Recording>,
+        /// Captures in the data directory, newest first. Listed again whenever a capture was written.
+        listed: Option<Vec<PathBuf>>,
+        /// Path of a capture to open that isn't in the data directory
+        path_input: String,
+        opened: Option<(PathBuf, FrameView)>,
+        error: Option<String>,
+    }
+
+    impl Default for Captures {
+        fn default() -> Self {
+            Self {
+                num_frames: 300,
+                format: CaptureFormat::Puffin,
+                recording: None,
+                listed: None,
+                path_input: String::new(),
+                opened: None,
+                error: None,
+            }
+        }
+    }
+
+    /// The directory in the data directory that captures are recorded into.
+    const CAPTURES: &str = "captures";
+
+    fn captures_dir() -> Option<PathBuf> {
+        eframe::storage_dir(APP_ID).map(|dir| dir.join(CAPTURES))
+    }
+
+    fn list_captures() -> Vec<PathBuf> {
+        let Some(dir) = captures_dir() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut captures: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "puffin")
+            })
+            .collect();
+        // the names start with the time they were recorded at
+        captures.sort_unstable_by(|a, b| b.cmp(a));
+        captures
+    }
+
+    fn write_capture(recording: &Recording, view: &FrameView) -> Result<PathBuf, String> {
+        let (path, file) = match recording.format {
+            CaptureFormat::Puffin => data_dir::create_file(
+                CAPTURES,
+                &format!("-{}", recording.live.profiler.label().to_lowercase()),
+                "puffin",
+            ),
+            CaptureFormat::ChromeTrace => data_dir::create_file(CAPTURES, "-trace", "json"),
+        }?;
+        let mut file = std::io::BufWriter::new(file);
+        let result = match recording.format {
+            CaptureFormat::Puffin => view.write(&mut file).map_err(|err| err.to_string()),
+            CaptureFormat::ChromeTrace => sim::write_chrome_trace(
+                &mut file,
+                view.scope_collection(),
+                view.recent_frames().map(Arc::as_ref),
+                &recording.gpu_frames,
+            )
+            .map_err(|err| err.to_string()),
+        };
+        result.map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+        Ok(path)
+    }
+
+    fn read_capture(path: &Path) -> Result<FrameView, String> {
+        let mut file = std::fs::File::open(path)
+            .map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+        FrameView::read(&mut file)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string())
+    }
+
+    impl Captures {
+        /// Traces the GPU frames while a Chrome trace is being recorded, and writes the recording once it has enough
+        /// frames, whether the window is open or not.
+        pub fn update(&mut self, particles: &mut ParticleSystem) {
+            if let Some(recording) = &mut self.recording {
+                if recording.format == CaptureFormat::ChromeTrace {
+                    recording
+                        .gpu_frames
+                        .extend(particles.take_gpu_frame_traces());
+                }
+            }
+            self.finish_recording();
+            particles.set_trace_gpu_frames(
+                self.recording
+                    .as_ref()
+                    .is_some_and(|recording| recording.format == CaptureFormat::ChromeTrace),
+            );
+        }
+
+        pub fn opened_name(&self) -> Option<String> {
+            self.opened.as_ref().map(|(path, _)| file_name(path))
+        }
+
+        pub fn opened_view(&self) -> Option<&FrameView> {
+            self.opened.as_ref().map(|(_, view)| view)
+        }
+
+        fn open(&mut self, path: PathBuf) -> bool {
+            match read_capture(&path) {
+                Ok(view) => {
+                    self.opened = Some((path, view));
+                    self.error = None;
+                    true
+                }
+                Err(err) => {
+                    log::warn!("Failed to open the capture: {err}");
+                    self.error = Some(err);
+                    false
+                }
+            }
+        }
+
+        /// Writes the recording once it has enough frames.
+        fn finish_recording(&mut self) {
+            let Some(recording) = &self.recording else {
+                return;
+            };
+            let view = recording.live.view.lock().unwrap();
+            if view.recent_frames().count() < recording.num_frames {
+                return;
+            }
+            let result = write_capture(recording, &view);
+            drop(view);
+            self.recording = None;
+            match result {
+                Ok(path) => {
+                    log::info!("Recorded {}", path.display());
+                    self.listed = None;
+                    self.error = None;
+                }
+                Err(err) => {
+                    log::warn!("Failed to record a capture: {err}");
+                    self.error = Some(err);
+                }
+            }
+        }
+
+        /// Records `live_profiler`, or opens a capture. Returns whether a capture was opened.
+        pub fn ui(&mut self, ui: &mut egui::Ui, live_profiler: Option<LiveProfiler>) -> bool {
+            let mut opened = false;
+
+            ui.horizontal(|ui| match &self.recording {
+                Some(recording) => {
+                    let num_recorded = recording.live.view.lock().unwrap().recent_frames().count();
+                    egui::ProgressBar::new(num_recorded as f32 / recording.num_frames as f32)
+                        .text(format!(
+                            "Recording {num_recorded} of {} {} frames",
+                            recording.num_frames,
+                            recording.live.profiler.label()
+                        ))
+                        .desired_width(240.)
+                        .ui(ui);
+                    if ui.button("Cancel").clicked() {
+                        self.recording = None;
+                    }
+                    ui.ctx().request_repaint();
+                }
+                None => {
+                    // Chrome traces always contain both profilers
+                    let profiler = match self.format {
+                        CaptureFormat::Puffin => live_profiler,
+                        CaptureFormat::ChromeTrace => Some(LiveProfiler::Cpu),
+                    };
+                    ui.add_enabled_ui(profiler.is_some(), |ui| {
+                        let clicked = ui
+                            .button("Record")
+                            .on_hover_text("Record the next frames into the data directory")
+                            .on_disabled_hover_text("Select the CPU or GPU profiler to record it")
+                            .clicked();
+                        egui::DragValue::new(&mut self.num_frames)
+                            .range(1..=10_000)
+                            .suffix(" frames")
+                            .ui(ui);
+                        if let (true, Some(profiler)) = (clicked, profiler) {
+                            let mut view = FrameView::default();
+                            view.set_max_recent(self.num_frames);
+                            self.recording = Some(Recording {
+                                format: self.format,
+                                live: LiveView::new(profiler, view),
+                                num_frames: self.num_frames,
+                                gpu_frames: Vec::new(),
+                            });
+                        }
+                    });
+                    egui::ComboBox::from_id_salt("capture format")
+                        .selected_text(self.format.label())
+                        .show_ui(ui, |ui| {
+                            for format in CaptureFormat::ALL {
+                                ui.selectable_value(&mut self.format, format, format.label());
+                            }
+                        });
+                }
+            });
+
+            ui.collapsing("Captures", |ui| {
+                if ui.button("Refresh").clicked() {
+                    self.listed = None;
+                }
+                let listed = self.listed.get_or_insert_with(list_captures);
+                if listed.is_empty() {
+                    ui.label("Nothing was recorded yet");
+                }
+                let mut clicked = None;
+                egui::ScrollArea::vertical()
+                    .max_height(120.)
+                    .show(ui, |ui| {
+                        for path in listed.iter() {
+                            let is_open = self
+                                .opened
+                                .as_ref()
+                                .is_some_and(|(opened, _)| opened == path);
+                            if ui.selectable_label(is_open, file_name(path)).clicked() {
+                                clicked = Some(path.clone());
+                            }
+                        }
+                    });
+                if let Some(path) = clicked {
+                    opened |= self.open(path);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    let response = ui.text_edit_singleline(&mut self.path_input);
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Open").clicked() || submitted {
+                        opened |= self.open(PathBuf::from(self.path_input.trim()));
+                    }
+                });
+            });
+
+            if let Some(error) = &self.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            opened
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Records the boids into an animated GIF or APNG without the UI.
+//!
+//! A recording steps the simulation by the same number of `sim_delta_time` steps for every frame, and waits for each
+//! frame to be captured before the next one is simulated. So it doesn't depend on how fast the app runs or how slow
+//! capturing is.
+//!
+//! The frames can also be written as they are, one raw RGBA image after the other, for encoders like ffmpeg.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use eframe::egui_wgpu::RenderState;
+use egui::Widget;
+
+use super::data_dir;
+use crate::{
+    shaders::boids,
+    sim::{DrawOptions, ImageRequest, ParticleSystem},
+};
+
+/// Recordings are at most this large on each side.
+const MAX_FRAME_SIZE: u32 = 4096;
+
+/// Frames that a recording waits for after starting over, until the new flock was passed on to the simulation and is
+/// what gets drawn.
+const START_OVER_FRAMES: u32 = 2;
+
+/// How hard the GIF encoder works on the palette of a frame, from 1 to 30, where 1 is the slowest and best.
+const GIF_QUANTIZATION_SPEED: i32 = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+enum RecordingFormat {
+    Gif,
+    Apng,
+}
+
+impl RecordingFormat {
+    const ALL: [Self; 2] = [Self::Gif, Self::Apng];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Gif => "GIF",
+            Self::Apng => "APNG",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Apng => "png",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Recorder {
+    format: RecordingFormat,
+    size: [u32; 2],
+    /// Simulation steps from one frame to the next
+    steps_per_frame: u32,
+    /// The recording stops by itself after this many frames, unless it is 0
+    max_frames: u32,
+    /// Also write the frames as raw RGBA
+    raw_frames: bool,
+    /// Start the flock and the timeline over when recording starts
+    start_over: bool,
+    /// Set when a recording started over, until the flock and the timeline were
+    #[serde(skip)]
+    started_over: bool,
+    #[serde(skip)]
+    recording: Option<Recording>,
+    /// What the last recording was saved to, or why it failed
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self {
+            format: RecordingFormat::Gif,
+            size: [512, 512],
+            steps_per_frame: 4,
+            max_frames: 300,
+            raw_frames: false,
+            start_over: true,
+            started_over: false,
+            recording: None,
+            status: None,
+        }
+    }
+}
+
+impl Recorder {
+    /// How many steps the simulation runs this frame, if it is being recorded. That's the same for every frame of a
+    /// recording, even while the simulation is paused.
+    pub fn recording_steps(&self) -> Option<u32> {
+        let recording = self.recording.as_ref()?;
+        // nothing moves until the recording captures its first frame
+        Some(if recording.frames_to_skip > 0 {
+            0
+        } else {
+            self.steps_per_frame
+        })
+    }
+
+    /// Whether a recording just started, which starts the flock and the timeline over.
+    pub fn take_start_over(&mut self) -> bool {
+        std::mem::take(&mut self.started_over)
+    }
+
+    /// `step_time` is how much simulation time a step is, with the speed multiplier.
+    pub fn ui(&mut self, ui: &mut egui::Ui, step_time: f32) {
+        let frame_time = self.steps_per_frame as f32 * step_time;
+        let [width, height] = self.size();
+        ui.add_enabled_ui(self.recording.is_none(), |ui| {
+            egui::ComboBox::from_label("Format")
+                .selected_text(self.format.label())
+                .show_ui(ui, |ui| {
+                    for format in RecordingFormat::ALL {
+                        ui.selectable_value(&mut self.format, format, format.label());
+                    }
+                });
+            ui.horizontal(|ui| {
+                egui::DragValue::new(&mut self.size[0])
+                    .range(1..=MAX_FRAME_SIZE)
+                    .suffix(" px")
+                    .ui(ui);
+                ui.label("×");
+                egui::DragValue::new(&mut self.size[1])
+                    .range(1..=MAX_FRAME_SIZE)
+                    .suffix(" px")
+                    .ui(ui);
+                ui.label("Size");
+            });
+            egui::Slider::new(&mut self.steps_per_frame, 1..=32)
+                .text("Steps per Frame")
+                .ui(ui)
+                .on_hover_text(format!(
+                    "Every frame is {frame_time:.3}s of simulation time later than the one before, and is shown that \
+                    long, which is {:.1} frames per second",
+                    frame_time.recip()
+                ));
+            egui::Slider::new(&mut self.max_frames, 0..=3000)
+                .text("Frames")
+                .ui(ui)
+                .on_hover_text("The recording stops by itself after this many frames, or only when it is stopped if this is 0");
+            ui.checkbox(&mut self.raw_frames, "Raw Frames").on_hover_text(format!(
+                "Also writes the frames one after the other as raw RGBA, which ffmpeg reads with \
+                -f rawvideo -pixel_format rgba -video_size {width}x{height} -framerate {:.3}",
+                frame_time.recip()
+            ));
+            ui.checkbox(&mut self.start_over, "Start Over").on_hover_text(
+                "Places the boids with the seed again and plays the timeline from the start when recording starts",
+            );
+        });
+
+        match &self.recording {
+            None => {
+                if ui
+                    .button("Record")
+                    .on_hover_text(
+                        "Records the boids without the UI to the recordings in the data directory",
+                    )
+                    .clicked()
+                {
+                    match self.start(frame_time) {
+                        Ok(recording) => {
+                            log::info!("Started recording to {}", recording.path.display());
+                            self.status = None;
+                            self.started_over = self.start_over;
+                            self.recording = Some(recording);
+                        }
+                        Err(err) => {
+                            log::error!("Failed to start recording: {err}");
+                            self.status = Some(Err(format!("Failed to start recording: {err}")));
+                        }
+                    }
+                }
+            }
+            Some(recording) => {
+                ui.label(format!(
+                    "Recorded {} frames, {:.2}s of simulation time",
+                    recording.num_frames,
+                    recording.num_frames as f64 * recording.frame_time
+                ));
+                if ui.button("Stop").clicked() {
+                    self.finish();
+                }
+            }
+        }
+
+        match &self.status {
+            Some(Ok(status)) => {
+                ui.label(status);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            None => {}
+        }
+    }
+
+    /// Captures a frame of the recording, if there is one, before this frame's steps are simulated. The arguments are
+    /// what the canvas is drawn with this frame.
+    pub fn update(
+        &mut self,
+        render_state: &RenderState,
+        render_params: boids::RenderParams,
+        draw_options: DrawOptions,
+        background: egui::Color32,
+    ) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if recording.frames_to_skip > 0 {
+            recording.frames_to_skip -= 1;
+            return;
+        }
+        let image = ImageRequest {
+            width: recording.size[0],
+            height: recording.size[1],
+            render_params,
+            draw_options,
+            background: background.to_array(),
+        };
+        let renderer = render_state.renderer.read();
+        let Some(particles) = renderer.callback_resources.get::<ParticleSystem>() else {
+            return;
+        };
+        let result = recording.add_frame(render_state, particles, &image);
+        drop(renderer);
+
+        if let Err(err) = result {
+            log::error!("Failed to record a frame: {err}");
+            self.status = Some(Err(format!("Failed to record a frame: {err}")));
+            self.finish();
+        } else if self.max_frames > 0 && recording.num_frames >= self.max_frames {
+            self.finish();
+        }
+    }
+
+    /// The size of the frames. The size that was saved, or that a preset has, isn't limited by the UI.
+    fn size(&self) -> [u32; 2] {
+        self.size.map(|side| side.clamp(1, MAX_FRAME_SIZE))
+    }
+
+    /// Creates the files of a recording, whose frames are `frame_time` seconds of simulation time apart.
+    fn start(&self, frame_time: f32) -> Result<Recording, String> {
+        let size = self.size();
+        // an APNG starts with its number of frames, so it is encoded from the raw frames once they are all recorded
+        let ((path, file), raw) = if self.raw_frames || self.format == RecordingFormat::Apng {
+            let [file, (raw_path, raw)] =
+                data_dir::create_files("recordings", "", [self.format.extension(), "rgba"])?;
+            (file, Some((raw_path, BufWriter::new(raw))))
+        } else {
+            (
+                data_dir::create_file("recordings", "", self.format.extension())?,
+                None,
+            )
+        };
+
+        let (gif, apng) = match self.format {
+            RecordingFormat::Gif => {
+                let mut encoder =
+                    gif::Encoder::new(BufWriter::new(file), size[0] as u16, size[1] as u16, &[])
+                        .map_err(|err| err.to_string())?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|err| err.to_string())?;
+                (Some(encoder), None)
+            }
+            RecordingFormat::Apng => (None, Some(file)),
+        };
+
+        Ok(Recording {
+            path,
+            size,
+            frame_time: f64::from(frame_time),
+            num_frames: 0,
+            frames_to_skip: if self.start_over {
+                START_OVER_FRAMES
+            } else {
+                0
+            },
+            gif,
+            apng,
+            raw,
+            keep_raw: self.raw_frames,
+        })
+    }
+
+    /// Ends the recording and writes what is left of it.
+    fn finish(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        let num_frames = recording.num_frames;
+        match recording.finish() {
+            Ok(paths) => {
+                let paths: Vec<String> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                log::info!(
+                    "Saved a recording of {num_frames} frames to {}",
+                    paths.join(", ")
+                );
+                // an error that stopped the recording stays visible
+                if !matches!(self.status, Some(Err(_))) {
+                    self.status = Some(Ok(format!("Saved {}", paths.join(", "))));
+                }
+            }
+            Err(err) => {
+                log::error!("Failed to save the recording: {err}");
+                self.status = Some(Err(format!("Failed to save the recording: {err}")));
+            }
+        }
+    }
+}
+
+struct Recording {
+    /// Where the GIF or APNG is written to
+    path: PathBuf,
+    /// At most `MAX_FRAME_SIZE` on each side, so the sides also fit into the `u16`s of a GIF
+    size: [u32; 2],
+    /// Seconds of simulation time from one frame to the next
+    frame_time: f64,
+    num_frames: u32,
+    /// Frames to wait for before capturing the first one
+    frames_to_skip: u32,
+    /// Only set when recording a GIF, which is encoded frame by frame
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+    /// Only set when recording an APNG, which is encoded into this once the recording is finished
+    apng: Option<File>,
+    /// The raw frames and their path, when they are kept or an APNG is encoded from them
+    raw: Option<(PathBuf, BufWriter<File>)>,
+    /// Whether the raw frames are kept once the recording is finished
+    keep_raw: bool,
+}
+
+impl Recording {
+    fn add_frame(
+        &mut self,
+        render_state: &RenderState,
+        particles: &ParticleSystem,
+        image: &ImageRequest,
+    ) -> Result<(), String> {
+        let mut pixels = Vec::with_capacity(image.width as usize * image.height as usize * 4);
+        particles
+            .render_image(&render_state.device, &render_state.queue, image, |rows| {
+                pixels.extend_from_slice(rows);
+                Ok(())
+            })
+            .map_err(|err| err.to_string())?;
+
+        if let Some((path, raw)) = &mut self.raw {
+            raw.write_all(&pixels)
+                .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+        }
+        let delay = self.frame_delay(self.num_frames, 100);
+        if let Some(gif) = &mut self.gif {
+            let mut frame = gif::Frame::from_rgba_speed(
+                self.size[0] as u16,
+                self.size[1] as u16,
+                &mut pixels,
+                GIF_QUANTIZATION_SPEED,
+            );
+            frame.delay = delay;
+            gif.write_frame(&frame).map_err(|err| err.to_string())?;
+        }
+        self.num_frames += 1;
+        Ok(())
+    }
+
+    /// How long the frame at `index` is shown, in `1 / units_per_second` seconds. The delays are rounded so that
+    /// they add up to the time of the whole recording, instead of drifting away from it.
+    fn frame_delay(&self, index: u32, units_per_second: u32) -> u16 {
+        let end =
+            |index: u32| (f64::from(index) * self.frame_time * f64::from(units_per_second)).round();
+        (end(index + 1) - end(index)).clamp(0., f64::from(u16::MAX)) as u16
+    }
+
+    /// Writes the rest of the files, and returns the paths of the ones that were kept.
+    fn finish(mut self) -> Result<Vec<PathBuf>, String> {
+        let mut paths = Vec::new();
+        if let Some(gif) = self.gif.take() {
+            gif.into_inner()
+                .and_then(|mut file| file.flush())
+                .map_err(|err| format!("failed to write {}: {err}", self.path.display()))?;
+            paths.push(self.path.clone());
+        }
+        if let Some((raw_path, mut raw)) = self.raw.take() {
+            raw.flush()
+                .map_err(|err| format!("failed to write {}: {err}", raw_path.display()))?;
+            drop(raw);
+            if let Some(file) = self.apng.take() {
+                let apng = self.encode_apng(file, &raw_path);
+                if !self.keep_raw {
+                    _ = std::fs::remove_file(&raw_path);
+                }
+                if apng.is_err() {
+                    _ = std::fs::remove_file(&self.path);
+                }
+                apng?;
+                paths.push(self.path.clone());
+            }
+            if self.keep_raw {
+                paths.push(raw_path);
+            }
+        }
+        Ok(paths)
+    }
+
+    fn encode_apng(&self, file: File, raw_path: &Path) -> Result<(), String> {
+        if self.num_frames == 0 {
+            return Err("an APNG needs at least one frame".to_owned());
+        }
+        let mut raw = BufReader::new(
+            File::open(raw_path)
+                .map_err(|err| format!("failed to open {}: {err}", raw_path.display()))?,
+        );
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.size[0], self.size[1]);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.num_frames, 0)
+            .map_err(|err| err.to_string())?;
+        let mut png = encoder.write_header().map_err(|err| err.to_string())?;
+        let mut pixels = vec![0; self.size[0] as usize * self.size[1] as usize * 4];
+        for index in 0..self.num_frames {
+            raw.read_exact(&mut pixels)
+                .map_err(|err| format!("failed to read {}: {err}", raw_path.display()))?;
+            png.set_frame_delay(self.frame_delay(index, 1000), 1000)
+                .map_err(|err| err.to_string())?;
+            png.write_image_data(&pixels)
+                .map_err(|err| err.to_string())?;
+        }
+        png.finish().map_err(|err| err.to_string())
+    }
+}
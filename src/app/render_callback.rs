@@ -0,0 +1,71 @@
+//! Draws the boids inside of egui, on top of [`ParticleSystem`].
+
+use eframe::egui_wgpu::CallbackTrait;
+use puffin::current_function_name;
+
+use crate::sim::{DrawOptions, FrameInput, ParticleSystem};
+
+// TODO: While `eframe` does handle a lot of the boilerplate for me, it wasn't really meant for a situation where I am
+// mostly doing my own custom rendering. The main challenge is that the only way to do custom rendering is through a
+// struct that implements `CallbackTrait`, which I have several nitpicks with:
+//   - I don't have direct access to the `wgpu::Surface` or `wgpu::SurfaceTexture`. The `render` function uses the same
+//     `wgpu::RenderPass` that the rest of egui uses to render to the surface, but I can't make multiple
+//     `wgpu::RenderPass`s that all target the `wgpu::SurfaceTexture`
+//   - `CustomCallback` must be recreated every frame. In fact `new_paint_callback` allocates a new Arc every frame.
+// `winit_frontend` uses `winit` and `egui` directly instead, behind the `winit` feature.
+pub struct RenderCallback {
+    pub frame: FrameInput,
+    /// New contents of the color lookup texture, see `Gradient::bake`
+    pub color_lut: Option<Vec<[u8; 4]>>,
+    pub draw_options: DrawOptions,
+}
+
+impl RenderCallback {
+    /// Records this frame of the simulation into `encoder`, see [`ParticleSystem::record`].
+    pub fn record(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        particles: &mut ParticleSystem,
+    ) {
+        if let Some(color_lut) = &self.color_lut {
+            particles.set_color_lut(queue, color_lut);
+        }
+        particles.record(device, queue, encoder, &self.frame);
+    }
+}
+
+impl CallbackTrait for RenderCallback {
+    fn prepare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        _screen_descriptor: &eframe::egui_wgpu::ScreenDescriptor,
+        _command_encoder: &mut wgpu::CommandEncoder,
+        callback_resources: &mut eframe::egui_wgpu::CallbackResources,
+    ) -> Vec<wgpu::CommandBuffer> {
+        if let Some(particles) = callback_resources.get_mut::<ParticleSystem>() {
+            // egui only submits its own encoder after every callback was prepared, but the readbacks have to be mapped
+            // after the copies into them were submitted
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(current_function_name!()),
+            });
+            self.record(device, queue, &mut encoder, particles);
+            queue.submit(Some(encoder.finish()));
+            particles.after_submit(device, queue);
+        }
+        vec![]
+    }
+
+    fn paint(
+        &self,
+        _info: egui::PaintCallbackInfo,
+        rpass: &mut wgpu::RenderPass<'static>,
+        callback_resources: &eframe::egui_wgpu::CallbackResources,
+    ) {
+        if let Some(particles) = callback_resources.get::<ParticleSystem>() {
+            particles.draw(rpass, &self.draw_options);
+        }
+    }
+}
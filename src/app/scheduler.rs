@@ -0,0 +1,128 @@
+//! Decides how many fixed-size simulation steps to run each frame.
+//!
+//! The simulation always advances in steps of `sim_delta_time`, so a slow frame means more steps have to be queued to
+//! catch up. Left unchecked that turns into a spiral where one long frame queues thousands of dispatches, which makes
+//! the next frame even longer. The scheduler caps the number of steps per frame, both with a hard limit and with a
+//! budget based on how long a single step took on the GPU, and drops whatever doesn't fit.
+
+/// How many frames the "falling behind" indicator stays visible after the last dropped step, so it doesn't flicker.
+const FALLING_BEHIND_HOLD_FRAMES: u32 = 30;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SimScheduler {
+    /// Hard upper bound on the number of steps dispatched in a single frame.
+    pub max_steps_per_frame: u32,
+    /// Whether to also limit the number of steps by the measured GPU time of a step.
+    pub use_gpu_budget: bool,
+    /// How much GPU time the simulation may use each frame, in milliseconds.
+    pub gpu_budget_ms: f32,
+
+    #[serde(skip)]
+    leftover_steps: f32,
+    #[serde(skip)]
+    last_num_steps: u32,
+    #[serde(skip)]
+    dropped_steps: u64,
+    #[serde(skip)]
+    frames_since_drop: Option<u32>,
+}
+
+impl Default for SimScheduler {
+    fn default() -> Self {
+        Self {
+            max_steps_per_frame: 16,
+            use_gpu_budget: true,
+            gpu_budget_ms: 8.,
+            leftover_steps: 0.,
+            last_num_steps: 0,
+            dropped_steps: 0,
+            frames_since_drop: None,
+        }
+    }
+}
+
+impl SimScheduler {
+    /// Returns the number of steps to run this frame. `step_gpu_time_ms` is the measured GPU time of a single step, if
+    /// timer queries are available.
+    pub fn steps_for_frame(
+        &mut self,
+        render_dt: f32,
+        sim_delta_time: f32,
+        step_gpu_time_ms: Option<f32>,
+    ) -> u32 {
+        let wanted_steps = self.leftover_steps + render_dt / sim_delta_time;
+        let max_steps = self.max_steps(step_gpu_time_ms);
+
+        let num_steps = if wanted_steps >= max_steps as f32 + 1. {
+            // We can't keep up, so throw away the backlog instead of carrying it over to the next frame
+            self.dropped_steps += (wanted_steps as u64).saturating_sub(max_steps as u64);
+            self.leftover_steps = 0.;
+            self.frames_since_drop = Some(0);
+            max_steps
+        } else {
+            self.leftover_steps = wanted_steps.fract();
+            self.frames_since_drop = self
+                .frames_since_drop
+                .map(|frames| frames + 1)
+                .filter(|&frames| frames < FALLING_BEHIND_HOLD_FRAMES);
+            (wanted_steps as u32).min(max_steps)
+        };
+
+        self.last_num_steps = num_steps;
+        num_steps
+    }
+
+    /// Forget the fractional step carried over from the previous frame, e.g. when pausing.
+    pub fn reset_leftover(&mut self) {
+        self.leftover_steps = 0.;
+    }
+
+    /// The most steps that run in a frame.
+    pub fn max_steps(&self, step_gpu_time_ms: Option<f32>) -> u32 {
+        match step_gpu_time_ms {
+            Some(step_time) if self.use_gpu_budget && step_time > 0. => {
+                // Always allow at least one step, otherwise a single expensive step would freeze the simulation
+                let budget_steps = (self.gpu_budget_ms / step_time).max(1.) as u32;
+                budget_steps.min(self.max_steps_per_frame)
+            }
+            _ => self.max_steps_per_frame,
+        }
+    }
+
+    pub fn is_falling_behind(&self) -> bool {
+        self.frames_since_drop.is_some()
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, step_gpu_time_ms: Option<f32>) {
+        use egui::Widget;
+
+        egui::Slider::new(&mut self.max_steps_per_frame, 1..=256)
+            .logarithmic(true)
+            .text("Max Steps per Frame")
+            .ui(ui);
+        ui.add_enabled_ui(step_gpu_time_ms.is_some(), |ui| {
+            ui.checkbox(&mut self.use_gpu_budget, "Limit by GPU time")
+                .on_disabled_hover_text("GPU timer queries are not available");
+            ui.add_enabled(
+                self.use_gpu_budget,
+                egui::Slider::new(&mut self.gpu_budget_ms, 0.5..=33.).text("GPU Budget (ms)"),
+            );
+        });
+
+        ui.label(format!("Steps this frame: {}", self.last_num_steps));
+        match step_gpu_time_ms {
+            Some(step_time) => ui.label(format!("GPU time per step: {step_time:.3} ms")),
+            None => ui.label("GPU time per step: unknown"),
+        };
+        ui.horizontal(|ui| {
+            ui.label(format!("Dropped steps: {}", self.dropped_steps));
+            if ui.small_button("Reset").clicked() {
+                self.dropped_steps = 0;
+            }
+        });
+        if self.is_falling_behind() {
+            ui.colored_label(ui.visuals().warn_fg_color, "⚠ Simulation falling behind");
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! How boids and the background are textured. Textures are loaded from KTX2 files dropped onto the window.
+
+use eframe::egui_wgpu::RenderState;
+use egui::Widget;
+
+use crate::sim::{self, ParticleSystem};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum BoidShape {
+    Triangle,
+    Sprite,
+}
+
+/// What a dropped texture replaces.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum TextureTarget {
+    Sprite,
+    Background,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SpriteSettings {
+    pub shape: BoidShape,
+    /// Half of the width and height of a sprite in simulation units
+    pub sprite_size: f32,
+    pub show_background: bool,
+    drop_target: TextureTarget,
+    // the textures themselves aren't persisted, so neither are their names
+    #[serde(skip)]
+    sprite_name: String,
+    #[serde(skip)]
+    background_name: Option<String>,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl Default for SpriteSettings {
+    fn default() -> Self {
+        Self {
+            shape: BoidShape::Triangle,
+            sprite_size: 0.01,
+            show_background: true,
+            drop_target: TextureTarget::Sprite,
+            sprite_name: "built-in".to_owned(),
+            background_name: None,
+            error: None,
+        }
+    }
+}
+
+impl SpriteSettings {
+    pub fn draw_sprites(&self) -> bool {
+        self.shape == BoidShape::Sprite
+    }
+
+    pub fn draw_background(&self) -> bool {
+        self.show_background && self.background_name.is_some()
+    }
+
+    fn load(
+        &mut self,
+        render_state: &RenderState,
+        target: TextureTarget,
+        name: String,
+        bytes: &[u8],
+    ) {
+        let texture = match sim::load_ktx2(&render_state.device, &render_state.queue, &name, bytes)
+        {
+            Ok(texture) => texture,
+            Err(err) => {
+                log::error!("Failed to load {name}: {err}");
+                self.error = Some(format!("Failed to load {name}: {err}"));
+                return;
+            }
+        };
+        self.error = None;
+
+        let mut renderer = render_state.renderer.write();
+        let Some(particles) = renderer.callback_resources.get_mut::<ParticleSystem>() else {
+            return;
+        };
+        match target {
+            TextureTarget::Sprite => {
+                particles.set_sprite_texture(&render_state.device, &texture);
+                self.sprite_name = name;
+                self.shape = BoidShape::Sprite;
+            }
+            TextureTarget::Background => {
+                particles.set_background_texture(&render_state.device, &texture);
+                self.background_name = Some(name);
+                self.show_background = true;
+            }
+        }
+    }
+
+    /// Loads files that were dropped onto the window as the current drop target.
+    pub fn handle_dropped_files(&mut self, ctx: &egui::Context, render_state: &RenderState) {
+        let dropped_files = ctx.input(|input| input.raw.dropped_files.clone());
+        for file in dropped_files {
+            let name = file
+                .path
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .map_or(file.name.clone(), |name| {
+                    name.to_string_lossy().into_owned()
+                });
+            // on the web the contents are dropped, natively only the path
+            let bytes = match (file.bytes, file.path) {
+                (Some(bytes), _) => bytes.to_vec(),
+                (None, Some(path)) => match std::fs::read(&path) {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        log::error!("Failed to read {}: {err}", path.display());
+                        self.error = Some(format!("Failed to read {name}: {err}"));
+                        continue;
+                    }
+                },
+                (None, None) => continue,
+            };
+            self.load(render_state, self.drop_target, name, &bytes);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, render_state: Option<&RenderState>) {
+        ui.horizontal(|ui| {
+            ui.label("Shape");
+            ui.selectable_value(&mut self.shape, BoidShape::Triangle, "Triangle");
+            ui.selectable_value(&mut self.shape, BoidShape::Sprite, "Sprite");
+        });
+        ui.add_enabled_ui(self.draw_sprites(), |ui| {
+            egui::Slider::new(&mut self.sprite_size, 0.001..=0.1)
+                .logarithmic(true)
+                .text("Sprite size")
+                .ui(ui);
+            ui.horizontal(|ui| {
+                ui.label(format!("Sprite: {}", self.sprite_name));
+                if let Some(render_state) = render_state {
+                    if ui.button("Use built-in").clicked() {
+                        self.load(
+                            render_state,
+                            TextureTarget::Sprite,
+                            "built-in".to_owned(),
+                            sim::BUILT_IN_SPRITE,
+                        );
+                    }
+                }
+            });
+        });
+
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                self.background_name.is_some(),
+                egui::Checkbox::new(&mut self.show_background, "Background"),
+            );
+            if let Some(name) = &self.background_name {
+                ui.label(name);
+            }
+            if self.background_name.is_some() && ui.button("Remove").clicked() {
+                if let Some(render_state) = render_state {
+                    let mut renderer = render_state.renderer.write();
+                    if let Some(particles) = renderer.callback_resources.get_mut::<ParticleSystem>()
+                    {
+                        particles.clear_background_texture();
+                    }
+                }
+                self.background_name = None;
+            }
+        });
+
+        ui.separator();
+        ui.label("Drop a .ktx2 file onto the window to use it as the");
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.drop_target, TextureTarget::Sprite, "sprite");
+            ui.radio_value(
+                &mut self.drop_target,
+                TextureTarget::Background,
+                "background",
+            );
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! What is chosen when the app is started, on the command line or in a config file. It overrides the state that the
+//! last run saved, and is only applied once.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+#[cfg(not(target_arch = "wasm32"))]
+use eframe::egui_wgpu::WgpuConfiguration;
+
+/// Which simulation runs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(clap::ValueEnum))]
+pub enum SimMode {
+    /// A fixed flock that lives forever
+    Flock,
+    /// Boids that are spawned by emitters and die after their lifetime
+    Emitters,
+}
+
+/// Overrides of the saved state. `None` keeps what was saved.
+#[derive(Clone, Default, Debug)]
+pub struct StartupOptions {
+    pub sim: Option<SimMode>,
+    pub paused: Option<bool>,
+    pub num_particles: Option<u32>,
+    /// Seeds both the fixed flock and the emitters
+    pub seed: Option<u32>,
+    /// Simulated seconds per step
+    pub delta_time: Option<f32>,
+    /// A preset that replaces the whole saved state, before the other options are applied on top of it
+    #[cfg(not(target_arch = "wasm32"))]
+    pub preset: Option<PathBuf>,
+    /// A snapshot that the fixed flock starts with. Its number of boids wins over `num_particles`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub snapshot: Option<PathBuf>,
+}
+
+/// How the native window is opened. On the web, the page decides the size of the canvas.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct WindowOptions {
+    /// Inner size in points
+    pub size: Option<[f32; 2]>,
+    pub fullscreen: bool,
+    /// Whether presenting waits for vsync. `None` keeps the default, which does.
+    pub vsync: Option<bool>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WindowOptions {
+    pub fn viewport(&self, viewport: egui::ViewportBuilder) -> egui::ViewportBuilder {
+        let viewport = match self.size {
+            Some(size) => viewport.with_inner_size(size),
+            None => viewport,
+        };
+        viewport.with_fullscreen(self.fullscreen)
+    }
+
+    pub fn apply(&self, wgpu_options: &mut WgpuConfiguration) {
+        if let Some(vsync) = self.vsync {
+            wgpu_options.present_mode = if vsync {
+                wgpu::PresentMode::AutoVsync
+            } else {
+                wgpu::PresentMode::AutoNoVsync
+            };
+        }
+    }
+}
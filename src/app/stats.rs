@@ -0,0 +1,207 @@
+//! Keeps a history of the flock statistics computed on the GPU and plots them.
+
+use std::collections::VecDeque;
+
+use crate::{shaders::boids::FlockStats, sim::FlockSample};
+
+struct Metric {
+    name: &'static str,
+    csv_name: &'static str,
+    value: fn(&FlockStats) -> f32,
+}
+
+const METRICS: [Metric; 5] = [
+    Metric {
+        name: "Mean speed",
+        csv_name: "mean_speed",
+        value: |stats| stats.mean_speed,
+    },
+    Metric {
+        name: "Polarization",
+        csv_name: "polarization",
+        value: |stats| stats.polarization,
+    },
+    Metric {
+        name: "Milling",
+        csv_name: "milling",
+        value: |stats| stats.milling,
+    },
+    Metric {
+        name: "Mean neighbor count",
+        csv_name: "mean_neighbor_count",
+        value: |stats| stats.mean_neighbor_count,
+    },
+    Metric {
+        name: "Mean nearest neighbor distance",
+        csv_name: "mean_nearest_distance",
+        value: |stats| stats.mean_nearest_distance,
+    },
+];
+
+const PLOT_HEIGHT: f32 = 60.;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FlockStatsWindow {
+    pub open: bool,
+    /// Maximum number of samples that are kept around. The oldest ones are dropped first.
+    capacity: usize,
+    #[serde(skip)]
+    history: VecDeque<FlockSample>,
+}
+
+impl Default for FlockStatsWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            capacity: 2000,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl FlockStatsWindow {
+    pub fn extend(&mut self, samples: impl IntoIterator<Item = FlockSample>) {
+        self.history.extend(samples);
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        let excess = self.history.len().saturating_sub(self.capacity);
+        self.history.drain(..excess);
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,sim_time");
+        for metric in &METRICS {
+            csv += ",";
+            csv += metric.csv_name;
+        }
+        csv += "\n";
+
+        for sample in &self.history {
+            csv += &format!("{},{}", sample.step, sample.sim_time);
+            for metric in &METRICS {
+                csv += &format!(",{}", (metric.value)(&sample.stats));
+            }
+            csv += "\n";
+        }
+        csv
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Flock Statistics")
+            .open(&mut open)
+            .default_width(320.)
+            .show(ctx, |ui| self.ui(ui));
+        self.open = open;
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.capacity)
+                    .range(10..=100_000)
+                    .prefix("History: ")
+                    .suffix(" samples"),
+            );
+            if ui.button("Clear").clicked() {
+                self.history.clear();
+            }
+        });
+        self.truncate();
+
+        ui.horizontal(|ui| {
+            if ui.button("Copy CSV").clicked() {
+                ui.ctx().copy_text(self.to_csv());
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Save CSV").clicked() {
+                self.save_csv();
+            }
+        });
+        ui.separator();
+
+        for metric in &METRICS {
+            let latest = self
+                .history
+                .back()
+                .map(|sample| (metric.value)(&sample.stats));
+            match latest {
+                Some(value) => ui.label(format!("{}: {value:.4}", metric.name)),
+                None => ui.label(format!("{}: -", metric.name)),
+            };
+            self.plot(ui, metric);
+        }
+    }
+
+    fn plot(&self, ui: &mut egui::Ui, metric: &Metric) {
+        let (rect, _response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), PLOT_HEIGHT),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+
+        let (Some(first), Some(last)) = (self.history.front(), self.history.back()) else {
+            return;
+        };
+        let (min, max) = self
+            .history
+            .iter()
+            .map(|sample| (metric.value)(&sample.stats))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+        let step_range = (last.step - first.step).max(1) as f32;
+        let value_range = (max - min).max(f32::EPSILON);
+
+        let points = self
+            .history
+            .iter()
+            .map(|sample| {
+                let x = (sample.step - first.step) as f32 / step_range;
+                let y = ((metric.value)(&sample.stats) - min) / value_range;
+                egui::pos2(
+                    egui::lerp(rect.x_range(), x),
+                    egui::lerp(rect.bottom()..=rect.top(), y),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.5, ui.visuals().selection.bg_fill),
+        ));
+
+        let text_color = ui.visuals().weak_text_color();
+        let font = egui::FontId::monospace(10.);
+        painter.text(
+            rect.left_top(),
+            egui::Align2::LEFT_TOP,
+            format!("{max:.3}"),
+            font.clone(),
+            text_color,
+        );
+        painter.text(
+            rect.left_bottom(),
+            egui::Align2::LEFT_BOTTOM,
+            format!("{min:.3}"),
+            font,
+            text_color,
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_csv(&self) {
+        let timestamp = web_time::SystemTime::now()
+            .duration_since(web_time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = format!("flock_stats_{timestamp}.csv");
+        match std::fs::write(&path, self.to_csv()) {
+            Ok(()) => log::info!("Saved flock statistics to {path}"),
+            Err(err) => log::error!("Failed to save flock statistics to {path}: {err}"),
+        }
+    }
+}
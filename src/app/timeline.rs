@@ -0,0 +1,417 @@
+//! Keyframes that change the settings and the camera over simulation time, for animations that play the same way every
+//! time, e.g. while recording.
+//!
+//! Every track keyframes a single setting. Between two keyframes, the value goes from one to the other the way the
+//! first of them says, and before the first and after the last keyframe it stays at theirs. The timeline only moves on
+//! while the simulation steps, by the simulation time of those steps, so it stays in sync with the flock however fast
+//! the app runs.
+
+use egui::Widget;
+
+use super::{boid_settings::Integrator, camera::Camera, RendererApp};
+use crate::sim::MAX_PARTICLES;
+
+/// A setting that can be keyframed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum Property {
+    DeltaTime,
+    NumParticles,
+    SeparationDistance,
+    SeparationScale,
+    AlignmentDistance,
+    AlignmentScale,
+    CohesionDistance,
+    CohesionScale,
+    Integrator,
+    Damping,
+    MinSpeed,
+    MaxSpeed,
+    CameraX,
+    CameraY,
+    CameraZoom,
+}
+
+impl Property {
+    pub const ALL: [Self; 15] = [
+        Self::DeltaTime,
+        Self::NumParticles,
+        Self::SeparationDistance,
+        Self::SeparationScale,
+        Self::AlignmentDistance,
+        Self::AlignmentScale,
+        Self::CohesionDistance,
+        Self::CohesionScale,
+        Self::Integrator,
+        Self::Damping,
+        Self::MinSpeed,
+        Self::MaxSpeed,
+        Self::CameraX,
+        Self::CameraY,
+        Self::CameraZoom,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::DeltaTime => "Simulation Delta Time",
+            Self::NumParticles => "Number of Boids",
+            Self::SeparationDistance => "Separation Distance",
+            Self::SeparationScale => "Separation Scale",
+            Self::AlignmentDistance => "Alignment Distance",
+            Self::AlignmentScale => "Alignment Scale",
+            Self::CohesionDistance => "Cohesion Distance",
+            Self::CohesionScale => "Cohesion Scale",
+            Self::Integrator => "Integrator",
+            Self::Damping => "Damping",
+            Self::MinSpeed => "Minimum Speed",
+            Self::MaxSpeed => "Maximum Speed",
+            Self::CameraX => "Camera X",
+            Self::CameraY => "Camera Y",
+            Self::CameraZoom => "Camera Zoom",
+        }
+    }
+
+    /// The labels of the options, if the setting is one of a few. Its values are their indices, which only change at
+    /// keyframes.
+    fn options(self) -> Option<Vec<&'static str>> {
+        match self {
+            Self::Integrator => Some(Integrator::ALL.map(Integrator::label).to_vec()),
+            _ => None,
+        }
+    }
+
+    pub fn get(self, app: &RendererApp) -> f32 {
+        let boids = &app.boid_settings;
+        match self {
+            Self::DeltaTime => app.sim_delta_time,
+            Self::NumParticles => app.num_particles as f32,
+            Self::SeparationDistance => boids.separation_distance,
+            Self::SeparationScale => boids.separation_scale,
+            Self::AlignmentDistance => boids.alignment_distance,
+            Self::AlignmentScale => boids.alignment_scale,
+            Self::CohesionDistance => boids.cohesion_distance,
+            Self::CohesionScale => boids.cohesion_scale,
+            Self::Integrator => Integrator::ALL
+                .iter()
+                .position(|&integrator| integrator == boids.integrator)
+                .unwrap_or_default() as f32,
+            Self::Damping => boids.damping,
+            Self::MinSpeed => boids.min_speed,
+            Self::MaxSpeed => boids.max_speed,
+            Self::CameraX => app.camera.center[0],
+            Self::CameraY => app.camera.center[1],
+            Self::CameraZoom => app.camera.zoom,
+        }
+    }
+
+    pub fn set(self, app: &mut RendererApp, value: f32) {
+        let boids = &mut app.boid_settings;
+        match self {
+            // the simulation can't step by nothing
+            Self::DeltaTime => app.sim_delta_time = value.max(0.001),
+            Self::NumParticles => {
+                app.num_particles = value.round().clamp(0., MAX_PARTICLES as f32) as u32;
+            }
+            Self::SeparationDistance => boids.separation_distance = value,
+            Self::SeparationScale => boids.separation_scale = value,
+            Self::AlignmentDistance => boids.alignment_distance = value,
+            Self::AlignmentScale => boids.alignment_scale = value,
+            Self::CohesionDistance => boids.cohesion_distance = value,
+            Self::CohesionScale => boids.cohesion_scale = value,
+            Self::Integrator => {
+                let index = value.round().clamp(0., (Integrator::ALL.len() - 1) as f32);
+                boids.integrator = Integrator::ALL[index as usize];
+            }
+            Self::Damping => boids.damping = value,
+            // the other one moves along, so the minimum is never above the maximum, whichever is set first
+            Self::MinSpeed => {
+                boids.min_speed = value;
+                boids.max_speed = boids.max_speed.max(value);
+            }
+            Self::MaxSpeed => {
+                boids.max_speed = value;
+                boids.min_speed = boids.min_speed.min(value);
+            }
+            Self::CameraX => app.camera.center[0] = value,
+            Self::CameraY => app.camera.center[1] = value,
+            Self::CameraZoom => {
+                app.camera.zoom = value.clamp(Camera::MIN_ZOOM, Camera::MAX_ZOOM);
+            }
+        }
+    }
+}
+
+/// How the value goes from a keyframe to the next one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Eases in and out of the keyframes
+    Smooth,
+    /// Keeps the value until the next keyframe
+    Step,
+}
+
+impl Interpolation {
+    const ALL: [Self; 3] = [Self::Linear, Self::Smooth, Self::Step];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Smooth => "Smooth",
+            Self::Step => "Step",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Keyframe {
+    /// Seconds of simulation time
+    pub time: f64,
+    pub value: f32,
+    /// How the value goes from this keyframe to the next one
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Track {
+    pub property: Property,
+    /// Sorted by time
+    #[serde(default, deserialize_with = "deserialize_keyframes")]
+    pub keyframes: Vec<Keyframe>,
+}
+
+/// Keyframes that were saved or written into a preset by hand aren't necessarily sorted.
+fn deserialize_keyframes<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Keyframe>, D::Error> {
+    let mut keyframes = <Vec<Keyframe> as serde::Deserialize>::deserialize(deserializer)?;
+    sort_keyframes(&mut keyframes);
+    Ok(keyframes)
+}
+
+fn sort_keyframes(keyframes: &mut [Keyframe]) {
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+}
+
+impl Track {
+    /// The value at `time`, unless there are no keyframes.
+    fn value_at(&self, time: f64) -> Option<f32> {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        let previous = next.checked_sub(1).map(|index| &self.keyframes[index]);
+        match (previous, self.keyframes.get(next)) {
+            (None, None) => None,
+            (None, Some(first)) => Some(first.value),
+            (Some(last), None) => Some(last.value),
+            (Some(from), Some(to)) => {
+                let interpolation = if self.property.options().is_some() {
+                    Interpolation::Step
+                } else {
+                    from.interpolation
+                };
+                let t = ((time - from.time) / (to.time - from.time)) as f32;
+                let t = match interpolation {
+                    Interpolation::Linear => t,
+                    Interpolation::Smooth => t * t * (3. - 2. * t),
+                    Interpolation::Step => 0.,
+                };
+                Some(from.value + (to.value - from.value) * t)
+            }
+        }
+    }
+}
+
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Timeline {
+    pub open: bool,
+    tracks: Vec<Track>,
+    /// Whether the timeline moves on with the simulation and sets the values of its tracks
+    #[serde(skip)]
+    playing: bool,
+    /// Seconds of simulation time since the start of the timeline
+    #[serde(skip)]
+    time: f64,
+}
+
+impl Timeline {
+    /// The values of the tracks at the current time, if the timeline is playing.
+    pub fn values(&self) -> Vec<(Property, f32)> {
+        if !self.playing {
+            return Vec::new();
+        }
+        self.tracks
+            .iter()
+            .filter_map(|track| Some((track.property, track.value_at(self.time)?)))
+            .collect()
+    }
+
+    /// Moves on by `sim_time` seconds of simulation time, if the timeline is playing.
+    pub fn advance(&mut self, sim_time: f64) {
+        if self.playing {
+            self.time += sim_time;
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn play_from_start(&mut self) {
+        self.playing = true;
+        self.time = 0.;
+    }
+
+    /// Time of the last keyframe.
+    fn duration(&self) -> f64 {
+        self.tracks
+            .iter()
+            .filter_map(|track| track.keyframes.last())
+            .map(|keyframe| keyframe.time)
+            .fold(0., f64::max)
+    }
+
+    /// `current_values` are the values of every one of `Property::ALL` right now, which new keyframes start with.
+    pub fn show(&mut self, ctx: &egui::Context, current_values: &[f32; Property::ALL.len()]) {
+        let mut open = self.open;
+        egui::Window::new("Timeline")
+            .open(&mut open)
+            .default_size([480., 360.])
+            .show(ctx, |ui| self.ui(ui, current_values));
+        self.open = open;
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, current_values: &[f32; Property::ALL.len()]) {
+        let duration = self.duration();
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.playing, "Play").on_hover_text(
+                "Sets the keyframed settings while the simulation runs. Recordings can start the timeline over, \
+                together with the flock.",
+            );
+            if ui.button("Rewind").clicked() {
+                self.time = 0.;
+            }
+            egui::Slider::new(&mut self.time, 0. ..=duration.max(1.))
+                .clamping(egui::SliderClamping::Never)
+                .text("Time (s)")
+                .ui(ui);
+        });
+        ui.separator();
+
+        let mut removed_track = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (track_index, track) in self.tracks.iter_mut().enumerate() {
+                ui.push_id(track_index, |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("property")
+                            .selected_text(track.property.label())
+                            .show_ui(ui, |ui| {
+                                for property in Property::ALL {
+                                    ui.selectable_value(
+                                        &mut track.property,
+                                        property,
+                                        property.label(),
+                                    );
+                                }
+                            });
+                        if ui
+                            .button("Add Keyframe")
+                            .on_hover_text("Keyframes the current value at the current time")
+                            .clicked()
+                        {
+                            let value = Property::ALL
+                                .iter()
+                                .position(|&property| property == track.property)
+                                .map_or(0., |index| current_values[index]);
+                            let keyframe = Keyframe {
+                                time: self.time,
+                                value,
+                                interpolation: Interpolation::default(),
+                            };
+                            match track
+                                .keyframes
+                                .iter_mut()
+                                .find(|existing| existing.time == self.time)
+                            {
+                                Some(existing) => existing.value = value,
+                                None => track.keyframes.push(keyframe),
+                            }
+                        }
+                        if ui.button("Remove Track").clicked() {
+                            removed_track = Some(track_index);
+                        }
+                    });
+                    track_ui(ui, track);
+                });
+                ui.separator();
+            }
+        });
+        if let Some(index) = removed_track {
+            self.tracks.remove(index);
+        }
+
+        if ui.button("Add Track").clicked() {
+            // the first setting that isn't keyframed yet
+            let property = Property::ALL
+                .into_iter()
+                .find(|&property| self.tracks.iter().all(|track| track.property != property))
+                .unwrap_or(Property::ALL[0]);
+            self.tracks.push(Track {
+                property,
+                keyframes: Vec::new(),
+            });
+        }
+    }
+}
+
+fn track_ui(ui: &mut egui::Ui, track: &mut Track) {
+    let options = track.property.options();
+    let mut removed_keyframe = None;
+    egui::Grid::new("keyframes").striped(true).show(ui, |ui| {
+        for (index, keyframe) in track.keyframes.iter_mut().enumerate() {
+            ui.push_id(index, |ui| {
+                egui::DragValue::new(&mut keyframe.time)
+                    .range(0. ..=f64::MAX)
+                    .speed(0.05)
+                    .suffix(" s")
+                    .ui(ui);
+            });
+            if let Some(options) = &options {
+                let selected = (keyframe.value.round().max(0.) as usize).min(options.len() - 1);
+                egui::ComboBox::from_id_salt(("option", index))
+                    .selected_text(options[selected])
+                    .show_ui(ui, |ui| {
+                        for (option, label) in options.iter().enumerate() {
+                            if ui.selectable_label(option == selected, *label).clicked() {
+                                keyframe.value = option as f32;
+                            }
+                        }
+                    });
+                ui.label("Step");
+            } else {
+                ui.push_id(index, |ui| {
+                    egui::DragValue::new(&mut keyframe.value).speed(0.01).ui(ui);
+                });
+                egui::ComboBox::from_id_salt(("interpolation", index))
+                    .selected_text(keyframe.interpolation.label())
+                    .show_ui(ui, |ui| {
+                        for interpolation in Interpolation::ALL {
+                            ui.selectable_value(
+                                &mut keyframe.interpolation,
+                                interpolation,
+                                interpolation.label(),
+                            );
+                        }
+                    });
+            }
+            if ui.button("Remove").clicked() {
+                removed_keyframe = Some(index);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(index) = removed_keyframe {
+        track.keyframes.remove(index);
+    }
+    // times can be dragged past each other
+    sort_keyframes(&mut track.keyframes);
+}
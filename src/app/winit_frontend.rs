@@ -0,0 +1,623 @@
+//! Runs the app on winit, wgpu and egui directly instead of through eframe. This gives the boids their own render pass
+//! to the swapchain, egui is drawn on top of them last, and the present mode can be changed while running.
+//!
+//! The app state is shared with the eframe frontend, so both start where the other one left off.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eframe::egui_wgpu::{self, RenderState, ScreenDescriptor};
+use egui_winit::winit::{
+    self,
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::{Fullscreen, Icon, Window, WindowId},
+};
+use puffin::profile_function;
+
+use crate::{
+    app::{
+        device, render_callback::RenderCallback, AdapterSelection, RendererApp, SceneTarget,
+        StartupOptions, WindowOptions, APP_ID,
+    },
+    sim::ParticleSystem,
+};
+
+/// eframe stores egui's memory under this key.
+const EGUI_MEMORY_KEY: &str = "egui";
+
+/// How often the state is saved while running, the same as eframe's default.
+const AUTO_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+const ICON: &[u8] = include_bytes!("../../assets/icon-256.png");
+
+#[derive(Debug)]
+pub enum WinitError {
+    EventLoop(winit::error::EventLoopError),
+    Window(winit::error::OsError),
+    Wgpu(egui_wgpu::WgpuError),
+}
+
+impl fmt::Display for WinitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EventLoop(err) => write!(f, "failed to run the event loop: {err}"),
+            Self::Window(err) => write!(f, "failed to create the window: {err}"),
+            Self::Wgpu(err) => write!(f, "failed to initialize wgpu: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for WinitError {}
+
+impl From<winit::error::EventLoopError> for WinitError {
+    fn from(err: winit::error::EventLoopError) -> Self {
+        Self::EventLoop(err)
+    }
+}
+
+impl From<winit::error::OsError> for WinitError {
+    fn from(err: winit::error::OsError) -> Self {
+        Self::Window(err)
+    }
+}
+
+impl From<egui_wgpu::WgpuError> for WinitError {
+    fn from(err: egui_wgpu::WgpuError) -> Self {
+        Self::Wgpu(err)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for WinitError {
+    fn from(err: wgpu::CreateSurfaceError) -> Self {
+        Self::Wgpu(err.into())
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for WinitError {
+    fn from(err: wgpu::RequestDeviceError) -> Self {
+        Self::Wgpu(err.into())
+    }
+}
+
+/// Runs the app until its window is closed. `adapter_selection` is the adapter to run on, `startup` overrides the
+/// saved state and `window_options` is how the window is opened.
+pub fn run_winit(
+    adapter_selection: AdapterSelection,
+    startup: StartupOptions,
+    window_options: WindowOptions,
+) -> Result<(), WinitError> {
+    let event_loop = EventLoop::new()?;
+    let mut frontend = WinitFrontend {
+        adapter_selection,
+        startup: Some(startup),
+        window_options,
+        running: None,
+        error: None,
+    };
+    event_loop.run_app(&mut frontend)?;
+    frontend.error.map_or(Ok(()), Err)
+}
+
+/// Stores the state in the same RON file as eframe does.
+struct FileStorage {
+    path: PathBuf,
+    values: HashMap<String, String>,
+}
+
+impl FileStorage {
+    fn open() -> Option<Self> {
+        let Some(dir) = eframe::storage_dir(APP_ID) else {
+            log::warn!("Saving disabled: there is no data directory");
+            return None;
+        };
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            log::warn!("Saving disabled: failed to create {}: {err}", dir.display());
+            return None;
+        }
+        let path = dir.join("app.ron");
+        let values = match std::fs::read_to_string(&path) {
+            Ok(ron) => ron::from_str(&ron).unwrap_or_else(|err| {
+                log::warn!("Ignoring the saved state in {}: {err}", path.display());
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Some(Self { path, values })
+    }
+}
+
+impl eframe::Storage for FileStorage {
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.values.get(key).cloned()
+    }
+
+    fn set_string(&mut self, key: &str, value: String) {
+        self.values.insert(key.to_owned(), value);
+    }
+
+    fn flush(&mut self) {
+        let result = ron::ser::to_string_pretty(&self.values, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|ron| std::fs::write(&self.path, ron).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            log::warn!("Failed to save to {}: {err}", self.path.display());
+        }
+    }
+}
+
+struct WinitFrontend {
+    adapter_selection: AdapterSelection,
+    /// Taken by the first window. A window that is created again after a suspend starts from the saved state.
+    startup: Option<StartupOptions>,
+    window_options: WindowOptions,
+    /// Only exists while there is a window
+    running: Option<Running>,
+    /// Why the app couldn't start
+    error: Option<WinitError>,
+}
+
+struct Running {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    /// Every present mode that the surface can be configured with
+    present_modes: Vec<wgpu::PresentMode>,
+    render_state: RenderState,
+    egui_state: egui_winit::State,
+    storage: Option<FileStorage>,
+    last_save: Instant,
+    /// When egui wants to be repainted, if it doesn't need to be continuously
+    repaint_at: Option<Instant>,
+    app: RendererApp,
+}
+
+impl Running {
+    fn new(
+        event_loop: &ActiveEventLoop,
+        selection: &AdapterSelection,
+        startup: StartupOptions,
+        window_options: &WindowOptions,
+    ) -> Result<Self, WinitError> {
+        let icon = eframe::icon_data::from_png_bytes(ICON).expect("Failed to load icon");
+        let mut attributes = Window::default_attributes()
+            .with_title(APP_ID)
+            .with_window_icon(Icon::from_rgba(icon.rgba, icon.width, icon.height).ok())
+            .with_fullscreen(
+                window_options
+                    .fullscreen
+                    .then_some(Fullscreen::Borderless(None)),
+            );
+        if let Some([width, height]) = window_options.size {
+            attributes = attributes.with_inner_size(winit::dpi::LogicalSize::new(width, height));
+        }
+        let window = Arc::new(event_loop.create_window(attributes)?);
+
+        let mut wgpu_options = device::get_wgpu_options(selection);
+        window_options.apply(&mut wgpu_options);
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu_options.supported_backends,
+            ..Default::default()
+        });
+        let surface = instance.create_surface(window.clone())?;
+
+        // unlike eframe, this can run on exactly the selected adapter
+        let selected_adapter = (*selection != AdapterSelection::default())
+            .then(|| {
+                instance
+                    .enumerate_adapters(wgpu_options.supported_backends)
+                    .into_iter()
+                    .find(|adapter| {
+                        selection.matches(&adapter.get_info())
+                            && adapter.is_surface_supported(&surface)
+                    })
+            })
+            .flatten();
+        let adapter = match selected_adapter {
+            Some(adapter) => adapter,
+            None => pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu_options.power_preference,
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            }))
+            .ok_or(egui_wgpu::WgpuError::NoSuitableAdapterFound)?,
+        };
+        let (device, queue) = pollster::block_on(
+            adapter.request_device(&(wgpu_options.device_descriptor)(&adapter), None),
+        )?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let format = egui_wgpu::preferred_framebuffer_format(&capabilities.formats)?;
+        let size = window.inner_size();
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu_options.present_mode,
+            desired_maximum_frame_latency: wgpu_options.desired_maximum_frame_latency.unwrap_or(2),
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: vec![],
+        };
+        surface.configure(&device, &surface_config);
+        let present_modes = [wgpu::PresentMode::AutoVsync, wgpu::PresentMode::AutoNoVsync]
+            .into_iter()
+            .chain(capabilities.present_modes)
+            .collect();
+
+        let renderer = egui_wgpu::Renderer::new(&device, format, None, 1, true);
+        let render_state = RenderState {
+            adapter: Arc::new(adapter),
+            available_adapters: instance
+                .enumerate_adapters(wgpu_options.supported_backends)
+                .into(),
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            target_format: format,
+            renderer: Arc::new(egui::mutex::RwLock::new(renderer)),
+        };
+
+        let storage = FileStorage::open();
+        let egui_ctx = egui::Context::default();
+        if let Some(memory) = storage
+            .as_ref()
+            .and_then(|storage| eframe::get_value(storage, EGUI_MEMORY_KEY))
+        {
+            egui_ctx.memory_mut(|m| *m = memory);
+        }
+        let egui_state = egui_winit::State::new(
+            egui_ctx,
+            egui::ViewportId::ROOT,
+            &window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            Some(render_state.device.limits().max_texture_dimension_2d as usize),
+        );
+
+        let app = RendererApp::with_render_state(
+            &render_state,
+            storage
+                .as_ref()
+                .map(|storage| storage as &dyn eframe::Storage),
+            selection.clone(),
+            startup,
+        );
+
+        Ok(Self {
+            window,
+            surface,
+            surface_config,
+            present_modes,
+            render_state,
+            egui_state,
+            storage,
+            last_save: Instant::now(),
+            repaint_at: None,
+            app,
+        })
+    }
+
+    fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        if size.width > 0 && size.height > 0 {
+            self.surface_config.width = size.width;
+            self.surface_config.height = size.height;
+            self.surface
+                .configure(&self.render_state.device, &self.surface_config);
+        }
+    }
+
+    fn save(&mut self) {
+        if let Some(storage) = &mut self.storage {
+            eframe::App::save(&mut self.app, storage);
+            self.egui_state
+                .egui_ctx()
+                .memory(|memory| eframe::set_value(storage, EGUI_MEMORY_KEY, memory));
+            eframe::Storage::flush(storage);
+        }
+        self.last_save = Instant::now();
+    }
+
+    /// Runs the UI and draws a frame. Returns whether the app wants to close.
+    fn redraw(&mut self) -> bool {
+        profile_function!();
+        puffin::GlobalProfiler::lock().new_frame();
+
+        let raw_input = self.egui_state.take_egui_input(&self.window);
+        let egui_ctx = self.egui_state.egui_ctx().clone();
+        let mut scene = None;
+        let mut present_mode = self.surface_config.present_mode;
+        let full_output = egui_ctx.run(raw_input, |ctx| {
+            let output = self
+                .app
+                .ui(ctx, Some(&self.render_state), SceneTarget::Frontend);
+            scene = output.scene;
+            if let Some(selection) = output.restart {
+                self.app.restart(
+                    ctx,
+                    self.storage
+                        .as_mut()
+                        .map(|storage| storage as &mut dyn eframe::Storage),
+                    &selection,
+                );
+            }
+            surface_ui(
+                ctx,
+                self.surface_config.format,
+                &self.present_modes,
+                &mut present_mode,
+            );
+        });
+        if present_mode != self.surface_config.present_mode {
+            self.surface_config.present_mode = present_mode;
+            self.surface
+                .configure(&self.render_state.device, &self.surface_config);
+        }
+
+        self.egui_state
+            .handle_platform_output(&self.window, full_output.platform_output);
+        let mut close = false;
+        self.repaint_at = None;
+        if let Some(viewport_output) = full_output.viewport_output.get(&egui::ViewportId::ROOT) {
+            close = viewport_output
+                .commands
+                .iter()
+                .any(|command| matches!(command, egui::ViewportCommand::Close));
+            if viewport_output.repaint_delay.is_zero() {
+                self.window.request_redraw();
+            } else {
+                self.repaint_at = Instant::now().checked_add(viewport_output.repaint_delay);
+            }
+        }
+
+        let paint_jobs = egui_ctx.tessellate(full_output.shapes, full_output.pixels_per_point);
+        self.render(
+            scene,
+            &paint_jobs,
+            &full_output.textures_delta,
+            full_output.pixels_per_point,
+        );
+
+        if self.last_save.elapsed() > AUTO_SAVE_INTERVAL {
+            self.save();
+        }
+        close
+    }
+
+    fn render(
+        &mut self,
+        scene: Option<(egui::Rect, RenderCallback)>,
+        paint_jobs: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+        pixels_per_point: f32,
+    ) {
+        profile_function!();
+
+        let surface_texture = match self.surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.surface
+                    .configure(&self.render_state.device, &self.surface_config);
+                self.window.request_redraw();
+                return;
+            }
+            Err(err) => {
+                log::warn!("Dropped frame: {err}");
+                return;
+            }
+        };
+        let view = surface_texture.texture.create_view(&Default::default());
+
+        let device = &self.render_state.device;
+        let queue = &self.render_state.queue;
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [self.surface_config.width, self.surface_config.height],
+            pixels_per_point,
+        };
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame"),
+        });
+
+        let mut renderer = self.render_state.renderer.write();
+        for (id, image_delta) in &textures_delta.set {
+            renderer.update_texture(device, queue, *id, image_delta);
+        }
+        let mut command_buffers =
+            renderer.update_buffers(device, queue, &mut encoder, paint_jobs, &screen_descriptor);
+
+        if let Some((_, callback)) = &scene {
+            if let Some(particles) = renderer.callback_resources.get_mut::<ParticleSystem>() {
+                callback.record(device, queue, &mut encoder, particles);
+            }
+        }
+
+        // the canvas is transparent in egui, so its color is cleared to here instead
+        let canvas_color = self.egui_state.egui_ctx().style().visuals.extreme_bg_color;
+        let [r, g, b, a] = if self.surface_config.format.is_srgb() {
+            egui::Rgba::from(canvas_color).to_array()
+        } else {
+            canvas_color.to_normalized_gamma_f32()
+        };
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("boids"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: r.into(),
+                            g: g.into(),
+                            b: b.into(),
+                            a: a.into(),
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            if let (Some((rect, callback)), Some(particles)) =
+                (&scene, renderer.callback_resources.get::<ParticleSystem>())
+            {
+                // the same viewport that egui gives a paint callback
+                let info = egui::PaintCallbackInfo {
+                    viewport: *rect,
+                    clip_rect: *rect,
+                    pixels_per_point,
+                    screen_size_px: screen_descriptor.size_in_pixels,
+                };
+                let viewport = info.viewport_in_pixels();
+                let clip = info.clip_rect_in_pixels();
+                if viewport.width_px > 0
+                    && viewport.height_px > 0
+                    && clip.width_px > 0
+                    && clip.height_px > 0
+                {
+                    rpass.set_viewport(
+                        viewport.left_px as f32,
+                        viewport.top_px as f32,
+                        viewport.width_px as f32,
+                        viewport.height_px as f32,
+                        0.,
+                        1.,
+                    );
+                    rpass.set_scissor_rect(
+                        clip.left_px as u32,
+                        clip.top_px as u32,
+                        clip.width_px as u32,
+                        clip.height_px as u32,
+                    );
+                    particles.draw(&mut rpass, &callback.draw_options);
+                }
+            }
+        }
+        {
+            let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("egui"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            renderer.render(&mut rpass.forget_lifetime(), paint_jobs, &screen_descriptor);
+        }
+
+        command_buffers.push(encoder.finish());
+        queue.submit(command_buffers);
+        if scene.is_some() {
+            if let Some(particles) = renderer.callback_resources.get_mut::<ParticleSystem>() {
+                particles.after_submit(device, queue);
+            }
+        }
+        for id in &textures_delta.free {
+            renderer.free_texture(id);
+        }
+        drop(renderer);
+
+        self.window.pre_present_notify();
+        surface_texture.present();
+    }
+}
+
+/// A small window to change how frames are presented, which eframe doesn't allow.
+fn surface_ui(
+    ctx: &egui::Context,
+    format: wgpu::TextureFormat,
+    present_modes: &[wgpu::PresentMode],
+    present_mode: &mut wgpu::PresentMode,
+) {
+    egui::Window::new("Surface")
+        .default_open(false)
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_BOTTOM, [8., -8.])
+        .show(ctx, |ui| {
+            ui.label(format!("Format: {format:?}"));
+            egui::ComboBox::from_label("Present mode")
+                .selected_text(format!("{present_mode:?}"))
+                .show_ui(ui, |ui| {
+                    for &mode in present_modes {
+                        ui.selectable_value(present_mode, mode, format!("{mode:?}"));
+                    }
+                });
+        });
+}
+
+impl ApplicationHandler for WinitFrontend {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.running.is_some() {
+            return;
+        }
+        match Running::new(
+            event_loop,
+            &self.adapter_selection,
+            self.startup.take().unwrap_or_default(),
+            &self.window_options,
+        ) {
+            Ok(running) => {
+                running.window.request_redraw();
+                self.running = Some(running);
+            }
+            Err(err) => {
+                self.error = Some(err);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let Some(running) = &mut self.running else {
+            return;
+        };
+        let response = running.egui_state.on_window_event(&running.window, &event);
+        let close = match event {
+            WindowEvent::CloseRequested => true,
+            WindowEvent::Resized(size) => {
+                running.resize(size);
+                running.window.request_redraw();
+                false
+            }
+            WindowEvent::RedrawRequested => running.redraw(),
+            _ => false,
+        };
+        if close {
+            running.save();
+            event_loop.exit();
+        } else if response.repaint {
+            running.window.request_redraw();
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(running) = &mut self.running else {
+            return;
+        };
+        match running.repaint_at {
+            Some(repaint_at) if repaint_at <= Instant::now() => {
+                running.repaint_at = None;
+                running.window.request_redraw();
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+            Some(repaint_at) => event_loop.set_control_flow(ControlFlow::WaitUntil(repaint_at)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+}
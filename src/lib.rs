@@ -1,12 +1,25 @@
 #![warn(clippy::all)]
 
+#[cfg(feature = "app")]
 mod app;
 pub mod shaders {
     #[allow(warnings)]
     pub mod boids;
 }
+#[cfg(feature = "sim")]
+pub mod sim;
 
-pub use app::RendererApp;
+#[cfg(all(feature = "app", not(target_arch = "wasm32")))]
+pub use app::{enumerate_adapters, WindowOptions};
+#[cfg(feature = "app")]
+pub use app::{
+    get_wgpu_options, init_logger, AdapterSelection, ForcedBackend, RendererApp, SimMode,
+    StartupOptions, SIM_DELTA_TIME_RANGE,
+};
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+pub use app::{run_winit, WinitError};
+#[cfg(all(feature = "sim", target_arch = "wasm32"))]
+pub use sim::step_boids;
 
 pub static PUFFIN_GPU_PROFILER: std::sync::LazyLock<std::sync::Mutex<puffin::GlobalProfiler>> =
     std::sync::LazyLock::new(|| std::sync::Mutex::new(puffin::GlobalProfiler::default()));
@@ -1,65 +1,326 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use eframe::{egui_wgpu::WgpuConfiguration, wgpu};
-use std::sync::Arc;
-
-fn get_wgpu_options() -> WgpuConfiguration {
-    WgpuConfiguration {
-        device_descriptor: Arc::new(|adapter| {
-            let base_limits: wgpu::Limits = if adapter.get_info().backend == wgpu::Backend::Gl {
-                wgpu::Limits::downlevel_webgl2_defaults()
-            } else {
-                wgpu::Limits::default()
-            };
-            wgpu::DeviceDescriptor {
-                label: Some("egui wgpu device"),
-                required_features: wgpu::Features::default()
-                    | wgpu::Features::POLYGON_MODE_LINE
-                    | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
-                    | wgpu_profiler::GpuProfiler::ALL_WGPU_TIMER_FEATURES,
-                required_limits: wgpu::Limits {
-                    // When using a depth buffer, we have to be able to create a texture
-                    // large enough for the entire surface, and we want to support 4k+ displays.
-                    max_texture_dimension_2d: 8192,
-                    ..base_limits
-                },
-                ..Default::default()
+/// Boids simulated with wgpu.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(clap::Parser)]
+#[command(args_override_self = true)]
+struct Args {
+    /// Read options from this TOML file, with the same names as on the command line, like `num-particles = 5000`.
+    /// Options that are also given on the command line are taken from there.
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+    /// Only consider adapters of this backend
+    #[arg(long, value_enum)]
+    backend: Option<wgpu_automata::ForcedBackend>,
+    /// Run on the first adapter whose name contains this, ignoring case
+    #[arg(long)]
+    adapter: Option<String>,
+    /// Print every adapter that wgpu can find and exit
+    #[arg(long)]
+    list_adapters: bool,
+    /// Run on winit, wgpu and egui directly instead of eframe, which draws the boids in their own render pass
+    #[cfg(feature = "winit")]
+    #[arg(long)]
+    winit: bool,
+    #[command(flatten)]
+    startup: StartupArgs,
+    #[command(flatten)]
+    window: WindowArgs,
+    #[command(flatten)]
+    profiler_servers: ProfilerServerArgs,
+}
+
+/// Overrides of the state that the last run saved.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(clap::Args)]
+#[command(next_help_heading = "Simulation")]
+struct StartupArgs {
+    /// Which simulation runs
+    #[arg(long, value_enum)]
+    sim: Option<wgpu_automata::SimMode>,
+    /// Start paused, or playing with `--paused=false`
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    paused: Option<bool>,
+    /// Number of boids, or the maximum number of boids with emitters
+    #[arg(long, value_parser = clap::value_parser!(u32).range(..=wgpu_automata::sim::MAX_PARTICLES as i64))]
+    num_particles: Option<u32>,
+    /// Seed of the starting flock and of the emitters
+    #[arg(long)]
+    seed: Option<u32>,
+    /// Simulated seconds per step
+    #[arg(long, value_parser = parse_delta_time)]
+    delta_time: Option<f32>,
+    /// Start with the settings of this preset instead of the saved ones. File > Save Preset saves one.
+    #[arg(long)]
+    preset: Option<std::path::PathBuf>,
+    /// Start with the flock of this snapshot, which "Save Snapshot" in "Starting Flock" saves
+    #[arg(long)]
+    snapshot: Option<std::path::PathBuf>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StartupArgs {
+    fn options(self) -> wgpu_automata::StartupOptions {
+        wgpu_automata::StartupOptions {
+            sim: self.sim,
+            paused: self.paused,
+            num_particles: self.num_particles,
+            seed: self.seed,
+            delta_time: self.delta_time,
+            preset: self.preset,
+            snapshot: self.snapshot,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(clap::Args)]
+#[command(next_help_heading = "Window")]
+struct WindowArgs {
+    /// Inner size of the window in points, like 1280x720
+    #[arg(long, value_parser = parse_window_size)]
+    window_size: Option<[f32; 2]>,
+    /// Open the window fullscreen
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    fullscreen: Option<bool>,
+    /// Wait for vsync, or present as fast as possible with `--vsync=false`
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    vsync: Option<bool>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WindowArgs {
+    fn options(&self) -> wgpu_automata::WindowOptions {
+        wgpu_automata::WindowOptions {
+            size: self.window_size,
+            fullscreen: self.fullscreen.unwrap_or_default(),
+            vsync: self.vsync,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_window_size(size: &str) -> Result<[f32; 2], String> {
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|value| *value >= 1.)
+    };
+    match size.split_once('x') {
+        Some((width, height)) => match (parse(width), parse(height)) {
+            (Some(width), Some(height)) => Ok([width, height]),
+            _ => Err("the width and height have to be at least 1".to_owned()),
+        },
+        None => Err("expected WIDTHxHEIGHT, like 1280x720".to_owned()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_delta_time(delta_time: &str) -> Result<f32, String> {
+    let range = wgpu_automata::SIM_DELTA_TIME_RANGE;
+    delta_time
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|delta_time| range.contains(delta_time))
+        .ok_or_else(|| {
+            format!(
+                "expected a number from {} to {}",
+                range.start(),
+                range.end()
+            )
+        })
+}
+
+/// Turns the options in a config file into command line arguments, like `num-particles = 5000` into
+/// `--num-particles=5000`. Flags without a value are passed if they are `true`, and left out if they are `false`.
+#[cfg(not(target_arch = "wasm32"))]
+fn config_args(path: &std::path::Path) -> Result<Vec<String>, String> {
+    use clap::CommandFactory as _;
+
+    let toml = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+    let table: toml::Table = toml
+        .parse()
+        .map_err(|err| format!("invalid config {}: {err}", path.display()))?;
+    let command = Args::command();
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(name.as_str()) && name != "config")
+            .ok_or_else(|| format!("unknown option `{key}` in {}", path.display()))?;
+        let value = match value {
+            toml::Value::Boolean(flag) if !arg.get_action().takes_values() => {
+                if flag {
+                    args.push(format!("--{name}"));
+                }
+                continue;
             }
-        }),
-        ..Default::default()
+            toml::Value::String(value) => value,
+            toml::Value::Integer(value) => value.to_string(),
+            toml::Value::Float(value) => value.to_string(),
+            toml::Value::Boolean(value) => value.to_string(),
+            _ => {
+                return Err(format!(
+                    "`{key}` in {} has to be a string, number or boolean",
+                    path.display()
+                ))
+            }
+        };
+        args.push(format!("--{name}={value}"));
+    }
+    Ok(args)
+}
+
+/// Parses the command line, and the config file it points to. The config file's options come first, so the ones on
+/// the command line override them.
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_args() -> Args {
+    use clap::{CommandFactory as _, Parser as _};
+
+    let args = Args::parse();
+    let Some(path) = &args.config else {
+        return args;
+    };
+    let config_args = config_args(path).unwrap_or_else(|err| {
+        Args::command()
+            .error(clap::error::ErrorKind::Io, err)
+            .exit()
+    });
+    let mut command_line = std::env::args_os();
+    let program = command_line.next().unwrap_or_default();
+    Args::parse_from(
+        std::iter::once(program)
+            .chain(config_args.into_iter().map(Into::into))
+            .chain(command_line),
+    )
+}
+
+/// The puffin servers that puffin_viewer can connect to. They are off unless asked for, and only reachable from this
+/// machine unless bound to another address.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(clap::Args)]
+#[command(next_help_heading = "Profiler servers")]
+struct ProfilerServerArgs {
+    /// Serve the CPU scopes
+    #[arg(long)]
+    cpu_profiler_server: bool,
+    /// Serve the GPU timer queries
+    #[arg(long)]
+    gpu_profiler_server: bool,
+    /// Address that the profiler servers listen on, like 0.0.0.0 to reach them from other machines
+    #[arg(long, default_value = "127.0.0.1")]
+    profiler_address: std::net::IpAddr,
+    #[arg(long, default_value_t = puffin_http::DEFAULT_PORT)]
+    cpu_profiler_port: u16,
+    #[arg(long, default_value_t = puffin_http::DEFAULT_PORT + 1)]
+    gpu_profiler_port: u16,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ProfilerServerArgs {
+    /// Starts the servers that were asked for. They stop once they are dropped. A server that fails to start is
+    /// skipped, so another instance of the app that already serves on the same port doesn't stop this one.
+    fn start(&self) -> Vec<puffin_http::Server> {
+        use wgpu_automata::PUFFIN_GPU_PROFILER;
+
+        let mut servers = Vec::new();
+        if self.cpu_profiler_server {
+            let address = std::net::SocketAddr::new(self.profiler_address, self.cpu_profiler_port);
+            match puffin_http::Server::new(&address.to_string()) {
+                Ok(server) => {
+                    log::info!("Serving the CPU profiler on {address}");
+                    servers.push(server);
+                }
+                Err(err) => log::warn!("Failed to serve the CPU profiler on {address}: {err}"),
+            }
+        }
+        if self.gpu_profiler_server {
+            let address = std::net::SocketAddr::new(self.profiler_address, self.gpu_profiler_port);
+            match puffin_http::Server::new_custom(
+                &address.to_string(),
+                |sink| PUFFIN_GPU_PROFILER.lock().unwrap().add_sink(sink),
+                |id| _ = PUFFIN_GPU_PROFILER.lock().unwrap().remove_sink(id),
+            ) {
+                Ok(server) => {
+                    log::info!("Serving the GPU profiler on {address}");
+                    servers.push(server);
+                }
+                Err(err) => log::warn!("Failed to serve the GPU profiler on {address}: {err}"),
+            }
+        }
+        servers
     }
 }
 
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
-    use wgpu_automata::PUFFIN_GPU_PROFILER;
+    use wgpu_automata::AdapterSelection;
+
+    // Log to stderr (if you run with `RUST_LOG=debug`), and to the log console
+    let logger = env_logger::Builder::from_default_env().build();
+    let filter = logger.filter();
+    wgpu_automata::init_logger(Box::new(logger), filter).expect("Failed to set the logger");
+
+    let args = parse_args();
+    if args.list_adapters {
+        for (i, info) in wgpu_automata::enumerate_adapters().iter().enumerate() {
+            println!(
+                "{i}: {} ({:?}, {:?}), driver: {}",
+                info.name,
+                info.backend,
+                info.device_type,
+                format!("{} {}", info.driver, info.driver_info).trim()
+            );
+        }
+        return Ok(());
+    }
+    let adapter_selection = AdapterSelection {
+        backend: args.backend,
+        adapter: args.adapter,
+    };
 
-    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    let startup = args.startup.options();
+    let window_options = args.window.options();
 
     puffin::set_scopes_on(true);
-    let _cpu_server =
-        puffin_http::Server::new(&format!("0.0.0.0:{}", puffin_http::DEFAULT_PORT)).unwrap();
-    let _gpu_server = puffin_http::Server::new_custom(
-        &format!("0.0.0.0:{}", puffin_http::DEFAULT_PORT + 1),
-        |sink| PUFFIN_GPU_PROFILER.lock().unwrap().add_sink(sink),
-        |id| _ = PUFFIN_GPU_PROFILER.lock().unwrap().remove_sink(id),
-    );
+    let _profiler_servers = args.profiler_servers.start();
+
+    #[cfg(feature = "winit")]
+    if args.winit {
+        return wgpu_automata::run_winit(adapter_selection, startup, window_options)
+            .map_err(|err| eframe::Error::AppCreation(Box::new(err)));
+    }
 
+    let mut wgpu_options = wgpu_automata::get_wgpu_options(&adapter_selection);
+    window_options.apply(&mut wgpu_options);
     let native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_icon(
-            // NOTE: Adding an icon is optional
-            eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon-256.png")[..])
-                .expect("Failed to load icon"),
+        viewport: window_options.viewport(
+            egui::ViewportBuilder::default().with_icon(
+                // NOTE: Adding an icon is optional
+                eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon-256.png")[..])
+                    .expect("Failed to load icon"),
+            ),
         ),
-        wgpu_options: get_wgpu_options(),
+        wgpu_options,
         ..Default::default()
     };
     eframe::run_native(
         "wgpu automatas",
         native_options,
-        Box::new(|cc| Ok(Box::new(wgpu_automata::RendererApp::new(cc)))),
+        Box::new(|cc| {
+            Ok(Box::new(wgpu_automata::RendererApp::new(
+                cc,
+                adapter_selection,
+                startup,
+            )))
+        }),
     )
 }
 
@@ -68,11 +329,20 @@ fn main() -> eframe::Result {
 fn main() {
     use eframe::wasm_bindgen::JsCast as _;
 
-    // Redirect `log` message to `console.log` and friends:
-    eframe::WebLogger::init(log::LevelFilter::Debug).ok();
+    // Redirect `log` message to `console.log` and friends, and to the log console:
+    wgpu_automata::init_logger(
+        Box::new(eframe::WebLogger::new(log::LevelFilter::Debug)),
+        log::LevelFilter::Debug,
+    )
+    .ok();
+
+    // `sim_worker.js` loads this module too, but only to call `step_boids`
+    if web_sys::window().is_none() {
+        return;
+    }
 
     let web_options = eframe::WebOptions {
-        wgpu_options: get_wgpu_options(),
+        wgpu_options: wgpu_automata::get_wgpu_options(&Default::default()),
         ..Default::default()
     };
 
@@ -92,7 +362,13 @@ fn main() {
             .start(
                 canvas,
                 web_options,
-                Box::new(|cc| Ok(Box::new(wgpu_automata::RendererApp::new(cc)))),
+                Box::new(|cc| {
+                    Ok(Box::new(wgpu_automata::RendererApp::new(
+                        cc,
+                        Default::default(),
+                        Default::default(),
+                    )))
+                }),
             )
             .await;
 
@@ -12,9 +12,11 @@
 pub struct Particle {
     pub pos: [f32; 2],
     pub vel: [f32; 2],
+    pub age: f32,
+    pub lifetime: f32,
 }
 const _: () = assert!(
-    std::mem::size_of::<Particle>() == 16,
+    std::mem::size_of::<Particle>() == 24,
     "size of Particle does not match WGSL"
 );
 const _: () = assert!(
@@ -25,6 +27,14 @@ const _: () = assert!(
     std::mem::offset_of!(Particle, vel) == 8,
     "offset of Particle.vel does not match WGSL"
 );
+const _: () = assert!(
+    std::mem::offset_of!(Particle, age) == 16,
+    "offset of Particle.age does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Particle, lifetime) == 20,
+    "offset of Particle.lifetime does not match WGSL"
+);
 #[repr(C)]
 #[derive(
     Debug,
@@ -45,45 +55,732 @@ pub struct SimParams {
     pub separation_scale: f32,
     pub alignment_scale: f32,
     pub cohesion_scale: f32,
+    pub integrator: u32,
+    pub damping: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+}
+const _: () = assert!(
+    std::mem::size_of::<SimParams>() == 48,
+    "size of SimParams does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, num_particles) == 0,
+    "offset of SimParams.num_particles does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, delta_time) == 4,
+    "offset of SimParams.delta_time does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, separation_distance) == 8,
+    "offset of SimParams.separation_distance does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, alignment_distance) == 12,
+    "offset of SimParams.alignment_distance does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, cohesion_distance) == 16,
+    "offset of SimParams.cohesion_distance does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, separation_scale) == 20,
+    "offset of SimParams.separation_scale does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, alignment_scale) == 24,
+    "offset of SimParams.alignment_scale does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, cohesion_scale) == 28,
+    "offset of SimParams.cohesion_scale does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, integrator) == 32,
+    "offset of SimParams.integrator does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, damping) == 36,
+    "offset of SimParams.damping does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, min_speed) == 40,
+    "offset of SimParams.min_speed does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SimParams, max_speed) == 44,
+    "offset of SimParams.max_speed does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct Emitter {
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    pub shape: u32,
+    pub rate: f32,
+    pub lifetime: f32,
+    pub speed: f32,
+    pub direction: f32,
+    pub spread: f32,
+    pub _padding: [f32; 2],
+}
+const _: () = assert!(
+    std::mem::size_of::<Emitter>() == 48,
+    "size of Emitter does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, start) == 0,
+    "offset of Emitter.start does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, end) == 8,
+    "offset of Emitter.end does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, shape) == 16,
+    "offset of Emitter.shape does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, rate) == 20,
+    "offset of Emitter.rate does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, lifetime) == 24,
+    "offset of Emitter.lifetime does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, speed) == 28,
+    "offset of Emitter.speed does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, direction) == 32,
+    "offset of Emitter.direction does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, spread) == 36,
+    "offset of Emitter.spread does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Emitter, _padding) == 40,
+    "offset of Emitter._padding does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct EmitParams {
+    pub emitters: [Emitter; 4],
+    pub num_emitters: u32,
+    pub enabled: u32,
+    pub seed: u32,
+    pub _padding: u32,
+}
+const _: () = assert!(
+    std::mem::size_of::<EmitParams>() == 208,
+    "size of EmitParams does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(EmitParams, emitters) == 0,
+    "offset of EmitParams.emitters does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(EmitParams, num_emitters) == 192,
+    "offset of EmitParams.num_emitters does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(EmitParams, enabled) == 196,
+    "offset of EmitParams.enabled does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(EmitParams, seed) == 200,
+    "offset of EmitParams.seed does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(EmitParams, _padding) == 204,
+    "offset of EmitParams._padding does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct ParticleInfo {
+    pub neighbor_count: u32,
+    pub nearest_distance: f32,
+}
+const _: () = assert!(
+    std::mem::size_of::<ParticleInfo>() == 8,
+    "size of ParticleInfo does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(ParticleInfo, neighbor_count) == 0,
+    "offset of ParticleInfo.neighbor_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(ParticleInfo, nearest_distance) == 4,
+    "offset of ParticleInfo.nearest_distance does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct StatsAccumulator {
+    pub speed: f32,
+    pub neighbor_count: f32,
+    pub nearest_distance: f32,
+    pub angular_momentum: f32,
+    pub heading: [f32; 2],
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub position_sq: f32,
+    pub _padding: f32,
+}
+const _: () = assert!(
+    std::mem::size_of::<StatsAccumulator>() == 48,
+    "size of StatsAccumulator does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, speed) == 0,
+    "offset of StatsAccumulator.speed does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, neighbor_count) == 4,
+    "offset of StatsAccumulator.neighbor_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, nearest_distance) == 8,
+    "offset of StatsAccumulator.nearest_distance does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, angular_momentum) == 12,
+    "offset of StatsAccumulator.angular_momentum does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, heading) == 16,
+    "offset of StatsAccumulator.heading does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, position) == 24,
+    "offset of StatsAccumulator.position does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, velocity) == 32,
+    "offset of StatsAccumulator.velocity does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, position_sq) == 40,
+    "offset of StatsAccumulator.position_sq does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(StatsAccumulator, _padding) == 44,
+    "offset of StatsAccumulator._padding does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct FlockStats {
+    pub mean_speed: f32,
+    pub polarization: f32,
+    pub milling: f32,
+    pub mean_neighbor_count: f32,
+    pub mean_nearest_distance: f32,
+}
+const _: () = assert!(
+    std::mem::size_of::<FlockStats>() == 20,
+    "size of FlockStats does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(FlockStats, mean_speed) == 0,
+    "offset of FlockStats.mean_speed does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(FlockStats, polarization) == 4,
+    "offset of FlockStats.polarization does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(FlockStats, milling) == 8,
+    "offset of FlockStats.milling does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(FlockStats, mean_neighbor_count) == 12,
+    "offset of FlockStats.mean_neighbor_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(FlockStats, mean_nearest_distance) == 16,
+    "offset of FlockStats.mean_nearest_distance does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct Steering {
+    pub acceleration: [f32; 2],
+    pub separation: [f32; 2],
+    pub alignment: [f32; 2],
+    pub cohesion: [f32; 2],
+    pub separation_count: u32,
+    pub alignment_count: u32,
+    pub cohesion_count: u32,
+    pub neighbor_count: u32,
+    pub nearest_distance: f32,
+    pub _padding: f32,
+}
+const _: () = assert!(
+    std::mem::size_of::<Steering>() == 56,
+    "size of Steering does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, acceleration) == 0,
+    "offset of Steering.acceleration does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, separation) == 8,
+    "offset of Steering.separation does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, alignment) == 16,
+    "offset of Steering.alignment does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, cohesion) == 24,
+    "offset of Steering.cohesion does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, separation_count) == 32,
+    "offset of Steering.separation_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, alignment_count) == 36,
+    "offset of Steering.alignment_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, cohesion_count) == 40,
+    "offset of Steering.cohesion_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, neighbor_count) == 44,
+    "offset of Steering.neighbor_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, nearest_distance) == 48,
+    "offset of Steering.nearest_distance does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(Steering, _padding) == 52,
+    "offset of Steering._padding does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct InspectParams {
+    pub pick_position: [f32; 2],
+    pub selected_index: u32,
+    pub _padding: u32,
+}
+const _: () = assert!(
+    std::mem::size_of::<InspectParams>() == 16,
+    "size of InspectParams does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(InspectParams, pick_position) == 0,
+    "offset of InspectParams.pick_position does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(InspectParams, selected_index) == 8,
+    "offset of InspectParams.selected_index does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(InspectParams, _padding) == 12,
+    "offset of InspectParams._padding does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct InspectedBoid {
+    pub particle: Particle,
+    pub steering: Steering,
+}
+const _: () = assert!(
+    std::mem::size_of::<InspectedBoid>() == 80,
+    "size of InspectedBoid does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(InspectedBoid, particle) == 0,
+    "offset of InspectedBoid.particle does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(InspectedBoid, steering) == 24,
+    "offset of InspectedBoid.steering does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct RenderParams {
+    pub camera_center: [f32; 2],
+    pub camera_zoom: f32,
+    pub selected_index: u32,
+    pub color_source: u32,
+    pub color_range_min: f32,
+    pub color_range_max: f32,
+    pub num_species: u32,
+    pub num_particles: u32,
+    pub sprite_size: f32,
+    pub clip_scale: [f32; 2],
+    pub clip_offset: [f32; 2],
+}
+const _: () = assert!(
+    std::mem::size_of::<RenderParams>() == 56,
+    "size of RenderParams does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, camera_center) == 0,
+    "offset of RenderParams.camera_center does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, camera_zoom) == 8,
+    "offset of RenderParams.camera_zoom does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, selected_index) == 12,
+    "offset of RenderParams.selected_index does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, color_source) == 16,
+    "offset of RenderParams.color_source does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, color_range_min) == 20,
+    "offset of RenderParams.color_range_min does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, color_range_max) == 24,
+    "offset of RenderParams.color_range_max does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, num_species) == 28,
+    "offset of RenderParams.num_species does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, num_particles) == 32,
+    "offset of RenderParams.num_particles does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, sprite_size) == 36,
+    "offset of RenderParams.sprite_size does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, clip_scale) == 40,
+    "offset of RenderParams.clip_scale does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(RenderParams, clip_offset) == 48,
+    "offset of RenderParams.clip_offset does not match WGSL"
+);
+#[repr(C)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    bytemuck :: Pod,
+    bytemuck :: Zeroable,
+    serde :: Serialize,
+    serde :: Deserialize,
+)]
+pub struct SteeringSums {
+    pub separation: [f32; 2],
+    pub alignment: [f32; 2],
+    pub center_of_mass: [f32; 2],
+    pub separation_count: u32,
+    pub alignment_count: u32,
+    pub cohesion_count: u32,
+    pub neighbor_count: u32,
+    pub nearest_distance: f32,
+    pub _padding: f32,
+}
+const _: () = assert!(
+    std::mem::size_of::<SteeringSums>() == 48,
+    "size of SteeringSums does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, separation) == 0,
+    "offset of SteeringSums.separation does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, alignment) == 8,
+    "offset of SteeringSums.alignment does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, center_of_mass) == 16,
+    "offset of SteeringSums.center_of_mass does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, separation_count) == 24,
+    "offset of SteeringSums.separation_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, alignment_count) == 28,
+    "offset of SteeringSums.alignment_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, cohesion_count) == 32,
+    "offset of SteeringSums.cohesion_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, neighbor_count) == 36,
+    "offset of SteeringSums.neighbor_count does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, nearest_distance) == 40,
+    "offset of SteeringSums.nearest_distance does not match WGSL"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SteeringSums, _padding) == 44,
+    "offset of SteeringSums._padding does not match WGSL"
+);
+pub const PI: f32 = 3.1415927f32;
+pub const TAU: f32 = 6.2831855f32;
+pub const INTEGRATOR_EXPLICIT_EULER: u32 = 0u32;
+pub const INTEGRATOR_SEMI_IMPLICIT_EULER: u32 = 1u32;
+pub const INTEGRATOR_VELOCITY_VERLET: u32 = 2u32;
+pub const INTEGRATOR_RK2: u32 = 3u32;
+pub const MAX_DISTANCE: f32 = 2.828427f32;
+pub const STATS_WORKGROUP_SIZE: u32 = 256u32;
+pub const PICK_INDEX_BITS: u32 = 17u32;
+pub const PICK_MAX_QUANTIZED_DISTANCE: u32 = 32767u32;
+pub const NO_SELECTION: u32 = 4294967295u32;
+pub const COLOR_SOURCE_HEADING: u32 = 0u32;
+pub const COLOR_SOURCE_SPEED: u32 = 1u32;
+pub const COLOR_SOURCE_DENSITY: u32 = 2u32;
+pub const COLOR_SOURCE_NEIGHBOR_COUNT: u32 = 3u32;
+pub const COLOR_SOURCE_SPECIES: u32 = 4u32;
+pub const COLOR_SOURCE_INDEX: u32 = 5u32;
+pub const PERCEPTION_RADIUS_SEGMENTS: u32 = 24u32;
+pub const PERCEPTION_RADII_VERTEX_COUNT: u32 = 144u32;
+pub const VELOCITY_VECTOR_SECONDS: f32 = 0.5f32;
+pub const FADE_OUT_FRACTION: f32 = 0.2f32;
+pub const EMITTER_POINT: u32 = 0u32;
+pub const EMITTER_LINE: u32 = 1u32;
+pub const EMITTER_AREA: u32 = 2u32;
+pub const MAX_EMITTERS: u32 = 4u32;
+pub const COUNT_LIVE: u32 = 0u32;
+pub const COUNT_NEXT: u32 = 1u32;
+pub const COUNT_STEP: u32 = 2u32;
+pub const COUNT_SPAWN_CARRY: u32 = 3u32;
+pub const NUM_COUNTS: u32 = 7u32;
+pub const INDIRECT_WORKGROUP_SIZE: u32 = 256u32;
+pub const TILED_WORKGROUP_SIZE: u32 = 256u32;
+pub const MAX_TILE_SIZE: u32 = 512u32;
+pub const INDIRECT_DISPATCH: u32 = 0u32;
+pub const INDIRECT_DISPATCH_BOIDS: u32 = 3u32;
+pub const INDIRECT_DRAW_TRIANGLES: u32 = 6u32;
+pub const INDIRECT_DRAW_SPRITES: u32 = 10u32;
+pub const INDIRECT_DRAW_VELOCITY_VECTORS: u32 = 14u32;
+pub const INDIRECT_DRAW_PERCEPTION_RADII: u32 = 18u32;
+pub const INDIRECT_ARGS_LEN: u32 = 22u32;
+pub const COMPACT_WORKGROUP_SIZE: u32 = 256u32;
+pub struct OverrideConstants {
+    pub boids_workgroup_size: Option<u32>,
+    pub tile_size: Option<u32>,
+}
+impl OverrideConstants {
+    pub fn constants(&self) -> std::collections::HashMap<String, f64> {
+        let entries = [
+            self.boids_workgroup_size
+                .map(|value| ("boids_workgroup_size".to_owned(), value as f64)),
+            self.tile_size
+                .map(|value| ("tile_size".to_owned(), value as f64)),
+        ];
+        entries.into_iter().flatten().collect()
+    }
+}
+pub mod render_params {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 6u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod color_lut {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 10u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = &'a wgpu::TextureView;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::TextureView(resource),
+        }
+    }
+}
+pub mod color_lut_sampler {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 11u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    };
+    pub type Resource<'a> = &'a wgpu::Sampler;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Sampler(resource),
+        }
+    }
+}
+pub mod sprite_texture {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 12u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = &'a wgpu::TextureView;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::TextureView(resource),
+        }
+    }
+}
+pub mod image_sampler {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 13u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    };
+    pub type Resource<'a> = &'a wgpu::Sampler;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Sampler(resource),
+        }
+    }
+}
+pub mod background_texture {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 14u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = &'a wgpu::TextureView;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::TextureView(resource),
+        }
+    }
 }
-const _: () = assert!(
-    std::mem::size_of::<SimParams>() == 32,
-    "size of SimParams does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, num_particles) == 0,
-    "offset of SimParams.num_particles does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, delta_time) == 4,
-    "offset of SimParams.delta_time does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, separation_distance) == 8,
-    "offset of SimParams.separation_distance does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, alignment_distance) == 12,
-    "offset of SimParams.alignment_distance does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, cohesion_distance) == 16,
-    "offset of SimParams.cohesion_distance does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, separation_scale) == 20,
-    "offset of SimParams.separation_scale does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, alignment_scale) == 24,
-    "offset of SimParams.alignment_scale does not match WGSL"
-);
-const _: () = assert!(
-    std::mem::offset_of!(SimParams, cohesion_scale) == 28,
-    "offset of SimParams.cohesion_scale does not match WGSL"
-);
-pub const PI: f32 = 3.1415927f32;
-pub const TAU: f32 = 6.2831855f32;
 pub mod params {
     pub const GROUP: u32 = 0u32;
     pub const BINDING: u32 = 0u32;
@@ -147,8 +844,197 @@ pub mod particles_dst {
         }
     }
 }
+pub mod particle_info {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 3u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod stats_partials {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 4u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod flock_stats {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 5u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod inspect_params {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 7u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod pick_result {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 8u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod inspected {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 9u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod particle_counts {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 15u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod indirect_args {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 16u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
+pub mod emit_params {
+    pub const GROUP: u32 = 0u32;
+    pub const BINDING: u32 = 17u32;
+    pub const LAYOUT: wgpu::BindGroupLayoutEntry = wgpu::BindGroupLayoutEntry {
+        binding: BINDING,
+        visibility: wgpu::ShaderStages::all(),
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    pub type Resource<'a> = wgpu::BufferBinding<'a>;
+    pub fn bind_group_entry(resource: Resource) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: BINDING,
+            resource: wgpu::BindingResource::Buffer(resource),
+        }
+    }
+}
 impl Particle {
-    pub const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+    pub const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 4] = [
         wgpu::VertexAttribute {
             format: wgpu::VertexFormat::Float32x2,
             offset: std::mem::offset_of!(Particle, pos) as u64,
@@ -159,6 +1045,16 @@ impl Particle {
             offset: std::mem::offset_of!(Particle, vel) as u64,
             shader_location: 1,
         },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32,
+            offset: std::mem::offset_of!(Particle, age) as u64,
+            shader_location: 4,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32,
+            offset: std::mem::offset_of!(Particle, lifetime) as u64,
+            shader_location: 5,
+        },
     ];
     pub const fn vertex_buffer_layout(
         step_mode: wgpu::VertexStepMode,
@@ -170,10 +1066,63 @@ impl Particle {
         }
     }
 }
-pub const BOIDS_CS_WORKGROUP_SIZE: [u32; 3] = [256, 1, 1];
+impl ParticleInfo {
+    pub const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Uint32,
+            offset: std::mem::offset_of!(ParticleInfo, neighbor_count) as u64,
+            shader_location: 2,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32,
+            offset: std::mem::offset_of!(ParticleInfo, nearest_distance) as u64,
+            shader_location: 3,
+        },
+    ];
+    pub const fn vertex_buffer_layout(
+        step_mode: wgpu::VertexStepMode,
+    ) -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ParticleInfo>() as u64,
+            step_mode,
+            attributes: &ParticleInfo::VERTEX_ATTRIBUTES,
+        }
+    }
+}
+pub const BOIDS_CS_64_WORKGROUP_SIZE: [u32; 3] = [64, 1, 1];
+pub const BOIDS_CS_128_WORKGROUP_SIZE: [u32; 3] = [128, 1, 1];
+pub const BOIDS_CS_256_WORKGROUP_SIZE: [u32; 3] = [256, 1, 1];
+pub const BOIDS_CS_512_WORKGROUP_SIZE: [u32; 3] = [512, 1, 1];
+pub const BOIDS_TILED_CS_WORKGROUP_SIZE: [u32; 3] = [256, 1, 1];
+pub const COMPACT_CS_WORKGROUP_SIZE: [u32; 3] = [256, 1, 1];
+pub const EMIT_CS_WORKGROUP_SIZE: [u32; 3] = [64, 1, 1];
+pub const INDIRECT_ARGS_CS_WORKGROUP_SIZE: [u32; 3] = [1, 1, 1];
+pub const FINISH_STEP_CS_WORKGROUP_SIZE: [u32; 3] = [1, 1, 1];
+pub const STATS_PARTIAL_CS_WORKGROUP_SIZE: [u32; 3] = [256, 1, 1];
+pub const STATS_FINAL_CS_WORKGROUP_SIZE: [u32; 3] = [256, 1, 1];
+pub const PICK_CS_WORKGROUP_SIZE: [u32; 3] = [256, 1, 1];
+pub const INSPECT_CS_WORKGROUP_SIZE: [u32; 3] = [1, 1, 1];
 pub const ENTRY_BOIDS_VS: &str = "boids_vs";
 pub const ENTRY_BOIDS_FS: &str = "boids_fs";
-pub const ENTRY_BOIDS_CS: &str = "boids_cs";
+pub const ENTRY_VELOCITY_VECTORS_VS: &str = "velocity_vectors_vs";
+pub const ENTRY_PERCEPTION_RADII_VS: &str = "perception_radii_vs";
+pub const ENTRY_SPRITES_VS: &str = "sprites_vs";
+pub const ENTRY_SPRITES_FS: &str = "sprites_fs";
+pub const ENTRY_BACKGROUND_VS: &str = "background_vs";
+pub const ENTRY_BACKGROUND_FS: &str = "background_fs";
+pub const ENTRY_BOIDS_CS_64: &str = "boids_cs_64";
+pub const ENTRY_BOIDS_CS_128: &str = "boids_cs_128";
+pub const ENTRY_BOIDS_CS_256: &str = "boids_cs_256";
+pub const ENTRY_BOIDS_CS_512: &str = "boids_cs_512";
+pub const ENTRY_BOIDS_TILED_CS: &str = "boids_tiled_cs";
+pub const ENTRY_COMPACT_CS: &str = "compact_cs";
+pub const ENTRY_EMIT_CS: &str = "emit_cs";
+pub const ENTRY_INDIRECT_ARGS_CS: &str = "indirect_args_cs";
+pub const ENTRY_FINISH_STEP_CS: &str = "finish_step_cs";
+pub const ENTRY_STATS_PARTIAL_CS: &str = "stats_partial_cs";
+pub const ENTRY_STATS_FINAL_CS: &str = "stats_final_cs";
+pub const ENTRY_PICK_CS: &str = "pick_cs";
+pub const ENTRY_INSPECT_CS: &str = "inspect_cs";
 #[derive(Debug)]
 pub struct VertexEntry<const N: usize> {
     pub entry_point: &'static str,
@@ -194,11 +1143,63 @@ pub fn vertex_state<'a, const N: usize>(
         },
     }
 }
-pub fn boids_vs_entry(particle: wgpu::VertexStepMode) -> VertexEntry<1> {
+pub fn boids_vs_entry(
+    particle: wgpu::VertexStepMode,
+    info: wgpu::VertexStepMode,
+    overrides: &OverrideConstants,
+) -> VertexEntry<2> {
     VertexEntry {
         entry_point: ENTRY_BOIDS_VS,
+        buffers: [
+            Particle::vertex_buffer_layout(particle),
+            ParticleInfo::vertex_buffer_layout(info),
+        ],
+        constants: overrides.constants(),
+    }
+}
+pub fn velocity_vectors_vs_entry(
+    particle: wgpu::VertexStepMode,
+    info: wgpu::VertexStepMode,
+    overrides: &OverrideConstants,
+) -> VertexEntry<2> {
+    VertexEntry {
+        entry_point: ENTRY_VELOCITY_VECTORS_VS,
+        buffers: [
+            Particle::vertex_buffer_layout(particle),
+            ParticleInfo::vertex_buffer_layout(info),
+        ],
+        constants: overrides.constants(),
+    }
+}
+pub fn perception_radii_vs_entry(
+    particle: wgpu::VertexStepMode,
+    overrides: &OverrideConstants,
+) -> VertexEntry<1> {
+    VertexEntry {
+        entry_point: ENTRY_PERCEPTION_RADII_VS,
         buffers: [Particle::vertex_buffer_layout(particle)],
-        constants: Default::default(),
+        constants: overrides.constants(),
+    }
+}
+pub fn sprites_vs_entry(
+    particle: wgpu::VertexStepMode,
+    info: wgpu::VertexStepMode,
+    overrides: &OverrideConstants,
+) -> VertexEntry<2> {
+    VertexEntry {
+        entry_point: ENTRY_SPRITES_VS,
+        buffers: [
+            Particle::vertex_buffer_layout(particle),
+            ParticleInfo::vertex_buffer_layout(info),
+        ],
+        constants: overrides.constants(),
+    }
+}
+pub fn background_vs_entry(overrides: &OverrideConstants) -> VertexEntry<0> {
+    VertexEntry {
+        entry_point: ENTRY_BACKGROUND_VS,
+        buffers: [],
+        constants: overrides.constants(),
     }
 }
 #[derive(Debug)]
@@ -221,15 +1222,38 @@ pub fn fragment_state<'a, const N: usize>(
         },
     }
 }
-pub fn boids_fs_entry(targets: [Option<wgpu::ColorTargetState>; 1]) -> FragmentEntry<1> {
+pub fn boids_fs_entry(
+    targets: [Option<wgpu::ColorTargetState>; 1],
+    overrides: &OverrideConstants,
+) -> FragmentEntry<1> {
     FragmentEntry {
         entry_point: ENTRY_BOIDS_FS,
         targets,
-        constants: Default::default(),
+        constants: overrides.constants(),
+    }
+}
+pub fn sprites_fs_entry(
+    targets: [Option<wgpu::ColorTargetState>; 1],
+    overrides: &OverrideConstants,
+) -> FragmentEntry<1> {
+    FragmentEntry {
+        entry_point: ENTRY_SPRITES_FS,
+        targets,
+        constants: overrides.constants(),
+    }
+}
+pub fn background_fs_entry(
+    targets: [Option<wgpu::ColorTargetState>; 1],
+    overrides: &OverrideConstants,
+) -> FragmentEntry<1> {
+    FragmentEntry {
+        entry_point: ENTRY_BACKGROUND_FS,
+        targets,
+        constants: overrides.constants(),
     }
 }
 pub fn create_shader_module(device: &wgpu::Device) -> wgpu::ShaderModule {
-    let source = std :: borrow :: Cow :: Borrowed ("const PI: f32 = 3.14159265358979323846264338327950288;\r\nconst TAU: f32 = 6.28318530717958647692528676655900577;\r\n\r\nstruct Particle {\r\n    @location(0) pos: vec2<f32>,\r\n    @location(1) vel: vec2<f32>,\r\n};\r\n\r\nstruct SimParams {\r\n    num_particles: u32,\r\n    delta_time: f32,\r\n    separation_distance: f32,\r\n    alignment_distance: f32,\r\n    cohesion_distance: f32,\r\n    separation_scale: f32,\r\n    alignment_scale: f32,\r\n    cohesion_scale: f32,\r\n};\r\n\r\nstruct VertexOutput {\r\n  @builtin(position) position: vec4f,\r\n  @location(0) color: vec4f,\r\n}\r\n\r\nvar<private> VERTEX_POSITIONS: array<vec2f, 3> = array(vec2f(-0.01, -0.02), vec2f(0.01, -0.02), vec2f(0.00, 0.02));\r\n\r\n@vertex\r\nfn boids_vs(\r\n    particle: Particle,\r\n    @builtin(vertex_index) vertex_index: u32,\r\n) -> VertexOutput {\r\n    let position = 0.2 * VERTEX_POSITIONS[vertex_index];\r\n    let angle = -atan2(particle.vel.x, particle.vel.y);\r\n    let pos = vec2<f32>(\r\n        position.x * cos(angle) - position.y * sin(angle),\r\n        position.x * sin(angle) + position.y * cos(angle)\r\n    );\r\n\r\n    var output: VertexOutput;\r\n    output.position = vec4(pos + particle.pos, 0., 1.);\r\n    output.color = vec4f(\r\n        saturate(2. * cos(angle)),\r\n        saturate(2. * cos(angle - (TAU / 3.))),\r\n        saturate(2. * cos(angle - (2. * TAU / 3.))),\r\n        1.\r\n    );\r\n    return output;\r\n}\r\n\r\n@fragment\r\nfn boids_fs(@location(0) color: vec4f) -> @location(0) vec4f {\r\n    return color;\r\n}\r\n\r\n@group(0) @binding(0) var<uniform> params : SimParams;\r\n@group(0) @binding(1) var<storage, read> particles_src : array<Particle>;\r\n@group(0) @binding(2) var<storage, read_write> particles_dst : array<Particle>;\r\n\r\n// https://github.com/austinEng/Project6-Vulkan-Flocking/blob/master/data/shaders/computeparticles/particle.comp\r\n@compute @workgroup_size(256)\r\nfn boids_cs(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {\r\n    let index = global_invocation_id.x;\r\n    if index >= params.num_particles {\r\n        return;\r\n    }\r\n\r\n    let me = particles_src[index];\r\n\r\n    var separation_vel = vec2f(0.);\r\n    var alignment_vel = vec2f(0.);\r\n    var alignment_count = 0u;\r\n    var center_of_mass = vec2f(0.);\r\n    var cohesion_count = 0u;\r\n\r\n    for (var i = 0u; i < params.num_particles; i++) {\r\n        if i == index {\r\n            continue;\r\n        }\r\n\r\n        let other = particles_src[i];\r\n\r\n        if distance(me.pos, other.pos) < params.separation_distance {\r\n            separation_vel += me.pos - other.pos;\r\n        }\r\n        if distance(me.pos, other.pos) < params.alignment_distance {\r\n            alignment_vel += other.vel;\r\n            alignment_count += 1u;\r\n        }\r\n        if distance(me.pos, other.pos) < params.cohesion_distance {\r\n            center_of_mass += other.pos;\r\n            cohesion_count += 1u;\r\n        }\r\n    }\r\n    if alignment_count > 0 {\r\n        alignment_vel /= f32(alignment_count);\r\n    }\r\n    var cohesion_vel = vec2f(0.);\r\n    if cohesion_count > 0 {\r\n        cohesion_vel = (center_of_mass / f32(cohesion_count)) - me.pos;\r\n    }\r\n\r\n    var new_particle = me;\r\n    new_particle.vel += separation_vel * params.separation_scale;\r\n    new_particle.vel += alignment_vel * params.alignment_scale;\r\n    new_particle.vel += cohesion_vel * params.cohesion_scale;\r\n\r\n    // clamp velocity for a more pleasing simulation\r\n    new_particle.vel = normalize(new_particle.vel) * clamp(length(new_particle.vel), 0.0, 0.1);\r\n\r\n    // kinematic update\r\n    new_particle.pos += new_particle.vel * params.delta_time;\r\n\r\n    // Wrap around boundary\r\n    new_particle.pos = 2. * fract(0.5 + 0.5 * new_particle.pos) - 1.;\r\n\r\n    // Write back\r\n    particles_dst[index] = new_particle;\r\n}\r\n") ;
+    let source = std :: borrow :: Cow :: Borrowed ("const PI: f32 = 3.14159265358979323846264338327950288;\nconst TAU: f32 = 6.28318530717958647692528676655900577;\n\n// Values of `SimParams.integrator`\nconst INTEGRATOR_EXPLICIT_EULER: u32 = 0u;\nconst INTEGRATOR_SEMI_IMPLICIT_EULER: u32 = 1u;\nconst INTEGRATOR_VELOCITY_VERLET: u32 = 2u;\nconst INTEGRATOR_RK2: u32 = 3u;\n\n// Longest possible distance between two boids in the [-1, 1] box\nconst MAX_DISTANCE: f32 = 2.82842712474619009760337744841939615;\n\nconst STATS_WORKGROUP_SIZE: u32 = 256u;\n\n// `pick_result` packs the distance to the picked position into the high bits and the particle index into the low bits,\n// so that `atomicMin` finds the nearest boid\nconst PICK_INDEX_BITS: u32 = 17u;\nconst PICK_MAX_QUANTIZED_DISTANCE: u32 = 32767u;\n\n// Value of `RenderParams.selected_index` when no boid is selected\nconst NO_SELECTION: u32 = 0xffffffffu;\n\n// Values of `RenderParams.color_source`\nconst COLOR_SOURCE_HEADING: u32 = 0u;\nconst COLOR_SOURCE_SPEED: u32 = 1u;\nconst COLOR_SOURCE_DENSITY: u32 = 2u;\nconst COLOR_SOURCE_NEIGHBOR_COUNT: u32 = 3u;\nconst COLOR_SOURCE_SPECIES: u32 = 4u;\nconst COLOR_SOURCE_INDEX: u32 = 5u;\n\n// Perception radii are drawn as line lists with this many segments per circle\nconst PERCEPTION_RADIUS_SEGMENTS: u32 = 24u;\n// Separation, alignment and cohesion circles with two vertices per segment\nconst PERCEPTION_RADII_VERTEX_COUNT: u32 = 144u;\n\n// Velocity vectors show how far a boid travels in this time\nconst VELOCITY_VECTOR_SECONDS: f32 = 0.5;\n\n// Boids shrink away during this fraction of their lifetime before they die\nconst FADE_OUT_FRACTION: f32 = 0.2;\n\n// Values of `Emitter.shape`\nconst EMITTER_POINT: u32 = 0u;\nconst EMITTER_LINE: u32 = 1u;\nconst EMITTER_AREA: u32 = 2u;\n\nconst MAX_EMITTERS: u32 = 4u;\n\n// Indices into `particle_counts`\n// Boids that are alive at the start of a step, only the first this many in `particles_src` are valid\nconst COUNT_LIVE: u32 = 0u;\n// Boids that survived the current step, which `compact_cs` moved to the front of `particles_dst`\nconst COUNT_NEXT: u32 = 1u;\n// Number of steps that emitted boids, seeds the random numbers of the emitters\nconst COUNT_STEP: u32 = 2u;\n// `MAX_EMITTERS` fractions of a boid that every emitter carries over into the next step, bitcast to u32\nconst COUNT_SPAWN_CARRY: u32 = 3u;\nconst NUM_COUNTS: u32 = 7u;\n\n// Workgroup size of every kernel that is dispatched with the arguments at `INDIRECT_DISPATCH`\nconst INDIRECT_WORKGROUP_SIZE: u32 = 256u;\n\nconst TILED_WORKGROUP_SIZE: u32 = 256u;\n// Size of the workgroup memory that `boids_tiled_cs` loads boids into\nconst MAX_TILE_SIZE: u32 = 512u;\n\n// Offsets of the `dispatch_workgroups_indirect` and `draw_indirect` arguments in `indirect_args`, in u32s. The draw\n// arguments draw every live boid with the given number of vertices.\nconst INDIRECT_DISPATCH: u32 = 0u;\n// Dispatches the `boids_cs_*` entry point with `boids_workgroup_size`\nconst INDIRECT_DISPATCH_BOIDS: u32 = 3u;\nconst INDIRECT_DRAW_TRIANGLES: u32 = 6u;\nconst INDIRECT_DRAW_SPRITES: u32 = 10u;\nconst INDIRECT_DRAW_VELOCITY_VECTORS: u32 = 14u;\nconst INDIRECT_DRAW_PERCEPTION_RADII: u32 = 18u;\nconst INDIRECT_ARGS_LEN: u32 = 22u;\n\nvar<private> SEPARATION_COLOR: vec4f = vec4f(0.9, 0.31, 0.31, 0.4);\nvar<private> ALIGNMENT_COLOR: vec4f = vec4f(0.31, 0.78, 0.31, 0.4);\nvar<private> COHESION_COLOR: vec4f = vec4f(0.31, 0.55, 0.94, 0.4);\n\nstruct Particle {\n    @location(0) pos: vec2<f32>,\n    @location(1) vel: vec2<f32>,\n    // Seconds since the boid was emitted\n    @location(4) age: f32,\n    // Seconds until the boid dies, or 0 if it lives forever\n    @location(5) lifetime: f32,\n};\n\nstruct SimParams {\n    num_particles: u32,\n    delta_time: f32,\n    separation_distance: f32,\n    alignment_distance: f32,\n    cohesion_distance: f32,\n    separation_scale: f32,\n    alignment_scale: f32,\n    cohesion_scale: f32,\n    integrator: u32,\n    damping: f32,\n    min_speed: f32,\n    max_speed: f32,\n};\n\n// Spawns boids at a point, along the line from `start` to `end`, or in the rectangle with the corners `start` and `end`\nstruct Emitter {\n    start: vec2f,\n    end: vec2f,\n    shape: u32,\n    // Boids per second\n    rate: f32,\n    lifetime: f32,\n    speed: f32,\n    // Angle of the velocity of new boids in radians, counterclockwise from the x axis\n    direction: f32,\n    // New boids fly off up to half of this angle to either side of `direction`\n    spread: f32,\n    _padding: vec2f,\n};\n\nstruct EmitParams {\n    emitters: array<Emitter, MAX_EMITTERS>,\n    num_emitters: u32,\n    // If 0, `SimParams.num_particles` boids live forever and nothing is emitted. Otherwise the emitters spawn boids\n    // until there are `SimParams.num_particles` of them, and boids die once they reach their lifetime.\n    enabled: u32,\n    seed: u32,\n    _padding: u32,\n};\n\n// Per-boid values from the last simulation step that aren't needed to simulate the next one. Also used as a vertex\n// buffer, so that coloring by them doesn't need storage buffers in the vertex shader.\nstruct ParticleInfo {\n    @location(2) neighbor_count: u32,\n    @location(3) nearest_distance: f32,\n};\n\n// Sums over (part of) the flock, used to reduce the flock down to `FlockStats`\nstruct StatsAccumulator {\n    speed: f32,\n    neighbor_count: f32,\n    nearest_distance: f32,\n    angular_momentum: f32,\n    heading: vec2f,\n    position: vec2f,\n    velocity: vec2f,\n    position_sq: f32,\n    _padding: f32,\n};\n\nstruct FlockStats {\n    mean_speed: f32,\n    // Length of the mean heading, 1 when every boid flies in the same direction\n    polarization: f32,\n    // Angular momentum around the center of the flock, normalized by its RMS radius and mean speed\n    milling: f32,\n    mean_neighbor_count: f32,\n    mean_nearest_distance: f32,\n};\n\n// The separation, alignment and cohesion fields are the individual contributions to the acceleration\nstruct Steering {\n    acceleration: vec2f,\n    separation: vec2f,\n    alignment: vec2f,\n    cohesion: vec2f,\n    separation_count: u32,\n    alignment_count: u32,\n    cohesion_count: u32,\n    neighbor_count: u32,\n    nearest_distance: f32,\n    _padding: f32,\n};\n\nstruct InspectParams {\n    pick_position: vec2f,\n    selected_index: u32,\n    _padding: u32,\n};\n\nstruct InspectedBoid {\n    particle: Particle,\n    steering: Steering,\n};\n\nstruct RenderParams {\n    camera_center: vec2f,\n    camera_zoom: f32,\n    selected_index: u32,\n    color_source: u32,\n    // The value of the color source that maps to the start and end of `color_lut`\n    color_range_min: f32,\n    color_range_max: f32,\n    num_species: u32,\n    num_particles: u32,\n    // Half of the width and height of a sprite\n    sprite_size: f32,\n    // Maps the camera's view onto the render target. It is scaled by 1 and not offset for the whole view, and narrows\n    // it down to one tile of an image that is rendered in tiles.\n    clip_scale: vec2f,\n    clip_offset: vec2f,\n};\n\nstruct VertexOutput {\n  @builtin(position) position: vec4f,\n  @location(0) color: vec4f,\n}\n\nstruct TexturedVertexOutput {\n  @builtin(position) position: vec4f,\n  @location(0) color: vec4f,\n  @location(1) uv: vec2f,\n}\n\nvar<private> VERTEX_POSITIONS: array<vec2f, 3> = array(vec2f(-0.01, -0.02), vec2f(0.01, -0.02), vec2f(0.00, 0.02));\n\n// Two triangles covering [-1, 1]\nvar<private> QUAD_CORNERS: array<vec2f, 6> = array(\n    vec2f(-1., -1.), vec2f(1., -1.), vec2f(1., 1.),\n    vec2f(-1., -1.), vec2f(1., 1.), vec2f(-1., 1.),\n);\n\n@group(0) @binding(6) var<uniform> render_params : RenderParams;\n@group(0) @binding(10) var color_lut: texture_2d<f32>;\n@group(0) @binding(11) var color_lut_sampler: sampler;\n@group(0) @binding(12) var sprite_texture: texture_2d<f32>;\n@group(0) @binding(13) var image_sampler: sampler;\n@group(0) @binding(14) var background_texture: texture_2d<f32>;\n\nfn world_to_clip(pos: vec2f) -> vec4f {\n    let view = (pos - render_params.camera_center) * render_params.camera_zoom;\n    return vec4(view * render_params.clip_scale + render_params.clip_offset, 0., 1.);\n}\n\n// Where in `color_lut` the color of a boid is looked up\nfn color_coordinate(particle: Particle, info: ParticleInfo, index: u32, angle: f32) -> f32 {\n    switch render_params.color_source {\n        case COLOR_SOURCE_HEADING: {\n            return fract(angle / TAU);\n        }\n        case COLOR_SOURCE_SPECIES: {\n            let num_species = max(render_params.num_species, 1u);\n            return (f32(index % num_species) + 0.5) / f32(num_species);\n        }\n        case COLOR_SOURCE_INDEX: {\n            return f32(index) / f32(max(render_params.num_particles, 1u));\n        }\n        default: {}\n    }\n\n    var value: f32;\n    switch render_params.color_source {\n        case COLOR_SOURCE_SPEED: {\n            value = length(particle.vel);\n        }\n        case COLOR_SOURCE_DENSITY: {\n            // Boids per unit area, estimated from the distance to the nearest neighbor\n            value = 1. / (PI * max(info.nearest_distance * info.nearest_distance, 1e-12));\n        }\n        // COLOR_SOURCE_NEIGHBOR_COUNT\n        default: {\n            value = f32(info.neighbor_count);\n        }\n    }\n    let range = render_params.color_range_max - render_params.color_range_min;\n    return saturate((value - render_params.color_range_min) / select(range, 1e-12, range == 0.));\n}\n\n// Angle that rotates something pointing up so that it points along `vel`\nfn heading_angle(vel: vec2f) -> f32 {\n    return -atan2(vel.x, vel.y);\n}\n\nfn rotate(v: vec2f, angle: f32) -> vec2f {\n    return vec2f(v.x * cos(angle) - v.y * sin(angle), v.x * sin(angle) + v.y * cos(angle));\n}\n\nfn boid_color(particle: Particle, info: ParticleInfo, index: u32, angle: f32) -> vec4f {\n    if index == render_params.selected_index {\n        return vec4f(1.);\n    }\n    let color_coordinate = color_coordinate(particle, info, index, angle);\n    return textureSampleLevel(color_lut, color_lut_sampler, vec2f(color_coordinate, 0.5), 0.);\n}\n\n// Selected boids are drawn larger, and boids shrink away at the end of their lifetime\nfn boid_scale(particle: Particle, index: u32) -> f32 {\n    var scale = select(1., 2., index == render_params.selected_index);\n    if particle.lifetime > 0. {\n        scale *= saturate((particle.lifetime - particle.age) / (FADE_OUT_FRACTION * particle.lifetime));\n    }\n    return scale;\n}\n\n@vertex\nfn boids_vs(\n    particle: Particle,\n    info: ParticleInfo,\n    @builtin(vertex_index) vertex_index: u32,\n    @builtin(instance_index) instance_index: u32,\n) -> VertexOutput {\n    let angle = heading_angle(particle.vel);\n    let position = 0.2 * boid_scale(particle, instance_index) * VERTEX_POSITIONS[vertex_index];\n\n    var output: VertexOutput;\n    output.position = world_to_clip(rotate(position, angle) + particle.pos);\n    output.color = boid_color(particle, info, instance_index, angle);\n    return output;\n}\n\n@fragment\nfn boids_fs(@location(0) color: vec4f) -> @location(0) vec4f {\n    return color;\n}\n\n// Draws a line from every boid along its velocity\n@vertex\nfn velocity_vectors_vs(\n    particle: Particle,\n    info: ParticleInfo,\n    @builtin(vertex_index) vertex_index: u32,\n    @builtin(instance_index) instance_index: u32,\n) -> VertexOutput {\n    let end = particle.pos + f32(vertex_index) * VELOCITY_VECTOR_SECONDS * particle.vel;\n\n    var output: VertexOutput;\n    output.position = world_to_clip(end);\n    output.color = boid_color(particle, info, instance_index, heading_angle(particle.vel));\n    return output;\n}\n\n// Draws circles with the separation, alignment and cohesion distances around every boid\n@vertex\nfn perception_radii_vs(particle: Particle, @builtin(vertex_index) vertex_index: u32) -> VertexOutput {\n    let circle = vertex_index / (2u * PERCEPTION_RADIUS_SEGMENTS);\n    let circle_vertex = vertex_index % (2u * PERCEPTION_RADIUS_SEGMENTS);\n    // segment i goes from point i to point i + 1\n    let point = (circle_vertex + 1u) / 2u;\n    let angle = TAU * f32(point) / f32(PERCEPTION_RADIUS_SEGMENTS);\n\n    var radius: f32;\n    var output: VertexOutput;\n    switch circle {\n        case 0u: {\n            radius = params.separation_distance;\n            output.color = SEPARATION_COLOR;\n        }\n        case 1u: {\n            radius = params.alignment_distance;\n            output.color = ALIGNMENT_COLOR;\n        }\n        default: {\n            radius = params.cohesion_distance;\n            output.color = COHESION_COLOR;\n        }\n    }\n    output.position = world_to_clip(particle.pos + radius * vec2f(cos(angle), sin(angle)));\n    return output;\n}\n\n// Draws boids as `sprite_texture` tinted with their color, with the top of the texture pointing along their velocity\n@vertex\nfn sprites_vs(\n    particle: Particle,\n    info: ParticleInfo,\n    @builtin(vertex_index) vertex_index: u32,\n    @builtin(instance_index) instance_index: u32,\n) -> TexturedVertexOutput {\n    let angle = heading_angle(particle.vel);\n    let corner = QUAD_CORNERS[vertex_index];\n    let position = render_params.sprite_size * boid_scale(particle, instance_index) * corner;\n\n    var output: TexturedVertexOutput;\n    output.position = world_to_clip(rotate(position, angle) + particle.pos);\n    output.color = boid_color(particle, info, instance_index, angle);\n    output.uv = vec2f(0.5, -0.5) * corner + 0.5;\n    return output;\n}\n\n@fragment\nfn sprites_fs(vertex: TexturedVertexOutput) -> @location(0) vec4f {\n    return vertex.color * textureSample(sprite_texture, image_sampler, vertex.uv);\n}\n\n// Stretches `background_texture` over the [-1, 1] box the boids live in\n@vertex\nfn background_vs(@builtin(vertex_index) vertex_index: u32) -> TexturedVertexOutput {\n    let corner = QUAD_CORNERS[vertex_index];\n\n    var output: TexturedVertexOutput;\n    output.position = world_to_clip(corner);\n    output.color = vec4f(1.);\n    output.uv = vec2f(0.5, -0.5) * corner + 0.5;\n    return output;\n}\n\n@fragment\nfn background_fs(vertex: TexturedVertexOutput) -> @location(0) vec4f {\n    return textureSample(background_texture, image_sampler, vertex.uv);\n}\n\n@group(0) @binding(0) var<uniform> params : SimParams;\n@group(0) @binding(1) var<storage, read> particles_src : array<Particle>;\n@group(0) @binding(2) var<storage, read_write> particles_dst : array<Particle>;\n@group(0) @binding(3) var<storage, read_write> particle_info : array<ParticleInfo>;\n@group(0) @binding(4) var<storage, read_write> stats_partials : array<StatsAccumulator>;\n@group(0) @binding(5) var<storage, read_write> flock_stats : FlockStats;\n@group(0) @binding(7) var<uniform> inspect_params : InspectParams;\n@group(0) @binding(8) var<storage, read_write> pick_result : atomic<u32>;\n@group(0) @binding(9) var<storage, read_write> inspected : InspectedBoid;\n@group(0) @binding(15) var<storage, read_write> particle_counts : array<atomic<u32>, NUM_COUNTS>;\n@group(0) @binding(16) var<storage, read_write> indirect_args : array<u32, INDIRECT_ARGS_LEN>;\n@group(0) @binding(17) var<uniform> emit_params : EmitParams;\n\nfn live_count() -> u32 {\n    return atomicLoad(&particle_counts[COUNT_LIVE]);\n}\n\n// Sums over the neighbors of a boid, that `finish_steering` turns into its `Steering`\nstruct SteeringSums {\n    separation: vec2f,\n    alignment: vec2f,\n    center_of_mass: vec2f,\n    separation_count: u32,\n    alignment_count: u32,\n    cohesion_count: u32,\n    neighbor_count: u32,\n    nearest_distance: f32,\n    _padding: f32,\n};\n\nfn empty_steering_sums() -> SteeringSums {\n    var sums: SteeringSums;\n    sums.nearest_distance = MAX_DISTANCE;\n    return sums;\n}\n\n// Adds another boid to the sums of a boid at `pos`. Boids within the largest of the three distances count as\n// neighbors.\nfn add_neighbor(sums: ptr<function, SteeringSums>, pos: vec2f, other_pos: vec2f, other_vel: vec2f) {\n    let neighbor_distance = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));\n    let dist = distance(pos, other_pos);\n\n    (*sums).nearest_distance = min((*sums).nearest_distance, dist);\n    if dist < neighbor_distance {\n        (*sums).neighbor_count += 1u;\n    }\n    if dist < params.separation_distance {\n        (*sums).separation += pos - other_pos;\n        (*sums).separation_count += 1u;\n    }\n    if dist < params.alignment_distance {\n        (*sums).alignment += other_vel;\n        (*sums).alignment_count += 1u;\n    }\n    if dist < params.cohesion_distance {\n        (*sums).center_of_mass += other_pos;\n        (*sums).cohesion_count += 1u;\n    }\n}\n\nfn finish_steering(sums: SteeringSums, pos: vec2f, vel: vec2f) -> Steering {\n    var alignment = vec2f(0.);\n    if sums.alignment_count > 0 {\n        alignment = sums.alignment / f32(sums.alignment_count);\n    }\n    var cohesion = vec2f(0.);\n    if sums.cohesion_count > 0 {\n        cohesion = (sums.center_of_mass / f32(sums.cohesion_count)) - pos;\n    }\n\n    var result: Steering;\n    result.separation = sums.separation * params.separation_scale;\n    result.alignment = alignment * params.alignment_scale;\n    result.cohesion = cohesion * params.cohesion_scale;\n    result.acceleration = result.separation + result.alignment + result.cohesion - vel * params.damping;\n    result.separation_count = sums.separation_count;\n    result.alignment_count = sums.alignment_count;\n    result.cohesion_count = sums.cohesion_count;\n    result.neighbor_count = sums.neighbor_count;\n    result.nearest_distance = sums.nearest_distance;\n    return result;\n}\n\n// How the rest of the flock in `particles_src` steers a boid at `pos` moving at `vel`.\n// https://github.com/austinEng/Project6-Vulkan-Flocking/blob/master/data/shaders/computeparticles/particle.comp\nfn steering(index: u32, pos: vec2f, vel: vec2f) -> Steering {\n    var sums = empty_steering_sums();\n    let num_particles = live_count();\n    for (var i = 0u; i < num_particles; i++) {\n        if i == index {\n            continue;\n        }\n\n        let other = particles_src[i];\n        add_neighbor(&sums, pos, other.pos, other.vel);\n    }\n    return finish_steering(sums, pos, vel);\n}\n\n// clamp velocity for a more pleasing simulation\nfn limit_speed(vel: vec2f) -> vec2f {\n    let speed = length(vel);\n    if speed == 0. {\n        return vel;\n    }\n    // `clamp` isn't defined if the minimum is above the maximum, so the maximum always wins instead\n    return vel * (min(max(speed, params.min_speed), params.max_speed) / speed);\n}\n\n// Whether the integrator evaluates the acceleration a second time, at `second_steering_state`\nfn needs_second_steering() -> bool {\n    return params.integrator == INTEGRATOR_VELOCITY_VERLET || params.integrator == INTEGRATOR_RK2;\n}\n\n// The integrators that evaluate the acceleration a second time only move this boid to its predicted state. The rest of\n// the flock stays where it was at the start of the step, so this is an approximation.\nfn second_steering_state(me: Particle, accel: vec2f) -> Particle {\n    let dt = params.delta_time;\n    var state = me;\n    if params.integrator == INTEGRATOR_RK2 {\n        // Midpoint method\n        state.pos += 0.5 * me.vel * dt;\n        state.vel += 0.5 * accel * dt;\n    } else {\n        state.pos += me.vel * dt + 0.5 * accel * dt * dt;\n        state.vel += accel * dt;\n    }\n    return state;\n}\n\n// Advances `me` by one step. `second_accel` is only used if `needs_second_steering`.\nfn integrate(me: Particle, accel: vec2f, second_accel: vec2f) -> Particle {\n    let dt = params.delta_time;\n    var new_particle = me;\n    switch params.integrator {\n        case INTEGRATOR_EXPLICIT_EULER: {\n            new_particle.pos += me.vel * dt;\n            new_particle.vel = limit_speed(me.vel + accel * dt);\n        }\n        case INTEGRATOR_VELOCITY_VERLET: {\n            new_particle.pos += me.vel * dt + 0.5 * accel * dt * dt;\n            new_particle.vel = limit_speed(me.vel + 0.5 * (accel + second_accel) * dt);\n        }\n        case INTEGRATOR_RK2: {\n            let mid_vel = me.vel + 0.5 * accel * dt;\n            new_particle.pos += mid_vel * dt;\n            new_particle.vel = limit_speed(me.vel + second_accel * dt);\n        }\n        // INTEGRATOR_SEMI_IMPLICIT_EULER\n        default: {\n            new_particle.vel = limit_speed(me.vel + accel * dt);\n            new_particle.pos += new_particle.vel * dt;\n        }\n    }\n\n    // Wrap around boundary\n    new_particle.pos = 2. * fract(0.5 + 0.5 * new_particle.pos) - 1.;\n    new_particle.age += dt;\n    return new_particle;\n}\n\n// Boids keep their index while they're simulated. With emitters, `compact_cs` drops the dead ones afterwards.\nfn write_particle(index: u32, new_particle: Particle, steering: Steering) {\n    particles_dst[index] = new_particle;\n    particle_info[index] = ParticleInfo(steering.neighbor_count, steering.nearest_distance);\n}\n\nfn is_dead(particle: Particle) -> bool {\n    return particle.lifetime > 0. && particle.age >= particle.lifetime;\n}\n\n// Workgroup size of the `boids_cs_*` entry point that is dispatched with the arguments at `INDIRECT_DISPATCH_BOIDS`.\n// Ideally it would size the workgroup of a single `boids_cs` directly, but naga can't evaluate override-expressions in\n// `@workgroup_size` yet, so there is an entry point for every size that can be picked instead.\noverride boids_workgroup_size: u32 = 256u;\n\n@compute @workgroup_size(64)\nfn boids_cs_64(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {\n    boids_cs(global_invocation_id.x);\n}\n\n@compute @workgroup_size(128)\nfn boids_cs_128(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {\n    boids_cs(global_invocation_id.x);\n}\n\n@compute @workgroup_size(256)\nfn boids_cs_256(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {\n    boids_cs(global_invocation_id.x);\n}\n\n@compute @workgroup_size(512)\nfn boids_cs_512(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {\n    boids_cs(global_invocation_id.x);\n}\n\n// Compares every boid with every other boid, reading the others straight from `particles_src`\nfn boids_cs(index: u32) {\n    if index >= live_count() {\n        return;\n    }\n\n    let me = particles_src[index];\n    let steering = steering(index, me.pos, me.vel);\n    var second_accel = vec2f(0.);\n    if needs_second_steering() {\n        let state = second_steering_state(me, steering.acceleration);\n        second_accel = steering(index, state.pos, state.vel).acceleration;\n    }\n    write_particle(index, integrate(me, steering.acceleration, second_accel), steering);\n}\n\n// Number of boids that `boids_tiled_cs` loads into workgroup memory at once, at most `MAX_TILE_SIZE`\noverride tile_size: u32 = 256u;\n\n// Position in xy and velocity in zw of the boids in the current tile\nvar<workgroup> tile: array<vec4f, MAX_TILE_SIZE>;\nvar<workgroup> tile_num_particles: u32;\n\n// Like `steering`, but the workgroup loads the flock into `tile` one tile at a time and every invocation reads the\n// others from there. Must be called from uniform control flow.\nfn steering_tiled(index: u32, pos: vec2f, vel: vec2f, num_particles: u32, local_index: u32) -> Steering {\n    let tile_capacity = clamp(tile_size, 1u, MAX_TILE_SIZE);\n    var sums = empty_steering_sums();\n    for (var tile_start = 0u; tile_start < num_particles; tile_start += tile_capacity) {\n        // the previous tile has to be used up before it is overwritten\n        workgroupBarrier();\n        for (var i = local_index; i < tile_capacity && tile_start + i < num_particles; i += TILED_WORKGROUP_SIZE) {\n            let other = particles_src[tile_start + i];\n            tile[i] = vec4f(other.pos, other.vel);\n        }\n        workgroupBarrier();\n\n        let tile_len = min(tile_capacity, num_particles - tile_start);\n        for (var i = 0u; i < tile_len; i++) {\n            if tile_start + i == index {\n                continue;\n            }\n            add_neighbor(&sums, pos, tile[i].xy, tile[i].zw);\n        }\n    }\n    return finish_steering(sums, pos, vel);\n}\n\n// Same as `boids_cs`, but with the flock shared through workgroup memory, see `steering_tiled`\n@compute @workgroup_size(256)\nfn boids_tiled_cs(\n    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,\n    @builtin(local_invocation_index) local_index: u32,\n) {\n    if local_index == 0u {\n        tile_num_particles = live_count();\n    }\n    let num_particles = workgroupUniformLoad(&tile_num_particles);\n    let index = global_invocation_id.x;\n\n    // invocations without a boid still have to help load the tiles\n    let me = particles_src[min(index, max(num_particles, 1u) - 1u)];\n    let steering = steering_tiled(index, me.pos, me.vel, num_particles, local_index);\n    var second_accel = vec2f(0.);\n    if needs_second_steering() {\n        let state = second_steering_state(me, steering.acceleration);\n        second_accel = steering_tiled(index, state.pos, state.vel, num_particles, local_index).acceleration;\n    }\n    if index < num_particles {\n        write_particle(index, integrate(me, steering.acceleration, second_accel), steering);\n    }\n}\n\n// https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/\nfn pcg_hash(input: u32) -> u32 {\n    let state = input * 747796405u + 2891336453u;\n    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;\n    return (word >> 22u) ^ word;\n}\n\n// Uniformly distributed in [0, 1], advances `state`\nfn random(state: ptr<function, u32>) -> f32 {\n    *state = pcg_hash(*state);\n    return f32(*state) / 4294967295.;\n}\n\n// How many boids `emitter` spawns in this step\nfn spawn_count(emitter: u32) -> u32 {\n    let carry = bitcast<f32>(atomicLoad(&particle_counts[COUNT_SPAWN_CARRY + emitter]));\n    return u32(carry + emit_params.emitters[emitter].rate * params.delta_time);\n}\n\nconst COMPACT_WORKGROUP_SIZE: u32 = 256u;\n\n// Running sums of whether the boids of the current chunk of `compact_cs` survived\nvar<workgroup> compact_offsets: array<u32, COMPACT_WORKGROUP_SIZE>;\nvar<workgroup> compact_num_particles: u32;\n\n// Moves the boids that survived the step to the front of `particles_dst` in the order they were in, so that indices\n// only shift past the boids that died and the emitters give the same flock every run. A single workgroup goes through\n// the flock a chunk at a time, since the survivors of a chunk can only be placed once every earlier chunk is.\n@compute @workgroup_size(256)\nfn compact_cs(@builtin(local_invocation_index) local_index: u32) {\n    if local_index == 0u {\n        compact_num_particles = live_count();\n    }\n    let num_particles = workgroupUniformLoad(&compact_num_particles);\n\n    var num_survivors = 0u;\n    for (var chunk_start = 0u; chunk_start < num_particles; chunk_start += COMPACT_WORKGROUP_SIZE) {\n        let index = chunk_start + local_index;\n        let particle = particles_dst[min(index, num_particles - 1u)];\n        let info = particle_info[min(index, num_particles - 1u)];\n        let survives = index < num_particles && !is_dead(particle);\n\n        // inclusive prefix sum, the previous chunk has to be done with the offsets first\n        workgroupBarrier();\n        compact_offsets[local_index] = u32(survives);\n        for (var stride = 1u; stride < COMPACT_WORKGROUP_SIZE; stride <<= 1u) {\n            workgroupBarrier();\n            var sum = compact_offsets[local_index];\n            if local_index >= stride {\n                sum += compact_offsets[local_index - stride];\n            }\n            workgroupBarrier();\n            compact_offsets[local_index] = sum;\n        }\n        // every boid of the chunk has to be read before any of them is moved over it\n        storageBarrier();\n        let chunk_survivors = workgroupUniformLoad(&compact_offsets[COMPACT_WORKGROUP_SIZE - 1u]);\n\n        if survives {\n            let dst_index = num_survivors + compact_offsets[local_index] - 1u;\n            particles_dst[dst_index] = particle;\n            particle_info[dst_index] = info;\n        }\n        num_survivors += chunk_survivors;\n    }\n    if local_index == 0u {\n        atomicStore(&particle_counts[COUNT_NEXT], num_survivors);\n    }\n}\n\n// Appends new boids to `particles_dst` after the survivors that `compact_cs` counted, the ones of every emitter after\n// the ones of the emitters before it. Every row of workgroups is one emitter.\n@compute @workgroup_size(64)\nfn emit_cs(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {\n    let emitter_index = global_invocation_id.y;\n    if emitter_index >= emit_params.num_emitters || global_invocation_id.x >= spawn_count(emitter_index) {\n        return;\n    }\n    var index = atomicLoad(&particle_counts[COUNT_NEXT]) + global_invocation_id.x;\n    for (var i = 0u; i < emitter_index; i++) {\n        index += spawn_count(i);\n    }\n    if index >= params.num_particles {\n        return;\n    }\n\n    let emitter = emit_params.emitters[emitter_index];\n    var state = pcg_hash(emit_params.seed ^ pcg_hash(atomicLoad(&particle_counts[COUNT_STEP])));\n    state = pcg_hash(state ^ pcg_hash(emitter_index * 65536u + global_invocation_id.x));\n\n    var particle: Particle;\n    switch emitter.shape {\n        case EMITTER_LINE: {\n            particle.pos = mix(emitter.start, emitter.end, random(&state));\n        }\n        case EMITTER_AREA: {\n            let x = random(&state);\n            particle.pos = mix(emitter.start, emitter.end, vec2f(x, random(&state)));\n        }\n        // EMITTER_POINT\n        default: {\n            particle.pos = emitter.start;\n        }\n    }\n    let angle = emitter.direction + emitter.spread * (random(&state) - 0.5);\n    particle.vel = emitter.speed * vec2f(cos(angle), sin(angle));\n    particle.age = 0.;\n    particle.lifetime = emitter.lifetime;\n\n    particles_dst[index] = particle;\n    particle_info[index] = ParticleInfo(0u, MAX_DISTANCE);\n}\n\nfn write_draw_args(offset: u32, vertex_count: u32, instance_count: u32) {\n    indirect_args[offset] = vertex_count;\n    indirect_args[offset + 1u] = instance_count;\n    indirect_args[offset + 2u] = 0u;\n    indirect_args[offset + 3u] = 0u;\n}\n\nfn write_indirect_args(num_particles: u32) {\n    indirect_args[INDIRECT_DISPATCH] = (num_particles + INDIRECT_WORKGROUP_SIZE - 1u) / INDIRECT_WORKGROUP_SIZE;\n    indirect_args[INDIRECT_DISPATCH + 1u] = 1u;\n    indirect_args[INDIRECT_DISPATCH + 2u] = 1u;\n    indirect_args[INDIRECT_DISPATCH_BOIDS] = (num_particles + boids_workgroup_size - 1u) / boids_workgroup_size;\n    indirect_args[INDIRECT_DISPATCH_BOIDS + 1u] = 1u;\n    indirect_args[INDIRECT_DISPATCH_BOIDS + 2u] = 1u;\n    write_draw_args(INDIRECT_DRAW_TRIANGLES, 3u, num_particles);\n    write_draw_args(INDIRECT_DRAW_SPRITES, 6u, num_particles);\n    write_draw_args(INDIRECT_DRAW_VELOCITY_VECTORS, 2u, num_particles);\n    write_draw_args(INDIRECT_DRAW_PERCEPTION_RADII, PERCEPTION_RADII_VERTEX_COUNT, num_particles);\n}\n\n// Fills `indirect_args` for the boids that are currently alive\n@compute @workgroup_size(1)\nfn indirect_args_cs() {\n    write_indirect_args(live_count());\n}\n\n// Makes the boids that survived or were emitted during this step the live ones, and prepares the counters for the\n// next step\n@compute @workgroup_size(1)\nfn finish_step_cs() {\n    var num_particles = atomicLoad(&particle_counts[COUNT_NEXT]);\n    for (var i = 0u; i < emit_params.num_emitters; i++) {\n        let carry = bitcast<f32>(atomicLoad(&particle_counts[COUNT_SPAWN_CARRY + i]));\n        let spawned = carry + emit_params.emitters[i].rate * params.delta_time;\n        // the same as `spawn_count` of the emitter\n        num_particles += u32(spawned);\n        atomicStore(&particle_counts[COUNT_SPAWN_CARRY + i], bitcast<u32>(fract(spawned)));\n    }\n    num_particles = min(num_particles, params.num_particles);\n    atomicStore(&particle_counts[COUNT_LIVE], num_particles);\n    atomicStore(&particle_counts[COUNT_NEXT], 0u);\n    atomicAdd(&particle_counts[COUNT_STEP], 1u);\n    write_indirect_args(num_particles);\n}\n\nfn cross2(a: vec2f, b: vec2f) -> f32 {\n    return a.x * b.y - a.y * b.x;\n}\n\nfn add_stats(a: StatsAccumulator, b: StatsAccumulator) -> StatsAccumulator {\n    var sum: StatsAccumulator;\n    sum.speed = a.speed + b.speed;\n    sum.neighbor_count = a.neighbor_count + b.neighbor_count;\n    sum.nearest_distance = a.nearest_distance + b.nearest_distance;\n    sum.angular_momentum = a.angular_momentum + b.angular_momentum;\n    sum.heading = a.heading + b.heading;\n    sum.position = a.position + b.position;\n    sum.velocity = a.velocity + b.velocity;\n    sum.position_sq = a.position_sq + b.position_sq;\n    return sum;\n}\n\nvar<workgroup> stats_scratch: array<StatsAccumulator, STATS_WORKGROUP_SIZE>;\n\n// Sums `value` over the whole workgroup. Must be called from uniform control flow.\nfn reduce_workgroup(local_index: u32, value: StatsAccumulator) -> StatsAccumulator {\n    stats_scratch[local_index] = value;\n    workgroupBarrier();\n    for (var stride = STATS_WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {\n        if local_index < stride {\n            stats_scratch[local_index] = add_stats(stats_scratch[local_index], stats_scratch[local_index + stride]);\n        }\n        workgroupBarrier();\n    }\n    return stats_scratch[0];\n}\n\n// First pass of the flock statistics: every workgroup sums up its share of `particles_src` into `stats_partials`\n@compute @workgroup_size(256)\nfn stats_partial_cs(\n    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,\n    @builtin(local_invocation_index) local_index: u32,\n    @builtin(workgroup_id) workgroup_id: vec3<u32>,\n) {\n    let index = global_invocation_id.x;\n\n    var value: StatsAccumulator;\n    if index < live_count() {\n        let particle = particles_src[index];\n        let info = particle_info[index];\n        let speed = length(particle.vel);\n        value.speed = speed;\n        if speed > 0. {\n            value.heading = particle.vel / speed;\n        }\n        value.neighbor_count = f32(info.neighbor_count);\n        value.nearest_distance = info.nearest_distance;\n        value.angular_momentum = cross2(particle.pos, particle.vel);\n        value.position = particle.pos;\n        value.velocity = particle.vel;\n        value.position_sq = dot(particle.pos, particle.pos);\n    }\n\n    let sum = reduce_workgroup(local_index, value);\n    if local_index == 0u {\n        stats_partials[workgroup_id.x] = sum;\n    }\n}\n\n// Second pass of the flock statistics: a single workgroup sums up `stats_partials` and writes `flock_stats`\n@compute @workgroup_size(256)\nfn stats_final_cs(@builtin(local_invocation_index) local_index: u32) {\n    let num_particles = live_count();\n    let num_partials = (num_particles + STATS_WORKGROUP_SIZE - 1u) / STATS_WORKGROUP_SIZE;\n\n    var value: StatsAccumulator;\n    for (var i = local_index; i < num_partials; i += STATS_WORKGROUP_SIZE) {\n        value = add_stats(value, stats_partials[i]);\n    }\n\n    let sum = reduce_workgroup(local_index, value);\n    if local_index == 0u {\n        let n = f32(max(num_particles, 1u));\n        let center = sum.position / n;\n        let mean_speed = sum.speed / n;\n        // sum((r - c) x v) = sum(r x v) - c x sum(v)\n        let angular_momentum = sum.angular_momentum - cross2(center, sum.velocity);\n        let rms_radius = sqrt(max(sum.position_sq / n - dot(center, center), 0.));\n\n        var stats: FlockStats;\n        stats.mean_speed = mean_speed;\n        stats.polarization = length(sum.heading) / n;\n        stats.milling = abs(angular_momentum) / max(n * rms_radius * mean_speed, 1e-6);\n        stats.mean_neighbor_count = sum.neighbor_count / n;\n        stats.mean_nearest_distance = sum.nearest_distance / n;\n        flock_stats = stats;\n    }\n}\n\n// Finds the boid nearest to `inspect_params.pick_position`\n@compute @workgroup_size(256)\nfn pick_cs(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {\n    let index = global_invocation_id.x;\n    if index >= live_count() {\n        return;\n    }\n\n    let dist = distance(particles_src[index].pos, inspect_params.pick_position);\n    let quantized_distance = u32(saturate(dist / MAX_DISTANCE) * f32(PICK_MAX_QUANTIZED_DISTANCE));\n    atomicMin(&pick_result, (quantized_distance << PICK_INDEX_BITS) | index);\n}\n\n// Gathers everything about the boid at `inspect_params.selected_index`\n@compute @workgroup_size(1)\nfn inspect_cs() {\n    let index = inspect_params.selected_index;\n    if index >= live_count() {\n        return;\n    }\n\n    let me = particles_src[index];\n    inspected = InspectedBoid(me, steering(index, me.pos, me.vel));\n}\n") ;
     device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: None,
         source: wgpu::ShaderSource::Wgsl(source),
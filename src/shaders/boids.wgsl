@@ -1,9 +1,91 @@
 const PI: f32 = 3.14159265358979323846264338327950288;
 const TAU: f32 = 6.28318530717958647692528676655900577;
 
+// Values of `SimParams.integrator`
+const INTEGRATOR_EXPLICIT_EULER: u32 = 0u;
+const INTEGRATOR_SEMI_IMPLICIT_EULER: u32 = 1u;
+const INTEGRATOR_VELOCITY_VERLET: u32 = 2u;
+const INTEGRATOR_RK2: u32 = 3u;
+
+// Longest possible distance between two boids in the [-1, 1] box
+const MAX_DISTANCE: f32 = 2.82842712474619009760337744841939615;
+
+const STATS_WORKGROUP_SIZE: u32 = 256u;
+
+// `pick_result` packs the distance to the picked position into the high bits and the particle index into the low bits,
+// so that `atomicMin` finds the nearest boid
+const PICK_INDEX_BITS: u32 = 17u;
+const PICK_MAX_QUANTIZED_DISTANCE: u32 = 32767u;
+
+// Value of `RenderParams.selected_index` when no boid is selected
+const NO_SELECTION: u32 = 0xffffffffu;
+
+// Values of `RenderParams.color_source`
+const COLOR_SOURCE_HEADING: u32 = 0u;
+const COLOR_SOURCE_SPEED: u32 = 1u;
+const COLOR_SOURCE_DENSITY: u32 = 2u;
+const COLOR_SOURCE_NEIGHBOR_COUNT: u32 = 3u;
+const COLOR_SOURCE_SPECIES: u32 = 4u;
+const COLOR_SOURCE_INDEX: u32 = 5u;
+
+// Perception radii are drawn as line lists with this many segments per circle
+const PERCEPTION_RADIUS_SEGMENTS: u32 = 24u;
+// Separation, alignment and cohesion circles with two vertices per segment
+const PERCEPTION_RADII_VERTEX_COUNT: u32 = 144u;
+
+// Velocity vectors show how far a boid travels in this time
+const VELOCITY_VECTOR_SECONDS: f32 = 0.5;
+
+// Boids shrink away during this fraction of their lifetime before they die
+const FADE_OUT_FRACTION: f32 = 0.2;
+
+// Values of `Emitter.shape`
+const EMITTER_POINT: u32 = 0u;
+const EMITTER_LINE: u32 = 1u;
+const EMITTER_AREA: u32 = 2u;
+
+const MAX_EMITTERS: u32 = 4u;
+
+// Indices into `particle_counts`
+// Boids that are alive at the start of a step, only the first this many in `particles_src` are valid
+const COUNT_LIVE: u32 = 0u;
+// Boids that survived the current step, which `compact_cs` moved to the front of `particles_dst`
+const COUNT_NEXT: u32 = 1u;
+// Number of steps that emitted boids, seeds the random numbers of the emitters
+const COUNT_STEP: u32 = 2u;
+// `MAX_EMITTERS` fractions of a boid that every emitter carries over into the next step, bitcast to u32
+const COUNT_SPAWN_CARRY: u32 = 3u;
+const NUM_COUNTS: u32 = 7u;
+
+// Workgroup size of every kernel that is dispatched with the arguments at `INDIRECT_DISPATCH`
+const INDIRECT_WORKGROUP_SIZE: u32 = 256u;
+
+const TILED_WORKGROUP_SIZE: u32 = 256u;
+// Size of the workgroup memory that `boids_tiled_cs` loads boids into
+const MAX_TILE_SIZE: u32 = 512u;
+
+// Offsets of the `dispatch_workgroups_indirect` and `draw_indirect` arguments in `indirect_args`, in u32s. The draw
+// arguments draw every live boid with the given number of vertices.
+const INDIRECT_DISPATCH: u32 = 0u;
+// Dispatches the `boids_cs_*` entry point with `boids_workgroup_size`
+const INDIRECT_DISPATCH_BOIDS: u32 = 3u;
+const INDIRECT_DRAW_TRIANGLES: u32 = 6u;
+const INDIRECT_DRAW_SPRITES: u32 = 10u;
+const INDIRECT_DRAW_VELOCITY_VECTORS: u32 = 14u;
+const INDIRECT_DRAW_PERCEPTION_RADII: u32 = 18u;
+const INDIRECT_ARGS_LEN: u32 = 22u;
+
+var<private> SEPARATION_COLOR: vec4f = vec4f(0.9, 0.31, 0.31, 0.4);
+var<private> ALIGNMENT_COLOR: vec4f = vec4f(0.31, 0.78, 0.31, 0.4);
+var<private> COHESION_COLOR: vec4f = vec4f(0.31, 0.55, 0.94, 0.4);
+
 struct Particle {
     @location(0) pos: vec2<f32>,
     @location(1) vel: vec2<f32>,
+    // Seconds since the boid was emitted
+    @location(4) age: f32,
+    // Seconds until the boid dies, or 0 if it lives forever
+    @location(5) lifetime: f32,
 };
 
 struct SimParams {
@@ -15,6 +97,109 @@ struct SimParams {
     separation_scale: f32,
     alignment_scale: f32,
     cohesion_scale: f32,
+    integrator: u32,
+    damping: f32,
+    min_speed: f32,
+    max_speed: f32,
+};
+
+// Spawns boids at a point, along the line from `start` to `end`, or in the rectangle with the corners `start` and `end`
+struct Emitter {
+    start: vec2f,
+    end: vec2f,
+    shape: u32,
+    // Boids per second
+    rate: f32,
+    lifetime: f32,
+    speed: f32,
+    // Angle of the velocity of new boids in radians, counterclockwise from the x axis
+    direction: f32,
+    // New boids fly off up to half of this angle to either side of `direction`
+    spread: f32,
+    _padding: vec2f,
+};
+
+struct EmitParams {
+    emitters: array<Emitter, MAX_EMITTERS>,
+    num_emitters: u32,
+    // If 0, `SimParams.num_particles` boids live forever and nothing is emitted. Otherwise the emitters spawn boids
+    // until there are `SimParams.num_particles` of them, and boids die once they reach their lifetime.
+    enabled: u32,
+    seed: u32,
+    _padding: u32,
+};
+
+// Per-boid values from the last simulation step that aren't needed to simulate the next one. Also used as a vertex
+// buffer, so that coloring by them doesn't need storage buffers in the vertex shader.
+struct ParticleInfo {
+    @location(2) neighbor_count: u32,
+    @location(3) nearest_distance: f32,
+};
+
+// Sums over (part of) the flock, used to reduce the flock down to `FlockStats`
+struct StatsAccumulator {
+    speed: f32,
+    neighbor_count: f32,
+    nearest_distance: f32,
+    angular_momentum: f32,
+    heading: vec2f,
+    position: vec2f,
+    velocity: vec2f,
+    position_sq: f32,
+    _padding: f32,
+};
+
+struct FlockStats {
+    mean_speed: f32,
+    // Length of the mean heading, 1 when every boid flies in the same direction
+    polarization: f32,
+    // Angular momentum around the center of the flock, normalized by its RMS radius and mean speed
+    milling: f32,
+    mean_neighbor_count: f32,
+    mean_nearest_distance: f32,
+};
+
+// The separation, alignment and cohesion fields are the individual contributions to the acceleration
+struct Steering {
+    acceleration: vec2f,
+    separation: vec2f,
+    alignment: vec2f,
+    cohesion: vec2f,
+    separation_count: u32,
+    alignment_count: u32,
+    cohesion_count: u32,
+    neighbor_count: u32,
+    nearest_distance: f32,
+    _padding: f32,
+};
+
+struct InspectParams {
+    pick_position: vec2f,
+    selected_index: u32,
+    _padding: u32,
+};
+
+struct InspectedBoid {
+    particle: Particle,
+    steering: Steering,
+};
+
+struct RenderParams {
+    camera_center: vec2f,
+    camera_zoom: f32,
+    selected_index: u32,
+    color_source: u32,
+    // The value of the color source that maps to the start and end of `color_lut`
+    color_range_min: f32,
+    color_range_max: f32,
+    num_species: u32,
+    num_particles: u32,
+    // Half of the width and height of a sprite
+    sprite_size: f32,
+    // Maps the camera's view onto the render target. It is scaled by 1 and not offset for the whole view, and narrows
+    // it down to one tile of an image that is rendered in tiles.
+    clip_scale: vec2f,
+    clip_offset: vec2f,
 };
 
 struct VertexOutput {
@@ -22,28 +207,105 @@ struct VertexOutput {
   @location(0) color: vec4f,
 }
 
+struct TexturedVertexOutput {
+  @builtin(position) position: vec4f,
+  @location(0) color: vec4f,
+  @location(1) uv: vec2f,
+}
+
 var<private> VERTEX_POSITIONS: array<vec2f, 3> = array(vec2f(-0.01, -0.02), vec2f(0.01, -0.02), vec2f(0.00, 0.02));
 
+// Two triangles covering [-1, 1]
+var<private> QUAD_CORNERS: array<vec2f, 6> = array(
+    vec2f(-1., -1.), vec2f(1., -1.), vec2f(1., 1.),
+    vec2f(-1., -1.), vec2f(1., 1.), vec2f(-1., 1.),
+);
+
+@group(0) @binding(6) var<uniform> render_params : RenderParams;
+@group(0) @binding(10) var color_lut: texture_2d<f32>;
+@group(0) @binding(11) var color_lut_sampler: sampler;
+@group(0) @binding(12) var sprite_texture: texture_2d<f32>;
+@group(0) @binding(13) var image_sampler: sampler;
+@group(0) @binding(14) var background_texture: texture_2d<f32>;
+
+fn world_to_clip(pos: vec2f) -> vec4f {
+    let view = (pos - render_params.camera_center) * render_params.camera_zoom;
+    return vec4(view * render_params.clip_scale + render_params.clip_offset, 0., 1.);
+}
+
+// Where in `color_lut` the color of a boid is looked up
+fn color_coordinate(particle: Particle, info: ParticleInfo, index: u32, angle: f32) -> f32 {
+    switch render_params.color_source {
+        case COLOR_SOURCE_HEADING: {
+            return fract(angle / TAU);
+        }
+        case COLOR_SOURCE_SPECIES: {
+            let num_species = max(render_params.num_species, 1u);
+            return (f32(index % num_species) + 0.5) / f32(num_species);
+        }
+        case COLOR_SOURCE_INDEX: {
+            return f32(index) / f32(max(render_params.num_particles, 1u));
+        }
+        default: {}
+    }
+
+    var value: f32;
+    switch render_params.color_source {
+        case COLOR_SOURCE_SPEED: {
+            value = length(particle.vel);
+        }
+        case COLOR_SOURCE_DENSITY: {
+            // Boids per unit area, estimated from the distance to the nearest neighbor
+            value = 1. / (PI * max(info.nearest_distance * info.nearest_distance, 1e-12));
+        }
+        // COLOR_SOURCE_NEIGHBOR_COUNT
+        default: {
+            value = f32(info.neighbor_count);
+        }
+    }
+    let range = render_params.color_range_max - render_params.color_range_min;
+    return saturate((value - render_params.color_range_min) / select(range, 1e-12, range == 0.));
+}
+
+// Angle that rotates something pointing up so that it points along `vel`
+fn heading_angle(vel: vec2f) -> f32 {
+    return -atan2(vel.x, vel.y);
+}
+
+fn rotate(v: vec2f, angle: f32) -> vec2f {
+    return vec2f(v.x * cos(angle) - v.y * sin(angle), v.x * sin(angle) + v.y * cos(angle));
+}
+
+fn boid_color(particle: Particle, info: ParticleInfo, index: u32, angle: f32) -> vec4f {
+    if index == render_params.selected_index {
+        return vec4f(1.);
+    }
+    let color_coordinate = color_coordinate(particle, info, index, angle);
+    return textureSampleLevel(color_lut, color_lut_sampler, vec2f(color_coordinate, 0.5), 0.);
+}
+
+// Selected boids are drawn larger, and boids shrink away at the end of their lifetime
+fn boid_scale(particle: Particle, index: u32) -> f32 {
+    var scale = select(1., 2., index == render_params.selected_index);
+    if particle.lifetime > 0. {
+        scale *= saturate((particle.lifetime - particle.age) / (FADE_OUT_FRACTION * particle.lifetime));
+    }
+    return scale;
+}
+
 @vertex
 fn boids_vs(
     particle: Particle,
+    info: ParticleInfo,
     @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
 ) -> VertexOutput {
-    let position = 0.2 * VERTEX_POSITIONS[vertex_index];
-    let angle = -atan2(particle.vel.x, particle.vel.y);
-    let pos = vec2<f32>(
-        position.x * cos(angle) - position.y * sin(angle),
-        position.x * sin(angle) + position.y * cos(angle)
-    );
+    let angle = heading_angle(particle.vel);
+    let position = 0.2 * boid_scale(particle, instance_index) * VERTEX_POSITIONS[vertex_index];
 
     var output: VertexOutput;
-    output.position = vec4(pos + particle.pos, 0., 1.);
-    output.color = vec4f(
-        saturate(2. * cos(angle)),
-        saturate(2. * cos(angle - (TAU / 3.))),
-        saturate(2. * cos(angle - (2. * TAU / 3.))),
-        1.
-    );
+    output.position = world_to_clip(rotate(position, angle) + particle.pos);
+    output.color = boid_color(particle, info, instance_index, angle);
     return output;
 }
 
@@ -52,67 +314,634 @@ fn boids_fs(@location(0) color: vec4f) -> @location(0) vec4f {
     return color;
 }
 
+// Draws a line from every boid along its velocity
+@vertex
+fn velocity_vectors_vs(
+    particle: Particle,
+    info: ParticleInfo,
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let end = particle.pos + f32(vertex_index) * VELOCITY_VECTOR_SECONDS * particle.vel;
+
+    var output: VertexOutput;
+    output.position = world_to_clip(end);
+    output.color = boid_color(particle, info, instance_index, heading_angle(particle.vel));
+    return output;
+}
+
+// Draws circles with the separation, alignment and cohesion distances around every boid
+@vertex
+fn perception_radii_vs(particle: Particle, @builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let circle = vertex_index / (2u * PERCEPTION_RADIUS_SEGMENTS);
+    let circle_vertex = vertex_index % (2u * PERCEPTION_RADIUS_SEGMENTS);
+    // segment i goes from point i to point i + 1
+    let point = (circle_vertex + 1u) / 2u;
+    let angle = TAU * f32(point) / f32(PERCEPTION_RADIUS_SEGMENTS);
+
+    var radius: f32;
+    var output: VertexOutput;
+    switch circle {
+        case 0u: {
+            radius = params.separation_distance;
+            output.color = SEPARATION_COLOR;
+        }
+        case 1u: {
+            radius = params.alignment_distance;
+            output.color = ALIGNMENT_COLOR;
+        }
+        default: {
+            radius = params.cohesion_distance;
+            output.color = COHESION_COLOR;
+        }
+    }
+    output.position = world_to_clip(particle.pos + radius * vec2f(cos(angle), sin(angle)));
+    return output;
+}
+
+// Draws boids as `sprite_texture` tinted with their color, with the top of the texture pointing along their velocity
+@vertex
+fn sprites_vs(
+    particle: Particle,
+    info: ParticleInfo,
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> TexturedVertexOutput {
+    let angle = heading_angle(particle.vel);
+    let corner = QUAD_CORNERS[vertex_index];
+    let position = render_params.sprite_size * boid_scale(particle, instance_index) * corner;
+
+    var output: TexturedVertexOutput;
+    output.position = world_to_clip(rotate(position, angle) + particle.pos);
+    output.color = boid_color(particle, info, instance_index, angle);
+    output.uv = vec2f(0.5, -0.5) * corner + 0.5;
+    return output;
+}
+
+@fragment
+fn sprites_fs(vertex: TexturedVertexOutput) -> @location(0) vec4f {
+    return vertex.color * textureSample(sprite_texture, image_sampler, vertex.uv);
+}
+
+// Stretches `background_texture` over the [-1, 1] box the boids live in
+@vertex
+fn background_vs(@builtin(vertex_index) vertex_index: u32) -> TexturedVertexOutput {
+    let corner = QUAD_CORNERS[vertex_index];
+
+    var output: TexturedVertexOutput;
+    output.position = world_to_clip(corner);
+    output.color = vec4f(1.);
+    output.uv = vec2f(0.5, -0.5) * corner + 0.5;
+    return output;
+}
+
+@fragment
+fn background_fs(vertex: TexturedVertexOutput) -> @location(0) vec4f {
+    return textureSample(background_texture, image_sampler, vertex.uv);
+}
+
 @group(0) @binding(0) var<uniform> params : SimParams;
 @group(0) @binding(1) var<storage, read> particles_src : array<Particle>;
 @group(0) @binding(2) var<storage, read_write> particles_dst : array<Particle>;
+@group(0) @binding(3) var<storage, read_write> particle_info : array<ParticleInfo>;
+@group(0) @binding(4) var<storage, read_write> stats_partials : array<StatsAccumulator>;
+@group(0) @binding(5) var<storage, read_write> flock_stats : FlockStats;
+@group(0) @binding(7) var<uniform> inspect_params : InspectParams;
+@group(0) @binding(8) var<storage, read_write> pick_result : atomic<u32>;
+@group(0) @binding(9) var<storage, read_write> inspected : InspectedBoid;
+@group(0) @binding(15) var<storage, read_write> particle_counts : array<atomic<u32>, NUM_COUNTS>;
+@group(0) @binding(16) var<storage, read_write> indirect_args : array<u32, INDIRECT_ARGS_LEN>;
+@group(0) @binding(17) var<uniform> emit_params : EmitParams;
+
+fn live_count() -> u32 {
+    return atomicLoad(&particle_counts[COUNT_LIVE]);
+}
 
+// Sums over the neighbors of a boid, that `finish_steering` turns into its `Steering`
+struct SteeringSums {
+    separation: vec2f,
+    alignment: vec2f,
+    center_of_mass: vec2f,
+    separation_count: u32,
+    alignment_count: u32,
+    cohesion_count: u32,
+    neighbor_count: u32,
+    nearest_distance: f32,
+    _padding: f32,
+};
+
+fn empty_steering_sums() -> SteeringSums {
+    var sums: SteeringSums;
+    sums.nearest_distance = MAX_DISTANCE;
+    return sums;
+}
+
+// Adds another boid to the sums of a boid at `pos`. Boids within the largest of the three distances count as
+// neighbors.
+fn add_neighbor(sums: ptr<function, SteeringSums>, pos: vec2f, other_pos: vec2f, other_vel: vec2f) {
+    let neighbor_distance = max(params.separation_distance, max(params.alignment_distance, params.cohesion_distance));
+    let dist = distance(pos, other_pos);
+
+    (*sums).nearest_distance = min((*sums).nearest_distance, dist);
+    if dist < neighbor_distance {
+        (*sums).neighbor_count += 1u;
+    }
+    if dist < params.separation_distance {
+        (*sums).separation += pos - other_pos;
+        (*sums).separation_count += 1u;
+    }
+    if dist < params.alignment_distance {
+        (*sums).alignment += other_vel;
+        (*sums).alignment_count += 1u;
+    }
+    if dist < params.cohesion_distance {
+        (*sums).center_of_mass += other_pos;
+        (*sums).cohesion_count += 1u;
+    }
+}
+
+fn finish_steering(sums: SteeringSums, pos: vec2f, vel: vec2f) -> Steering {
+    var alignment = vec2f(0.);
+    if sums.alignment_count > 0 {
+        alignment = sums.alignment / f32(sums.alignment_count);
+    }
+    var cohesion = vec2f(0.);
+    if sums.cohesion_count > 0 {
+        cohesion = (sums.center_of_mass / f32(sums.cohesion_count)) - pos;
+    }
+
+    var result: Steering;
+    result.separation = sums.separation * params.separation_scale;
+    result.alignment = alignment * params.alignment_scale;
+    result.cohesion = cohesion * params.cohesion_scale;
+    result.acceleration = result.separation + result.alignment + result.cohesion - vel * params.damping;
+    result.separation_count = sums.separation_count;
+    result.alignment_count = sums.alignment_count;
+    result.cohesion_count = sums.cohesion_count;
+    result.neighbor_count = sums.neighbor_count;
+    result.nearest_distance = sums.nearest_distance;
+    return result;
+}
+
+// How the rest of the flock in `particles_src` steers a boid at `pos` moving at `vel`.
 // https://github.com/austinEng/Project6-Vulkan-Flocking/blob/master/data/shaders/computeparticles/particle.comp
+fn steering(index: u32, pos: vec2f, vel: vec2f) -> Steering {
+    var sums = empty_steering_sums();
+    let num_particles = live_count();
+    for (var i = 0u; i < num_particles; i++) {
+        if i == index {
+            continue;
+        }
+
+        let other = particles_src[i];
+        add_neighbor(&sums, pos, other.pos, other.vel);
+    }
+    return finish_steering(sums, pos, vel);
+}
+
+// clamp velocity for a more pleasing simulation
+fn limit_speed(vel: vec2f) -> vec2f {
+    let speed = length(vel);
+    if speed == 0. {
+        return vel;
+    }
+    // `clamp` isn't defined if the minimum is above the maximum, so the maximum always wins instead
+    return vel * (min(max(speed, params.min_speed), params.max_speed) / speed);
+}
+
+// Whether the integrator evaluates the acceleration a second time, at `second_steering_state`
+fn needs_second_steering() -> bool {
+    return params.integrator == INTEGRATOR_VELOCITY_VERLET || params.integrator == INTEGRATOR_RK2;
+}
+
+// The integrators that evaluate the acceleration a second time only move this boid to its predicted state. The rest of
+// the flock stays where it was at the start of the step, so this is an approximation.
+fn second_steering_state(me: Particle, accel: vec2f) -> Particle {
+    let dt = params.delta_time;
+    var state = me;
+    if params.integrator == INTEGRATOR_RK2 {
+        // Midpoint method
+        state.pos += 0.5 * me.vel * dt;
+        state.vel += 0.5 * accel * dt;
+    } else {
+        state.pos += me.vel * dt + 0.5 * accel * dt * dt;
+        state.vel += accel * dt;
+    }
+    return state;
+}
+
+// Advances `me` by one step. `second_accel` is only used if `needs_second_steering`.
+fn integrate(me: Particle, accel: vec2f, second_accel: vec2f) -> Particle {
+    let dt = params.delta_time;
+    var new_particle = me;
+    switch params.integrator {
+        case INTEGRATOR_EXPLICIT_EULER: {
+            new_particle.pos += me.vel * dt;
+            new_particle.vel = limit_speed(me.vel + accel * dt);
+        }
+        case INTEGRATOR_VELOCITY_VERLET: {
+            new_particle.pos += me.vel * dt + 0.5 * accel * dt * dt;
+            new_particle.vel = limit_speed(me.vel + 0.5 * (accel + second_accel) * dt);
+        }
+        case INTEGRATOR_RK2: {
+            let mid_vel = me.vel + 0.5 * accel * dt;
+            new_particle.pos += mid_vel * dt;
+            new_particle.vel = limit_speed(me.vel + second_accel * dt);
+        }
+        // INTEGRATOR_SEMI_IMPLICIT_EULER
+        default: {
+            new_particle.vel = limit_speed(me.vel + accel * dt);
+            new_particle.pos += new_particle.vel * dt;
+        }
+    }
+
+    // Wrap around boundary
+    new_particle.pos = 2. * fract(0.5 + 0.5 * new_particle.pos) - 1.;
+    new_particle.age += dt;
+    return new_particle;
+}
+
+// Boids keep their index while they're simulated. With emitters, `compact_cs` drops the dead ones afterwards.
+fn write_particle(index: u32, new_particle: Particle, steering: Steering) {
+    particles_dst[index] = new_particle;
+    particle_info[index] = ParticleInfo(steering.neighbor_count, steering.nearest_distance);
+}
+
+fn is_dead(particle: Particle) -> bool {
+    return particle.lifetime > 0. && particle.age >= particle.lifetime;
+}
+
+// Workgroup size of the `boids_cs_*` entry point that is dispatched with the arguments at `INDIRECT_DISPATCH_BOIDS`.
+// Ideally it would size the workgroup of a single `boids_cs` directly, but naga can't evaluate override-expressions in
+// `@workgroup_size` yet, so there is an entry point for every size that can be picked instead.
+override boids_workgroup_size: u32 = 256u;
+
+@compute @workgroup_size(64)
+fn boids_cs_64(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    boids_cs(global_invocation_id.x);
+}
+
+@compute @workgroup_size(128)
+fn boids_cs_128(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    boids_cs(global_invocation_id.x);
+}
+
 @compute @workgroup_size(256)
-fn boids_cs(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
-    let index = global_invocation_id.x;
-    if index >= params.num_particles {
+fn boids_cs_256(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    boids_cs(global_invocation_id.x);
+}
+
+@compute @workgroup_size(512)
+fn boids_cs_512(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    boids_cs(global_invocation_id.x);
+}
+
+// Compares every boid with every other boid, reading the others straight from `particles_src`
+fn boids_cs(index: u32) {
+    if index >= live_count() {
         return;
     }
 
     let me = particles_src[index];
+    let steering = steering(index, me.pos, me.vel);
+    var second_accel = vec2f(0.);
+    if needs_second_steering() {
+        let state = second_steering_state(me, steering.acceleration);
+        second_accel = steering(index, state.pos, state.vel).acceleration;
+    }
+    write_particle(index, integrate(me, steering.acceleration, second_accel), steering);
+}
 
-    var separation_vel = vec2f(0.);
-    var alignment_vel = vec2f(0.);
-    var alignment_count = 0u;
-    var center_of_mass = vec2f(0.);
-    var cohesion_count = 0u;
+// Number of boids that `boids_tiled_cs` loads into workgroup memory at once, at most `MAX_TILE_SIZE`
+override tile_size: u32 = 256u;
 
-    for (var i = 0u; i < params.num_particles; i++) {
-        if i == index {
-            continue;
+// Position in xy and velocity in zw of the boids in the current tile
+var<workgroup> tile: array<vec4f, MAX_TILE_SIZE>;
+var<workgroup> tile_num_particles: u32;
+
+// Like `steering`, but the workgroup loads the flock into `tile` one tile at a time and every invocation reads the
+// others from there. Must be called from uniform control flow.
+fn steering_tiled(index: u32, pos: vec2f, vel: vec2f, num_particles: u32, local_index: u32) -> Steering {
+    let tile_capacity = clamp(tile_size, 1u, MAX_TILE_SIZE);
+    var sums = empty_steering_sums();
+    for (var tile_start = 0u; tile_start < num_particles; tile_start += tile_capacity) {
+        // the previous tile has to be used up before it is overwritten
+        workgroupBarrier();
+        for (var i = local_index; i < tile_capacity && tile_start + i < num_particles; i += TILED_WORKGROUP_SIZE) {
+            let other = particles_src[tile_start + i];
+            tile[i] = vec4f(other.pos, other.vel);
         }
+        workgroupBarrier();
 
-        let other = particles_src[i];
+        let tile_len = min(tile_capacity, num_particles - tile_start);
+        for (var i = 0u; i < tile_len; i++) {
+            if tile_start + i == index {
+                continue;
+            }
+            add_neighbor(&sums, pos, tile[i].xy, tile[i].zw);
+        }
+    }
+    return finish_steering(sums, pos, vel);
+}
+
+// Same as `boids_cs`, but with the flock shared through workgroup memory, see `steering_tiled`
+@compute @workgroup_size(256)
+fn boids_tiled_cs(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    if local_index == 0u {
+        tile_num_particles = live_count();
+    }
+    let num_particles = workgroupUniformLoad(&tile_num_particles);
+    let index = global_invocation_id.x;
+
+    // invocations without a boid still have to help load the tiles
+    let me = particles_src[min(index, max(num_particles, 1u) - 1u)];
+    let steering = steering_tiled(index, me.pos, me.vel, num_particles, local_index);
+    var second_accel = vec2f(0.);
+    if needs_second_steering() {
+        let state = second_steering_state(me, steering.acceleration);
+        second_accel = steering_tiled(index, state.pos, state.vel, num_particles, local_index).acceleration;
+    }
+    if index < num_particles {
+        write_particle(index, integrate(me, steering.acceleration, second_accel), steering);
+    }
+}
+
+// https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/
+fn pcg_hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Uniformly distributed in [0, 1], advances `state`
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = pcg_hash(*state);
+    return f32(*state) / 4294967295.;
+}
+
+// How many boids `emitter` spawns in this step
+fn spawn_count(emitter: u32) -> u32 {
+    let carry = bitcast<f32>(atomicLoad(&particle_counts[COUNT_SPAWN_CARRY + emitter]));
+    return u32(carry + emit_params.emitters[emitter].rate * params.delta_time);
+}
+
+const COMPACT_WORKGROUP_SIZE: u32 = 256u;
+
+// Running sums of whether the boids of the current chunk of `compact_cs` survived
+var<workgroup> compact_offsets: array<u32, COMPACT_WORKGROUP_SIZE>;
+var<workgroup> compact_num_particles: u32;
+
+// Moves the boids that survived the step to the front of `particles_dst` in the order they were in, so that indices
+// only shift past the boids that died and the emitters give the same flock every run. A single workgroup goes through
+// the flock a chunk at a time, since the survivors of a chunk can only be placed once every earlier chunk is.
+@compute @workgroup_size(256)
+fn compact_cs(@builtin(local_invocation_index) local_index: u32) {
+    if local_index == 0u {
+        compact_num_particles = live_count();
+    }
+    let num_particles = workgroupUniformLoad(&compact_num_particles);
+
+    var num_survivors = 0u;
+    for (var chunk_start = 0u; chunk_start < num_particles; chunk_start += COMPACT_WORKGROUP_SIZE) {
+        let index = chunk_start + local_index;
+        let particle = particles_dst[min(index, num_particles - 1u)];
+        let info = particle_info[min(index, num_particles - 1u)];
+        let survives = index < num_particles && !is_dead(particle);
+
+        // inclusive prefix sum, the previous chunk has to be done with the offsets first
+        workgroupBarrier();
+        compact_offsets[local_index] = u32(survives);
+        for (var stride = 1u; stride < COMPACT_WORKGROUP_SIZE; stride <<= 1u) {
+            workgroupBarrier();
+            var sum = compact_offsets[local_index];
+            if local_index >= stride {
+                sum += compact_offsets[local_index - stride];
+            }
+            workgroupBarrier();
+            compact_offsets[local_index] = sum;
+        }
+        // every boid of the chunk has to be read before any of them is moved over it
+        storageBarrier();
+        let chunk_survivors = workgroupUniformLoad(&compact_offsets[COMPACT_WORKGROUP_SIZE - 1u]);
+
+        if survives {
+            let dst_index = num_survivors + compact_offsets[local_index] - 1u;
+            particles_dst[dst_index] = particle;
+            particle_info[dst_index] = info;
+        }
+        num_survivors += chunk_survivors;
+    }
+    if local_index == 0u {
+        atomicStore(&particle_counts[COUNT_NEXT], num_survivors);
+    }
+}
+
+// Appends new boids to `particles_dst` after the survivors that `compact_cs` counted, the ones of every emitter after
+// the ones of the emitters before it. Every row of workgroups is one emitter.
+@compute @workgroup_size(64)
+fn emit_cs(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let emitter_index = global_invocation_id.y;
+    if emitter_index >= emit_params.num_emitters || global_invocation_id.x >= spawn_count(emitter_index) {
+        return;
+    }
+    var index = atomicLoad(&particle_counts[COUNT_NEXT]) + global_invocation_id.x;
+    for (var i = 0u; i < emitter_index; i++) {
+        index += spawn_count(i);
+    }
+    if index >= params.num_particles {
+        return;
+    }
 
-        if distance(me.pos, other.pos) < params.separation_distance {
-            separation_vel += me.pos - other.pos;
+    let emitter = emit_params.emitters[emitter_index];
+    var state = pcg_hash(emit_params.seed ^ pcg_hash(atomicLoad(&particle_counts[COUNT_STEP])));
+    state = pcg_hash(state ^ pcg_hash(emitter_index * 65536u + global_invocation_id.x));
+
+    var particle: Particle;
+    switch emitter.shape {
+        case EMITTER_LINE: {
+            particle.pos = mix(emitter.start, emitter.end, random(&state));
         }
-        if distance(me.pos, other.pos) < params.alignment_distance {
-            alignment_vel += other.vel;
-            alignment_count += 1u;
+        case EMITTER_AREA: {
+            let x = random(&state);
+            particle.pos = mix(emitter.start, emitter.end, vec2f(x, random(&state)));
         }
-        if distance(me.pos, other.pos) < params.cohesion_distance {
-            center_of_mass += other.pos;
-            cohesion_count += 1u;
+        // EMITTER_POINT
+        default: {
+            particle.pos = emitter.start;
         }
     }
-    if alignment_count > 0 {
-        alignment_vel /= f32(alignment_count);
+    let angle = emitter.direction + emitter.spread * (random(&state) - 0.5);
+    particle.vel = emitter.speed * vec2f(cos(angle), sin(angle));
+    particle.age = 0.;
+    particle.lifetime = emitter.lifetime;
+
+    particles_dst[index] = particle;
+    particle_info[index] = ParticleInfo(0u, MAX_DISTANCE);
+}
+
+fn write_draw_args(offset: u32, vertex_count: u32, instance_count: u32) {
+    indirect_args[offset] = vertex_count;
+    indirect_args[offset + 1u] = instance_count;
+    indirect_args[offset + 2u] = 0u;
+    indirect_args[offset + 3u] = 0u;
+}
+
+fn write_indirect_args(num_particles: u32) {
+    indirect_args[INDIRECT_DISPATCH] = (num_particles + INDIRECT_WORKGROUP_SIZE - 1u) / INDIRECT_WORKGROUP_SIZE;
+    indirect_args[INDIRECT_DISPATCH + 1u] = 1u;
+    indirect_args[INDIRECT_DISPATCH + 2u] = 1u;
+    indirect_args[INDIRECT_DISPATCH_BOIDS] = (num_particles + boids_workgroup_size - 1u) / boids_workgroup_size;
+    indirect_args[INDIRECT_DISPATCH_BOIDS + 1u] = 1u;
+    indirect_args[INDIRECT_DISPATCH_BOIDS + 2u] = 1u;
+    write_draw_args(INDIRECT_DRAW_TRIANGLES, 3u, num_particles);
+    write_draw_args(INDIRECT_DRAW_SPRITES, 6u, num_particles);
+    write_draw_args(INDIRECT_DRAW_VELOCITY_VECTORS, 2u, num_particles);
+    write_draw_args(INDIRECT_DRAW_PERCEPTION_RADII, PERCEPTION_RADII_VERTEX_COUNT, num_particles);
+}
+
+// Fills `indirect_args` for the boids that are currently alive
+@compute @workgroup_size(1)
+fn indirect_args_cs() {
+    write_indirect_args(live_count());
+}
+
+// Makes the boids that survived or were emitted during this step the live ones, and prepares the counters for the
+// next step
+@compute @workgroup_size(1)
+fn finish_step_cs() {
+    var num_particles = atomicLoad(&particle_counts[COUNT_NEXT]);
+    for (var i = 0u; i < emit_params.num_emitters; i++) {
+        let carry = bitcast<f32>(atomicLoad(&particle_counts[COUNT_SPAWN_CARRY + i]));
+        let spawned = carry + emit_params.emitters[i].rate * params.delta_time;
+        // the same as `spawn_count` of the emitter
+        num_particles += u32(spawned);
+        atomicStore(&particle_counts[COUNT_SPAWN_CARRY + i], bitcast<u32>(fract(spawned)));
     }
-    var cohesion_vel = vec2f(0.);
-    if cohesion_count > 0 {
-        cohesion_vel = (center_of_mass / f32(cohesion_count)) - me.pos;
+    num_particles = min(num_particles, params.num_particles);
+    atomicStore(&particle_counts[COUNT_LIVE], num_particles);
+    atomicStore(&particle_counts[COUNT_NEXT], 0u);
+    atomicAdd(&particle_counts[COUNT_STEP], 1u);
+    write_indirect_args(num_particles);
+}
+
+fn cross2(a: vec2f, b: vec2f) -> f32 {
+    return a.x * b.y - a.y * b.x;
+}
+
+fn add_stats(a: StatsAccumulator, b: StatsAccumulator) -> StatsAccumulator {
+    var sum: StatsAccumulator;
+    sum.speed = a.speed + b.speed;
+    sum.neighbor_count = a.neighbor_count + b.neighbor_count;
+    sum.nearest_distance = a.nearest_distance + b.nearest_distance;
+    sum.angular_momentum = a.angular_momentum + b.angular_momentum;
+    sum.heading = a.heading + b.heading;
+    sum.position = a.position + b.position;
+    sum.velocity = a.velocity + b.velocity;
+    sum.position_sq = a.position_sq + b.position_sq;
+    return sum;
+}
+
+var<workgroup> stats_scratch: array<StatsAccumulator, STATS_WORKGROUP_SIZE>;
+
+// Sums `value` over the whole workgroup. Must be called from uniform control flow.
+fn reduce_workgroup(local_index: u32, value: StatsAccumulator) -> StatsAccumulator {
+    stats_scratch[local_index] = value;
+    workgroupBarrier();
+    for (var stride = STATS_WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if local_index < stride {
+            stats_scratch[local_index] = add_stats(stats_scratch[local_index], stats_scratch[local_index + stride]);
+        }
+        workgroupBarrier();
     }
+    return stats_scratch[0];
+}
 
-    var new_particle = me;
-    new_particle.vel += separation_vel * params.separation_scale;
-    new_particle.vel += alignment_vel * params.alignment_scale;
-    new_particle.vel += cohesion_vel * params.cohesion_scale;
+// First pass of the flock statistics: every workgroup sums up its share of `particles_src` into `stats_partials`
+@compute @workgroup_size(256)
+fn stats_partial_cs(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let index = global_invocation_id.x;
+
+    var value: StatsAccumulator;
+    if index < live_count() {
+        let particle = particles_src[index];
+        let info = particle_info[index];
+        let speed = length(particle.vel);
+        value.speed = speed;
+        if speed > 0. {
+            value.heading = particle.vel / speed;
+        }
+        value.neighbor_count = f32(info.neighbor_count);
+        value.nearest_distance = info.nearest_distance;
+        value.angular_momentum = cross2(particle.pos, particle.vel);
+        value.position = particle.pos;
+        value.velocity = particle.vel;
+        value.position_sq = dot(particle.pos, particle.pos);
+    }
 
-    // clamp velocity for a more pleasing simulation
-    new_particle.vel = normalize(new_particle.vel) * clamp(length(new_particle.vel), 0.0, 0.1);
+    let sum = reduce_workgroup(local_index, value);
+    if local_index == 0u {
+        stats_partials[workgroup_id.x] = sum;
+    }
+}
 
-    // kinematic update
-    new_particle.pos += new_particle.vel * params.delta_time;
+// Second pass of the flock statistics: a single workgroup sums up `stats_partials` and writes `flock_stats`
+@compute @workgroup_size(256)
+fn stats_final_cs(@builtin(local_invocation_index) local_index: u32) {
+    let num_particles = live_count();
+    let num_partials = (num_particles + STATS_WORKGROUP_SIZE - 1u) / STATS_WORKGROUP_SIZE;
 
-    // Wrap around boundary
-    new_particle.pos = 2. * fract(0.5 + 0.5 * new_particle.pos) - 1.;
+    var value: StatsAccumulator;
+    for (var i = local_index; i < num_partials; i += STATS_WORKGROUP_SIZE) {
+        value = add_stats(value, stats_partials[i]);
+    }
 
-    // Write back
-    particles_dst[index] = new_particle;
+    let sum = reduce_workgroup(local_index, value);
+    if local_index == 0u {
+        let n = f32(max(num_particles, 1u));
+        let center = sum.position / n;
+        let mean_speed = sum.speed / n;
+        // sum((r - c) x v) = sum(r x v) - c x sum(v)
+        let angular_momentum = sum.angular_momentum - cross2(center, sum.velocity);
+        let rms_radius = sqrt(max(sum.position_sq / n - dot(center, center), 0.));
+
+        var stats: FlockStats;
+        stats.mean_speed = mean_speed;
+        stats.polarization = length(sum.heading) / n;
+        stats.milling = abs(angular_momentum) / max(n * rms_radius * mean_speed, 1e-6);
+        stats.mean_neighbor_count = sum.neighbor_count / n;
+        stats.mean_nearest_distance = sum.nearest_distance / n;
+        flock_stats = stats;
+    }
+}
+
+// Finds the boid nearest to `inspect_params.pick_position`
+@compute @workgroup_size(256)
+fn pick_cs(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let index = global_invocation_id.x;
+    if index >= live_count() {
+        return;
+    }
+
+    let dist = distance(particles_src[index].pos, inspect_params.pick_position);
+    let quantized_distance = u32(saturate(dist / MAX_DISTANCE) * f32(PICK_MAX_QUANTIZED_DISTANCE));
+    atomicMin(&pick_result, (quantized_distance << PICK_INDEX_BITS) | index);
+}
+
+// Gathers everything about the boid at `inspect_params.selected_index`
+@compute @workgroup_size(1)
+fn inspect_cs() {
+    let index = inspect_params.selected_index;
+    if index >= live_count() {
+        return;
+    }
+
+    let me = particles_src[index];
+    inspected = InspectedBoid(me, steering(index, me.pos, me.vel));
 }
@@ -0,0 +1,35 @@
+//! Simulates and draws the boids with a `wgpu::Device` and `wgpu::Queue` owned by the caller, so the flock can be
+//! embedded into any wgpu renderer without eframe. The app is just a thin layer on top of this.
+//!
+//! Every frame:
+//! 1. [`ParticleSystem::record`] records the simulation steps, statistics and picking into the caller's encoder.
+//! 2. Once that encoder was submitted, [`ParticleSystem::after_submit`] starts reading back the results and collects
+//!    the ones that finished.
+//! 3. [`ParticleSystem::draw`] draws the boids into a render pass with the color format the system was created with.
+//!
+//! [`ParticleSystem::render_image`] draws them into an image of any size instead, e.g. to save them to a file.
+
+mod autotune;
+mod cpu_simulation;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+mod particles;
+mod profiler;
+mod readback;
+mod snapshot;
+mod texture;
+
+#[cfg(target_arch = "wasm32")]
+pub use cpu_simulation::step_boids;
+#[cfg(not(target_arch = "wasm32"))]
+pub use export::ExportError;
+#[cfg(not(target_arch = "wasm32"))]
+pub use particles::ImageRequest;
+pub use particles::{
+    BoidStyle, BoidsKernel, DrawOptions, FlockSample, FrameInput, Overlay, ParticleSystem,
+    PickedBoid, BUILT_IN_SPRITE, DEFAULT_FLOCK_SEED, DEFAULT_WORKGROUP_SIZE, LUT_WIDTH,
+    MAX_PARTICLES, MAX_TILE_SIZE,
+};
+pub use profiler::{write_chrome_trace, GpuFrameTrace};
+pub use snapshot::{decode_snapshot, encode_snapshot, SnapshotError};
+pub use texture::{load_ktx2, TextureError};
@@ -0,0 +1,94 @@
+//! Finds the workgroup size that simulates the boids the fastest on this adapter, by simulating with every candidate
+//! size for a while and comparing the GPU time per step that the profiler measured.
+
+/// Frames that are simulated with a new size before its step times count, while the driver settles on the pipeline.
+const WARMUP_FRAMES: u32 = 3;
+
+/// Step times measured per candidate. The median of them is compared, so a frame that was preempted doesn't decide.
+const SAMPLES_PER_CANDIDATE: usize = 20;
+
+/// Benchmarks `candidates` one after the other.
+pub struct Autotune {
+    candidates: Vec<u32>,
+    /// Index into `candidates` of the size that is simulated with right now
+    current: usize,
+    /// Frames of the current candidate that are still recorded before its results count
+    warmup_frames_left: u32,
+    samples: Vec<f32>,
+    /// Median step time of every candidate that finished, in milliseconds
+    medians: Vec<f32>,
+}
+
+impl Autotune {
+    /// `candidates` must not be empty.
+    pub fn new(candidates: Vec<u32>) -> Self {
+        assert!(!candidates.is_empty(), "nothing to autotune");
+        Self {
+            candidates,
+            current: 0,
+            warmup_frames_left: WARMUP_FRAMES,
+            samples: Vec::new(),
+            medians: Vec::new(),
+        }
+    }
+
+    /// The workgroup size to simulate the next frame with.
+    pub fn workgroup_size(&self) -> u32 {
+        self.candidates[self.current]
+    }
+
+    /// Call once for every frame that is recorded with `workgroup_size`. Returns whether its step time counts, which
+    /// it doesn't while the candidate warms up.
+    pub fn frame_recorded(&mut self) -> bool {
+        if self.warmup_frames_left > 0 {
+            self.warmup_frames_left -= 1;
+            return false;
+        }
+        true
+    }
+
+    /// How far along the benchmark is, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        let done = self.current * SAMPLES_PER_CANDIDATE + self.samples.len();
+        done as f32 / (self.candidates.len() * SAMPLES_PER_CANDIDATE) as f32
+    }
+
+    /// Adds the step time of a frame that counted, see `frame_recorded`. Frames of a previous candidate that arrive
+    /// late are ignored.
+    ///
+    /// Returns the fastest workgroup size once every candidate was measured.
+    pub fn add_sample(&mut self, workgroup_size: u32, step_time_ms: f32) -> Option<u32> {
+        if workgroup_size != self.workgroup_size() {
+            return None;
+        }
+
+        self.samples.push(step_time_ms);
+        if self.samples.len() < SAMPLES_PER_CANDIDATE {
+            return None;
+        }
+        self.samples.sort_by(f32::total_cmp);
+        self.medians.push(self.samples[self.samples.len() / 2]);
+        self.samples.clear();
+        self.current += 1;
+        self.warmup_frames_left = WARMUP_FRAMES;
+        if self.current < self.candidates.len() {
+            return None;
+        }
+
+        // the first of equally fast sizes wins
+        self.current = self.candidates.len() - 1;
+        let (fastest, _) = self
+            .candidates
+            .iter()
+            .zip(&self.medians)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        log::info!(
+            "Autotuned the workgroup size to {fastest}, median step times: {:?} ms",
+            self.candidates
+                .iter()
+                .zip(&self.medians)
+                .collect::<Vec<_>>()
+        );
+        Some(*fastest)
+    }
+}
@@ -0,0 +1,530 @@
+//! Simulates boids on the CPU, for adapters without compute shaders like WebGL2. The results are uploaded into the
+//! same vertex buffers that the compute shaders write to, so rendering doesn't change.
+//!
+//! Everything here mirrors a compute shader in `boids.wgsl`, and has to be kept in sync with it. Steps run in parallel
+//! with rayon natively. On the web they run in `sim_worker.js`, so that they don't block the page.
+
+use emath::Vec2;
+
+use crate::{shaders::boids, sim::particles::PickedBoid};
+
+/// See `steering` in `boids.wgsl`.
+fn steering(
+    params: &boids::SimParams,
+    particles: &[boids::Particle],
+    index: usize,
+    pos: Vec2,
+    vel: Vec2,
+) -> boids::Steering {
+    let mut separation = Vec2::ZERO;
+    let mut separation_count = 0;
+    let mut alignment = Vec2::ZERO;
+    let mut alignment_count = 0;
+    let mut center_of_mass = Vec2::ZERO;
+    let mut cohesion_count = 0;
+    let mut neighbor_count = 0;
+    let mut nearest_distance = boids::MAX_DISTANCE;
+    let neighbor_distance = params
+        .separation_distance
+        .max(params.alignment_distance)
+        .max(params.cohesion_distance);
+
+    for (i, other) in particles.iter().enumerate() {
+        if i == index {
+            continue;
+        }
+
+        let other_pos = Vec2::from(other.pos);
+        let distance = (pos - other_pos).length();
+        nearest_distance = nearest_distance.min(distance);
+        if distance < neighbor_distance {
+            neighbor_count += 1;
+        }
+        if distance < params.separation_distance {
+            separation += pos - other_pos;
+            separation_count += 1;
+        }
+        if distance < params.alignment_distance {
+            alignment += Vec2::from(other.vel);
+            alignment_count += 1;
+        }
+        if distance < params.cohesion_distance {
+            center_of_mass += other_pos;
+            cohesion_count += 1;
+        }
+    }
+    if alignment_count > 0 {
+        alignment /= alignment_count as f32;
+    }
+    let cohesion = if cohesion_count > 0 {
+        center_of_mass / cohesion_count as f32 - pos
+    } else {
+        Vec2::ZERO
+    };
+
+    let separation = separation * params.separation_scale;
+    let alignment = alignment * params.alignment_scale;
+    let cohesion = cohesion * params.cohesion_scale;
+    boids::Steering {
+        acceleration: (separation + alignment + cohesion - vel * params.damping).into(),
+        separation: separation.into(),
+        alignment: alignment.into(),
+        cohesion: cohesion.into(),
+        separation_count,
+        alignment_count,
+        cohesion_count,
+        neighbor_count,
+        nearest_distance,
+        _padding: 0.,
+    }
+}
+
+/// See `limit_speed` in `boids.wgsl`.
+fn limit_speed(params: &boids::SimParams, vel: Vec2) -> Vec2 {
+    let speed = vel.length();
+    if speed == 0. {
+        return vel;
+    }
+    // `clamp` panics if the minimum is above the maximum, so the maximum wins instead, like in the shader
+    vel * (speed.max(params.min_speed).min(params.max_speed) / speed)
+}
+
+/// See `boids_cs` in `boids.wgsl`.
+fn step_particle(
+    params: &boids::SimParams,
+    particles: &[boids::Particle],
+    index: usize,
+) -> (boids::Particle, boids::ParticleInfo) {
+    let me = particles[index];
+    let pos = Vec2::from(me.pos);
+    let vel = Vec2::from(me.vel);
+    let dt = params.delta_time;
+    let me_steering = steering(params, particles, index, pos, vel);
+    let accel = Vec2::from(me_steering.acceleration);
+
+    let (new_pos, new_vel) = match params.integrator {
+        boids::INTEGRATOR_EXPLICIT_EULER => (pos + vel * dt, limit_speed(params, vel + accel * dt)),
+        boids::INTEGRATOR_VELOCITY_VERLET => {
+            let new_pos = pos + vel * dt + 0.5 * accel * dt * dt;
+            let next_accel =
+                steering(params, particles, index, new_pos, vel + accel * dt).acceleration;
+            let new_vel = limit_speed(params, vel + 0.5 * (accel + Vec2::from(next_accel)) * dt);
+            (new_pos, new_vel)
+        }
+        boids::INTEGRATOR_RK2 => {
+            let mid_pos = pos + 0.5 * vel * dt;
+            let mid_vel = vel + 0.5 * accel * dt;
+            let mid_accel = steering(params, particles, index, mid_pos, mid_vel).acceleration;
+            (
+                pos + mid_vel * dt,
+                limit_speed(params, vel + Vec2::from(mid_accel) * dt),
+            )
+        }
+        _ => {
+            let new_vel = limit_speed(params, vel + accel * dt);
+            (pos + new_vel * dt, new_vel)
+        }
+    };
+
+    // Wrap around boundary
+    let fract = |x: f32| x - x.floor();
+    let wrap = |x: f32| 2. * fract(0.5 + 0.5 * x) - 1.;
+    (
+        boids::Particle {
+            pos: [wrap(new_pos.x), wrap(new_pos.y)],
+            vel: new_vel.into(),
+            age: me.age + dt,
+            ..me
+        },
+        boids::ParticleInfo {
+            neighbor_count: me_steering.neighbor_count,
+            nearest_distance: me_steering.nearest_distance,
+        },
+    )
+}
+
+/// Advances `particles` by `num_steps` steps, using `scratch` to hold the previous step. All three slices have one
+/// element per simulated boid.
+fn simulate(
+    params: &boids::SimParams,
+    particles: &mut [boids::Particle],
+    scratch: &mut [boids::Particle],
+    info: &mut [boids::ParticleInfo],
+    num_steps: u32,
+) {
+    for _ in 0..num_steps {
+        scratch.copy_from_slice(particles);
+        let src = &*scratch;
+        let update = |(index, (particle, info)): (
+            usize,
+            (&mut boids::Particle, &mut boids::ParticleInfo),
+        )| {
+            (*particle, *info) = step_particle(params, src, index);
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            use rayon::prelude::*;
+            particles
+                .par_iter_mut()
+                .zip(info.par_iter_mut())
+                .enumerate()
+                .for_each(update);
+        }
+        #[cfg(target_arch = "wasm32")]
+        particles
+            .iter_mut()
+            .zip(info.iter_mut())
+            .enumerate()
+            .for_each(update);
+    }
+}
+
+/// Called by `sim_worker.js` to run `num_steps` steps on the particles and particle infos in place. Both are passed as
+/// bytes, since they are only copied between the worker and the main thread.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn step_boids(params: &[u8], particles: &mut [u8], info: &mut [u8], num_steps: u32) {
+    // the bytes aren't necessarily aligned, so they can't be cast in place
+    let params: boids::SimParams = bytemuck::pod_read_unaligned(params);
+    let mut new_particles: Vec<boids::Particle> = bytemuck::pod_collect_to_vec(particles);
+    let mut new_info: Vec<boids::ParticleInfo> = bytemuck::pod_collect_to_vec(info);
+    let mut scratch = new_particles.clone();
+    simulate(
+        &params,
+        &mut new_particles,
+        &mut scratch,
+        &mut new_info,
+        num_steps,
+    );
+    particles.copy_from_slice(bytemuck::cast_slice(&new_particles));
+    info.copy_from_slice(bytemuck::cast_slice(&new_info));
+}
+
+/// The worker that runs steps on the web. `JsValue`s can't be sent between threads, so it can't be part of
+/// `CpuSimulation`, which has to be `Send` to be shared with egui's renderer.
+#[cfg(target_arch = "wasm32")]
+mod worker {
+    use std::{cell::RefCell, rc::Rc};
+
+    use wasm_bindgen::{prelude::Closure, JsCast as _, JsValue};
+
+    /// The id of the job, and the particles and particle infos sent back by the worker, as bytes. The worker runs its
+    /// jobs in order, so only the latest result is kept.
+    type Results = Rc<RefCell<Option<(u32, Vec<u8>, Vec<u8>)>>>;
+
+    struct SimWorker {
+        worker: web_sys::Worker,
+        results: Results,
+        _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    }
+
+    thread_local! {
+        /// `None` if the worker couldn't be started, in which case steps run on the main thread.
+        static WORKER: Option<SimWorker> = start();
+    }
+
+    fn start() -> Option<SimWorker> {
+        let options = web_sys::WorkerOptions::new();
+        // the worker imports the same wasm module as the page
+        options.set_type(web_sys::WorkerType::Module);
+        let worker = match web_sys::Worker::new_with_options("./sim_worker.js", &options) {
+            Ok(worker) => worker,
+            Err(err) => {
+                log::warn!(
+                    "Failed to start the simulation worker, simulating on the main thread: {err:?}"
+                );
+                return None;
+            }
+        };
+
+        let results = Results::default();
+        let on_message = {
+            let results = results.clone();
+            Closure::<dyn FnMut(_)>::new(move |event: web_sys::MessageEvent| {
+                let data = event.data();
+                let field = |name: &str| {
+                    js_sys::Reflect::get(&data, &name.into())
+                        .ok()
+                        .and_then(|value| value.dyn_into::<js_sys::Uint8Array>().ok())
+                        .map(|array| array.to_vec())
+                };
+                let id = js_sys::Reflect::get(&data, &"id".into())
+                    .ok()
+                    .and_then(|id| id.as_f64());
+                if let (Some(id), Some(particles), Some(info)) =
+                    (id, field("particles"), field("info"))
+                {
+                    *results.borrow_mut() = Some((id as u32, particles, info));
+                }
+            })
+        };
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Some(SimWorker {
+            worker,
+            results,
+            _on_message: on_message,
+        })
+    }
+
+    /// Whether steps can run in the worker.
+    pub fn is_available() -> bool {
+        WORKER.with(|worker| worker.is_some())
+    }
+
+    /// Sends the particles to the worker, which sends them back after `num_steps` steps, together with `id`.
+    pub fn post(id: u32, params: &[u8], particles: &[u8], info: &[u8], num_steps: u32) {
+        WORKER.with(|worker| {
+            let Some(worker) = worker else {
+                return;
+            };
+            let particles = js_sys::Uint8Array::from(particles);
+            let info = js_sys::Uint8Array::from(info);
+            let message = js_sys::Object::new();
+            for (name, value) in [
+                ("id", JsValue::from(id)),
+                ("params", JsValue::from(js_sys::Uint8Array::from(params))),
+                ("particles", particles.clone().into()),
+                ("info", info.clone().into()),
+                ("numSteps", num_steps.into()),
+            ] {
+                _ = js_sys::Reflect::set(&message, &name.into(), &value);
+            }
+            let transfer = js_sys::Array::of2(&particles.buffer(), &info.buffer());
+            if let Err(err) = worker
+                .worker
+                .post_message_with_transfer(&message, &transfer)
+            {
+                log::error!("Failed to send particles to the simulation worker: {err:?}");
+            }
+        });
+    }
+
+    /// The id of the job, and the particles and particle infos the worker sent back for it since the last call.
+    pub fn try_recv() -> Option<(u32, Vec<u8>, Vec<u8>)> {
+        WORKER.with(|worker| worker.as_ref()?.results.borrow_mut().take())
+    }
+}
+
+/// Steps that were sent to the worker and haven't come back yet.
+#[cfg(target_arch = "wasm32")]
+struct Job {
+    /// Sent back by the worker together with the results, so that the results of other jobs aren't taken for these
+    id: u32,
+    num_particles: usize,
+    num_steps: u32,
+    delta_time: f32,
+    start: web_time::Instant,
+}
+
+/// Steps that finished simulating, see [`CpuSimulation::advance`].
+#[derive(Clone, Copy, Debug)]
+pub struct FinishedSteps {
+    pub num_steps: u32,
+    /// Simulation time the steps advanced by
+    pub sim_time: f64,
+    /// How long a single step took in milliseconds
+    pub step_time_ms: f32,
+}
+
+pub struct CpuSimulation {
+    particles: Vec<boids::Particle>,
+    scratch: Vec<boids::Particle>,
+    info: Vec<boids::ParticleInfo>,
+    /// Whether the particles changed since they were last uploaded
+    changed: bool,
+    /// Steps that are waiting for the worker to finish its current job, at most the `max_steps` passed to `advance`
+    #[cfg(target_arch = "wasm32")]
+    pending_steps: u32,
+    /// The job whose results are waited for. Results of other jobs, like the ones started before `set_particles`,
+    /// are dropped.
+    #[cfg(target_arch = "wasm32")]
+    job: Option<Job>,
+    #[cfg(target_arch = "wasm32")]
+    next_job_id: u32,
+}
+
+impl CpuSimulation {
+    pub fn new(particles: Vec<boids::Particle>) -> Self {
+        Self {
+            scratch: particles.clone(),
+            info: vec![bytemuck::Zeroable::zeroed(); particles.len()],
+            particles,
+            changed: true,
+            #[cfg(target_arch = "wasm32")]
+            pending_steps: 0,
+            #[cfg(target_arch = "wasm32")]
+            job: None,
+            #[cfg(target_arch = "wasm32")]
+            next_job_id: 0,
+        }
+    }
+
+    pub fn particles(&self) -> &[boids::Particle] {
+        &self.particles
+    }
+
+    /// Replaces the first particles with `particles`. Steps that weren't simulated yet start from them.
+    pub fn set_particles(&mut self, particles: &[boids::Particle]) {
+        let num_particles = particles.len().min(self.particles.len());
+        self.particles[..num_particles].copy_from_slice(&particles[..num_particles]);
+        self.changed = true;
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.job = None;
+        }
+    }
+
+    /// Advances the first `params.num_particles` particles by `num_steps` steps, and returns the steps that finished.
+    /// On the web they finish in a later frame, and until then at most `max_steps` steps queue up. The ones beyond that
+    /// are dropped, just like the scheduler drops steps that don't fit into a frame.
+    pub fn advance(
+        &mut self,
+        params: &boids::SimParams,
+        num_steps: u32,
+        #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))] max_steps: u32,
+    ) -> Option<FinishedSteps> {
+        let num_particles = (params.num_particles as usize).min(self.particles.len());
+
+        #[cfg(target_arch = "wasm32")]
+        if worker::is_available() {
+            let mut finished = None;
+            if let Some((id, particles, info)) = worker::try_recv() {
+                if let Some(job) = self.job.take_if(|job| job.id == id) {
+                    let particles: Vec<boids::Particle> = bytemuck::pod_collect_to_vec(&particles);
+                    let info: Vec<boids::ParticleInfo> = bytemuck::pod_collect_to_vec(&info);
+                    self.particles[..job.num_particles].copy_from_slice(&particles);
+                    self.info[..job.num_particles].copy_from_slice(&info);
+                    self.changed = true;
+                    finished = Some(FinishedSteps {
+                        num_steps: job.num_steps,
+                        sim_time: f64::from(job.delta_time) * f64::from(job.num_steps),
+                        step_time_ms: job.start.elapsed().as_secs_f32() * 1e3
+                            / job.num_steps as f32,
+                    });
+                }
+            }
+
+            // otherwise a worker that can't keep up falls further behind with every frame
+            self.pending_steps = (self.pending_steps + num_steps).min(max_steps);
+            if self.job.is_none() && self.pending_steps > 0 {
+                let id = self.next_job_id;
+                self.next_job_id = self.next_job_id.wrapping_add(1);
+                worker::post(
+                    id,
+                    bytemuck::bytes_of(params),
+                    bytemuck::cast_slice(&self.particles[..num_particles]),
+                    bytemuck::cast_slice(&self.info[..num_particles]),
+                    self.pending_steps,
+                );
+                self.job = Some(Job {
+                    id,
+                    num_particles,
+                    num_steps: std::mem::take(&mut self.pending_steps),
+                    delta_time: params.delta_time,
+                    start: web_time::Instant::now(),
+                });
+            }
+            return finished;
+        }
+
+        if num_steps == 0 {
+            return None;
+        }
+        let start = web_time::Instant::now();
+        simulate(
+            params,
+            &mut self.particles[..num_particles],
+            &mut self.scratch[..num_particles],
+            &mut self.info[..num_particles],
+            num_steps,
+        );
+        self.changed = true;
+        Some(FinishedSteps {
+            num_steps,
+            sim_time: f64::from(params.delta_time) * f64::from(num_steps),
+            step_time_ms: start.elapsed().as_secs_f32() * 1e3 / num_steps as f32,
+        })
+    }
+
+    /// The particles and their infos, if they changed since the last call.
+    pub fn take_changed(&mut self) -> Option<(&[boids::Particle], &[boids::ParticleInfo])> {
+        std::mem::take(&mut self.changed).then_some((&self.particles, &self.info))
+    }
+
+    /// See `stats_partial_cs` and `stats_final_cs` in `boids.wgsl`.
+    pub fn flock_stats(&self, num_particles: u32) -> boids::FlockStats {
+        let num_particles = (num_particles as usize).min(self.particles.len());
+        let mut speed = 0.;
+        let mut neighbor_count = 0.;
+        let mut nearest_distance = 0.;
+        let mut angular_momentum = 0.;
+        let mut heading = Vec2::ZERO;
+        let mut position = Vec2::ZERO;
+        let mut velocity = Vec2::ZERO;
+        let mut position_sq = 0.;
+        for (particle, info) in self.particles[..num_particles]
+            .iter()
+            .zip(&self.info[..num_particles])
+        {
+            let pos = Vec2::from(particle.pos);
+            let vel = Vec2::from(particle.vel);
+            let particle_speed = vel.length();
+            speed += particle_speed;
+            if particle_speed > 0. {
+                heading += vel / particle_speed;
+            }
+            neighbor_count += info.neighbor_count as f32;
+            nearest_distance += info.nearest_distance;
+            angular_momentum += pos.x * vel.y - pos.y * vel.x;
+            position += pos;
+            velocity += vel;
+            position_sq += pos.length_sq();
+        }
+
+        let n = num_particles.max(1) as f32;
+        let center = position / n;
+        let mean_speed = speed / n;
+        let angular_momentum = angular_momentum - (center.x * velocity.y - center.y * velocity.x);
+        let rms_radius = (position_sq / n - center.length_sq()).max(0.).sqrt();
+        boids::FlockStats {
+            mean_speed,
+            polarization: heading.length() / n,
+            milling: angular_momentum.abs() / (n * rms_radius * mean_speed).max(1e-6),
+            mean_neighbor_count: neighbor_count / n,
+            mean_nearest_distance: nearest_distance / n,
+        }
+    }
+
+    /// The boid nearest to `position`, see `pick_cs` in `boids.wgsl`.
+    pub fn pick(&self, position: [f32; 2], num_particles: u32) -> Option<PickedBoid> {
+        let position = Vec2::from(position);
+        self.particles
+            .iter()
+            .take(num_particles as usize)
+            .enumerate()
+            .map(|(index, particle)| PickedBoid {
+                index: index as u32,
+                distance: (Vec2::from(particle.pos) - position).length(),
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Everything about the boid at `index`, see `inspect_cs` in `boids.wgsl`.
+    pub fn inspect(&self, params: &boids::SimParams, index: u32) -> Option<boids::InspectedBoid> {
+        let num_particles = (params.num_particles as usize).min(self.particles.len());
+        let particles = &self.particles[..num_particles];
+        let particle = *particles.get(index as usize)?;
+        Some(boids::InspectedBoid {
+            particle,
+            steering: steering(
+                params,
+                particles,
+                index as usize,
+                particle.pos.into(),
+                particle.vel.into(),
+            ),
+        })
+    }
+}
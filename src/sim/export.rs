@@ -0,0 +1,232 @@
+//! Renders images that can be larger than the screen, and larger than a texture can be, by rendering them in tiles.
+//!
+//! The tiles are read back one row at a time, so only a row of them is ever in memory. Unlike everything else that
+//! is read back, this waits for the GPU, so it is only meant for exporting images on request. That isn't possible on
+//! the web, where nothing can wait for the GPU.
+
+use std::{fmt, sync::mpsc};
+
+/// Tiles are at most this large, even if the device supports larger textures, since every tile is also copied into a
+/// staging buffer.
+const MAX_EXPORT_TILE_SIZE: u32 = 4096;
+
+const BYTES_PER_PIXEL: usize = 4;
+
+#[derive(Debug)]
+pub enum ExportError {
+    /// The image has no pixels
+    Empty,
+    /// Only 8 bit RGBA and BGRA targets can be read back
+    UnsupportedFormat(wgpu::TextureFormat),
+    /// A tile couldn't be read back
+    Readback(wgpu::BufferAsyncError),
+    /// Passing on the pixels failed
+    Write(std::io::Error),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the image has no pixels"),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "images can't be read back from {format:?} targets")
+            }
+            Self::Readback(err) => write!(f, "failed to read back a tile: {err}"),
+            Self::Write(err) => write!(f, "failed to write the image: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// How the camera's view is mapped onto a tile, see `clip_scale` and `clip_offset` in `boids::RenderParams`.
+#[derive(Clone, Copy, Debug)]
+pub struct TileTransform {
+    pub clip_scale: [f32; 2],
+    pub clip_offset: [f32; 2],
+}
+
+impl TileTransform {
+    /// The transform of the tile at `origin` with `size` pixels in an image of `image_size` pixels. The camera's view
+    /// covers the shorter side of the image, and shows more of the world along the longer one.
+    fn new(image_size: [u32; 2], origin: [u32; 2], size: [u32; 2]) -> Self {
+        let [width, height] = image_size.map(f64::from);
+        let [x, y] = origin.map(f64::from);
+        let [tile_width, tile_height] = size.map(f64::from);
+        let view_size = width.min(height);
+        Self {
+            clip_scale: [
+                (view_size / tile_width) as f32,
+                (view_size / tile_height) as f32,
+            ],
+            // clip space points up, while the image's rows go down
+            clip_offset: [
+                ((width - 2. * x - tile_width) / tile_width) as f32,
+                ((2. * y + tile_height - height) / tile_height) as f32,
+            ],
+        }
+    }
+}
+
+/// Renders an image of `width` by `height` pixels into targets of `format` in tiles, which `draw_tile` draws into with
+/// the transform of that tile. Every row of tiles is passed on to `write_rows` from the top, as the rows of pixels it
+/// covers in 8 bit RGBA. The image is cleared with `background`, which is an sRGB color with linear alpha.
+pub fn render_tiled(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    [width, height]: [u32; 2],
+    background: [u8; 4],
+    mut draw_tile: impl FnMut(TileTransform, &mut wgpu::RenderPass<'_>),
+    mut write_rows: impl FnMut(&[u8]) -> std::io::Result<()>,
+) -> Result<(), ExportError> {
+    if width == 0 || height == 0 {
+        return Err(ExportError::Empty);
+    }
+    let swap_red_blue = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        _ => return Err(ExportError::UnsupportedFormat(format)),
+    };
+
+    let max_tile_size = device
+        .limits()
+        .max_texture_dimension_2d
+        .min(MAX_EXPORT_TILE_SIZE);
+    let tile_size = wgpu::Extent3d {
+        width: width.min(max_tile_size),
+        height: height.min(max_tile_size),
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Export Tile"),
+        size: tile_size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&Default::default());
+    let padded_bytes_per_row = (tile_size.width * BYTES_PER_PIXEL as u32)
+        .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Export Tile Readback"),
+        size: u64::from(padded_bytes_per_row) * u64::from(tile_size.height),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let clear_color = {
+        let [r, g, b, a] = background.map(|channel| f64::from(channel) / 255.);
+        // sRGB targets expect linear colors, while the others store them as they are
+        let to_target = |channel: f64| {
+            if format.is_srgb() {
+                srgb_to_linear(channel)
+            } else {
+                channel
+            }
+        };
+        wgpu::Color {
+            r: to_target(r),
+            g: to_target(g),
+            b: to_target(b),
+            a,
+        }
+    };
+
+    // images can be far wider than a tile, so their rows are only measured in `usize`
+    let row_bytes = width as usize * BYTES_PER_PIXEL;
+    let mut rows = Vec::new();
+    for y in (0..height).step_by(tile_size.height as usize) {
+        let tile_height = tile_size.height.min(height - y);
+        rows.clear();
+        rows.resize(row_bytes * tile_height as usize, 0);
+        for x in (0..width).step_by(tile_size.width as usize) {
+            let tile_width = tile_size.width.min(width - x);
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Export Tile"),
+            });
+            {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Export Tile"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(clear_color),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                // tiles at the right and bottom edges only cover part of the texture
+                rpass.set_viewport(0., 0., tile_width as f32, tile_height as f32, 0., 1.);
+                draw_tile(
+                    TileTransform::new([width, height], [x, y], [tile_width, tile_height]),
+                    &mut rpass,
+                );
+            }
+            encoder.copy_texture_to_buffer(
+                texture.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &staging_buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: tile_width,
+                    height: tile_height,
+                    depth_or_array_layers: 1,
+                },
+            );
+            queue.submit([encoder.finish()]);
+
+            let (sender, receiver) = mpsc::channel();
+            staging_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    _ = sender.send(result);
+                });
+            device.poll(wgpu::Maintain::Wait);
+            receiver
+                .recv()
+                .expect("waiting on the device should have mapped the buffer")
+                .map_err(ExportError::Readback)?;
+            {
+                let mapped = staging_buffer.slice(..).get_mapped_range();
+                let tile_row_bytes = tile_width as usize * BYTES_PER_PIXEL;
+                let start = x as usize * BYTES_PER_PIXEL;
+                for (row, tile_row) in rows
+                    .chunks_exact_mut(row_bytes)
+                    .zip(mapped.chunks(padded_bytes_per_row as usize))
+                {
+                    let row = &mut row[start..start + tile_row_bytes];
+                    row.copy_from_slice(&tile_row[..tile_row_bytes]);
+                    if swap_red_blue {
+                        for pixel in row.chunks_exact_mut(BYTES_PER_PIXEL) {
+                            pixel.swap(0, 2);
+                        }
+                    }
+                }
+            }
+            staging_buffer.unmap();
+        }
+        write_rows(&rows).map_err(ExportError::Write)?;
+    }
+    Ok(())
+}
+
+fn srgb_to_linear(channel: f64) -> f64 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
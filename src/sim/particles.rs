@@ -0,0 +1,2069 @@
+// Flocking boids example with gpu compute update pass
+// adapted from https://github.com/austinEng/webgpu-samples/blob/master/src/examples/computeBoids.ts
+
+use std::{collections::VecDeque, sync::Arc};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::sim::export::{self, ExportError};
+use crate::{
+    shaders::*,
+    sim::{
+        autotune::Autotune,
+        cpu_simulation::CpuSimulation,
+        profiler::{self, GpuFrameTrace},
+        readback::ReadbackQueue,
+        texture,
+    },
+    PUFFIN_GPU_PROFILER,
+};
+use boids::SimParams;
+use nanorand::{Rng, WyRand};
+use puffin::current_function_name;
+use wgpu::util::DeviceExt;
+use wgpu_profiler::{GpuProfiler, GpuProfilerSettings, GpuTimerQueryResult};
+
+pub const MAX_PARTICLES: usize = 100_000;
+
+/// Width of the lookup texture that boids are colored with, see [`ParticleSystem::set_color_lut`].
+pub const LUT_WIDTH: u32 = 256;
+
+/// Default sprite that boids can be drawn with.
+pub const BUILT_IN_SPRITE: &[u8] = include_bytes!("../../assets/rgba8.ktx2");
+
+/// How much each new measurement contributes to the smoothed GPU time per step.
+const STEP_TIME_SMOOTHING: f32 = 0.1;
+
+/// How many readbacks of each kind may be in flight before new ones are skipped.
+const MAX_READBACKS_IN_FLIGHT: usize = 4;
+
+/// How many traced GPU frames are kept until they are taken, dropping the oldest ones first.
+const MAX_GPU_FRAME_TRACES: usize = 1000;
+
+const _: () = assert!(
+    MAX_PARTICLES <= 1 << boids::PICK_INDEX_BITS,
+    "particle indices don't fit into the pick result"
+);
+const _: () = assert!(
+    boids::BOIDS_TILED_CS_WORKGROUP_SIZE[0] == boids::INDIRECT_WORKGROUP_SIZE
+        && boids::STATS_PARTIAL_CS_WORKGROUP_SIZE[0] == boids::INDIRECT_WORKGROUP_SIZE
+        && boids::PICK_CS_WORKGROUP_SIZE[0] == boids::INDIRECT_WORKGROUP_SIZE,
+    "kernels that are dispatched indirectly don't match the dispatch arguments"
+);
+
+/// Workgroup sizes that [`BoidsKernel::AllPairs`] can simulate with, together with the entry point for each of them.
+/// See `boids_workgroup_size` in `boids.wgsl`.
+const BOIDS_CS_ENTRIES: [(u32, &str); 4] = [
+    (
+        boids::BOIDS_CS_64_WORKGROUP_SIZE[0],
+        boids::ENTRY_BOIDS_CS_64,
+    ),
+    (
+        boids::BOIDS_CS_128_WORKGROUP_SIZE[0],
+        boids::ENTRY_BOIDS_CS_128,
+    ),
+    (
+        boids::BOIDS_CS_256_WORKGROUP_SIZE[0],
+        boids::ENTRY_BOIDS_CS_256,
+    ),
+    (
+        boids::BOIDS_CS_512_WORKGROUP_SIZE[0],
+        boids::ENTRY_BOIDS_CS_512,
+    ),
+];
+
+/// Workgroup size of [`BoidsKernel::AllPairs`] until another one is picked.
+pub const DEFAULT_WORKGROUP_SIZE: u32 = boids::BOIDS_CS_256_WORKGROUP_SIZE[0];
+
+/// Byte offset of the `u32` at `index` in the counts or indirect argument buffer, see `boids::COUNT_LIVE` and
+/// `boids::INDIRECT_DISPATCH`.
+const fn u32_offset(index: u32) -> wgpu::BufferAddress {
+    index as wgpu::BufferAddress * size_of::<u32>() as wgpu::BufferAddress
+}
+
+/// Seed of the flock that the simulation starts with, until another one is set with [`ParticleSystem::reset_flock`].
+pub const DEFAULT_FLOCK_SEED: u32 = 42;
+
+/// The fixed flock that `seed` places at random. Boids that are spawned at the start live forever.
+fn initial_particles(seed: u32) -> Vec<boids::Particle> {
+    // TODO: do this on the GPU
+    let mut rng = WyRand::new_seed(seed.into());
+    let mut unif = || rng.generate::<f32>() * 2f32 - 1f32; // Generate a num (-1, 1)
+    (0..MAX_PARTICLES)
+        .map(|_| boids::Particle {
+            pos: [unif(), unif()],
+            vel: [unif(), unif()],
+            age: 0.,
+            lifetime: 0.,
+        })
+        .collect()
+}
+
+/// The boid nearest to a picked position.
+#[derive(Clone, Copy, Debug)]
+pub struct PickedBoid {
+    pub index: u32,
+    /// Distance from the picked position, with a precision of about 1e-4
+    pub distance: f32,
+}
+
+impl PickedBoid {
+    fn unpack(pick_result: u32) -> Option<Self> {
+        // the result is only left at its initial value if there weren't any particles to pick from
+        (pick_result != u32::MAX).then(|| Self {
+            index: pick_result & ((1 << boids::PICK_INDEX_BITS) - 1),
+            distance: (pick_result >> boids::PICK_INDEX_BITS) as f32
+                / boids::PICK_MAX_QUANTIZED_DISTANCE as f32
+                * boids::MAX_DISTANCE,
+        })
+    }
+}
+
+/// Flock statistics after a specific simulation step.
+#[derive(Clone, Copy, Debug)]
+pub struct FlockSample {
+    pub step: u64,
+    pub sim_time: f64,
+    pub stats: boids::FlockStats,
+}
+
+/// Which compute shader simulates the boids. Both compare every boid with every other boid, and only differ in how they
+/// read the others.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BoidsKernel {
+    /// Reads the other boids straight from the particle buffer
+    #[default]
+    AllPairs,
+    /// Loads the flock into workgroup memory `tile_size` boids at a time, at most `MAX_TILE_SIZE` of them
+    Tiled { tile_size: u32 },
+}
+
+impl BoidsKernel {
+    /// Label of the profiler scope around the simulation compute pass, so the kernels can be told apart.
+    fn scope_label(self) -> &'static str {
+        match self {
+            Self::AllPairs => "simulate",
+            Self::Tiled { .. } => "simulate tiled",
+        }
+    }
+}
+
+/// Largest tile size of [`BoidsKernel::Tiled`].
+pub const MAX_TILE_SIZE: u32 = boids::MAX_TILE_SIZE;
+
+/// Everything that [`ParticleSystem::record`] needs for a frame.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameInput {
+    pub sim_params: SimParams,
+    pub num_steps: u32,
+    /// Most steps that may be simulated in a frame. Only used to limit the steps that queue up when simulating on the
+    /// CPU on the web, where they run in the background.
+    pub max_steps: u32,
+    /// Compute the flock statistics after simulating, see [`ParticleSystem::take_stats_samples`]
+    pub compute_stats: bool,
+    pub render_params: boids::RenderParams,
+    /// Find the boid nearest to this position after simulating, see [`ParticleSystem::take_pick_results`]
+    pub pick_position: Option<[f32; 2]>,
+    /// Read back everything about this boid after simulating, see [`ParticleSystem::inspected`]
+    pub inspect_index: Option<u32>,
+    /// Spawn boids with these emitters instead of simulating a fixed flock, up to `sim_params.num_particles` of them.
+    /// `enabled` is set automatically. Emitters need compute shaders, so they are ignored when simulating on the CPU.
+    pub emitters: Option<boids::EmitParams>,
+    /// Simulate with this kernel. Ignored when simulating on the CPU.
+    pub kernel: BoidsKernel,
+    /// Workgroup size of [`BoidsKernel::AllPairs`]. Rounded down to one of [`ParticleSystem::workgroup_sizes`], and
+    /// ignored while autotuning.
+    pub workgroup_size: u32,
+}
+
+/// What the boids are drawn as.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BoidStyle {
+    #[default]
+    Triangles,
+    /// Outlines of the triangles, or filled triangles if the device doesn't support `POLYGON_MODE_LINE`
+    Wireframe,
+    /// Textured quads, see [`ParticleSystem::set_sprite_texture`]
+    Sprites,
+}
+
+/// Debug lines drawn on top of the boids.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Overlay {
+    #[default]
+    None,
+    VelocityVectors,
+    /// Separation, alignment and cohesion distances around every boid
+    PerceptionRadii,
+}
+
+/// How [`ParticleSystem::draw`] draws the boids.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DrawOptions {
+    pub style: BoidStyle,
+    /// Draw the background texture behind the boids, if one was set
+    pub background: bool,
+    pub overlay: Overlay,
+}
+
+/// An image of the boids without anything around them, see [`ParticleSystem::render_image`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug)]
+pub struct ImageRequest {
+    pub width: u32,
+    pub height: u32,
+    /// The camera's view covers the shorter side of the image, regardless of `clip_scale` and `clip_offset`
+    pub render_params: boids::RenderParams,
+    pub draw_options: DrawOptions,
+    /// sRGB color with linear alpha that is behind the boids
+    pub background: [u8; 4],
+}
+
+/// Persistent WGPU data for particle rendering and simulation
+pub struct ParticleSystem {
+    sim_param_buffer: wgpu::Buffer,
+    particle_buffers: Vec<wgpu::Buffer>,
+    particle_info_buffer: wgpu::Buffer,
+    simulation: Simulation,
+    render_param_buffer: wgpu::Buffer,
+    /// What the render pipelines draw into
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    color_format: wgpu::TextureFormat,
+    color_lut_texture: wgpu::Texture,
+    color_lut_view: wgpu::TextureView,
+    color_lut_sampler: wgpu::Sampler,
+    image_sampler: wgpu::Sampler,
+    render_bind_group: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+    /// Only available if the device supports `POLYGON_MODE_LINE`
+    wireframe_pipeline: Option<wgpu::RenderPipeline>,
+    debug_bind_group: wgpu::BindGroup,
+    velocity_vectors_pipeline: wgpu::RenderPipeline,
+    perception_radii_pipeline: wgpu::RenderPipeline,
+    sprite_bind_group_layout: wgpu::BindGroupLayout,
+    sprite_bind_group: wgpu::BindGroup,
+    sprite_pipeline: wgpu::RenderPipeline,
+    background_bind_group_layout: wgpu::BindGroupLayout,
+    /// Only set once a background image was loaded
+    background_bind_group: Option<wgpu::BindGroup>,
+    background_pipeline: wgpu::RenderPipeline,
+    stats_samples: Vec<FlockSample>,
+    pick_results: Vec<Option<PickedBoid>>,
+    /// A pick that couldn't be read back yet because every readback buffer was in flight, which is picked again
+    pending_pick: Option<[f32; 2]>,
+    inspected: Option<(u32, boids::InspectedBoid)>,
+    frame_num: usize,
+    sim_time: f64,
+    /// Simulation time at the last [`ParticleSystem::take_finished_sim_time`].
+    reported_sim_time: f64,
+    profiler: GpuProfiler,
+    /// Every frame whose profiler results haven't been processed yet.
+    pending_frames: VecDeque<PendingFrame>,
+    gpu_time_per_step_ms: Option<f32>,
+    /// The kernel that `gpu_time_per_step_ms` was measured with
+    kernel: BoidsKernel,
+    /// Only set while autotuning, see [`ParticleSystem::start_autotune`]
+    autotune: Option<Autotune>,
+    /// Until it is taken by [`ParticleSystem::take_autotuned_workgroup_size`]
+    autotuned_workgroup_size: Option<u32>,
+    /// Only collected while [`ParticleSystem::set_trace_gpu_frames`] is on
+    gpu_frame_traces: VecDeque<GpuFrameTrace>,
+    trace_gpu_frames: bool,
+    /// How many boids were simulated by the last `record`, and are drawn. Only used when simulating on the CPU, since
+    /// the GPU keeps track of the live boids itself.
+    num_particles: u32,
+    /// The fixed flock starts over with this seed when emitters are turned off
+    flock_seed: u32,
+    /// Written into the particle buffers once the fixed flock is simulated, see [`ParticleSystem::load_particles`]
+    pending_particles: Option<Vec<boids::Particle>>,
+    /// Whether the next `record` reads back the flock, see [`ParticleSystem::request_snapshot`]
+    snapshot_requested: bool,
+    /// Until it is taken by [`ParticleSystem::take_snapshot`]
+    snapshot: Option<Vec<boids::Particle>>,
+}
+
+/// A frame that was recorded, but whose profiler results haven't arrived yet.
+struct PendingFrame {
+    num_steps: u32,
+    /// `puffin::now_ns` when the frame was recorded
+    recorded_ns: puffin::NanoSecond,
+    /// The workgroup size that was autotuned in this frame, if its step time counts
+    autotune_sample: Option<u32>,
+}
+
+/// Where the boids are simulated.
+enum Simulation {
+    Gpu(Box<GpuSimulation>),
+    /// For devices that can't run compute shaders
+    Cpu(CpuSimulation),
+}
+
+/// Compute pipelines that simulate, reduce and inspect the boids, and their buffers.
+struct GpuSimulation {
+    shader: wgpu::ShaderModule,
+    /// Also used by the pipelines that are created after `init`
+    pipeline_cache: Option<Arc<wgpu::PipelineCache>>,
+    particle_bind_groups: Vec<wgpu::BindGroup>,
+    compute_pipeline_layout: wgpu::PipelineLayout,
+    counts_pipeline_layout: wgpu::PipelineLayout,
+    /// Sizes in `BOIDS_CS_ENTRIES` that the device can run
+    workgroup_sizes: Vec<u32>,
+    sized_pipelines: SizedPipelines,
+    /// Created once it is used, together with the tile size it was created with.
+    tiled_pipeline: Option<(u32, wgpu::ComputePipeline)>,
+    emit_param_buffer: wgpu::Buffer,
+    /// Drops the boids that died during a step when emitting, keeping the order of the others.
+    compact_pipeline: wgpu::ComputePipeline,
+    emit_pipeline: wgpu::ComputePipeline,
+    /// Number of live boids and the other `boids::COUNT_*` counters, which never leave the GPU.
+    counts_buffer: wgpu::Buffer,
+    /// Dispatch and draw arguments for the live boids, written by `indirect_args_cs` and `finish_step_cs`.
+    indirect_buffer: wgpu::Buffer,
+    /// Binds the indirect arguments as storage, so it can't be bound while they are used.
+    counts_bind_group: wgpu::BindGroup,
+    /// Whether the last frame was simulated with emitters.
+    emitting: bool,
+    stats_bind_groups: Vec<wgpu::BindGroup>,
+    stats_partial_pipeline: wgpu::ComputePipeline,
+    stats_final_pipeline: wgpu::ComputePipeline,
+    flock_stats_buffer: wgpu::Buffer,
+    /// Tagged with the step and simulation time the statistics were computed at.
+    stats_readback: ReadbackQueue<(u64, f64)>,
+    inspect_param_buffer: wgpu::Buffer,
+    pick_result_buffer: wgpu::Buffer,
+    inspected_buffer: wgpu::Buffer,
+    inspect_bind_groups: Vec<wgpu::BindGroup>,
+    pick_pipeline: wgpu::ComputePipeline,
+    inspect_pipeline: wgpu::ComputePipeline,
+    pick_readback: ReadbackQueue<()>,
+    /// Tagged with the index of the inspected boid.
+    inspect_readback: ReadbackQueue<u32>,
+    /// Tagged with the number of boids in the snapshot.
+    snapshot_readback: ReadbackQueue<u32>,
+}
+
+/// The pipelines that depend on the workgroup size of `boids_cs`, since they either run it or write its dispatch
+/// arguments.
+struct SizedPipelines {
+    workgroup_size: u32,
+    boids: wgpu::ComputePipeline,
+    indirect_args: wgpu::ComputePipeline,
+    finish_step: wgpu::ComputePipeline,
+}
+
+impl GpuSimulation {
+    /// Whether `device` can run the compute shaders. WebGL2 can't, and neither can any device created with its limits.
+    fn is_supported(device: &wgpu::Device) -> bool {
+        let limits = device.limits();
+        // the flock statistics bind the most storage buffers
+        limits.max_compute_invocations_per_workgroup >= boids::INDIRECT_WORKGROUP_SIZE
+            && limits.max_storage_buffers_per_shader_stage >= 5
+    }
+
+    fn new(
+        device: &wgpu::Device,
+        shader: wgpu::ShaderModule,
+        pipeline_cache: Option<Arc<wgpu::PipelineCache>>,
+        sim_param_buffer: &wgpu::Buffer,
+        particle_buffers: &[wgpu::Buffer],
+        particle_info_buffer: &wgpu::Buffer,
+    ) -> Self {
+        // create compute bind layout group and compute pipeline layout
+
+        let compute_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particles_src::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particles_dst::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particle_info::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particle_counts::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::emit_params::LAYOUT
+                    },
+                ],
+                label: None,
+            });
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("compute"),
+                bind_group_layouts: &[&compute_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // emitters append new boids to the survivors of each step, and the live count stays on the GPU
+
+        let compact_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(boids::ENTRY_COMPACT_CS),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader,
+            entry_point: boids::ENTRY_COMPACT_CS,
+            compilation_options: Default::default(),
+            cache: pipeline_cache.as_deref(),
+        });
+        let emit_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(boids::ENTRY_EMIT_CS),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader,
+            entry_point: boids::ENTRY_EMIT_CS,
+            compilation_options: Default::default(),
+            cache: pipeline_cache.as_deref(),
+        });
+        let emit_param_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Emit Parameter Buffer"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: size_of::<boids::EmitParams>() as _,
+            mapped_at_creation: false,
+        });
+        let counts_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Counts Buffer"),
+            size: (boids::NUM_COUNTS as usize * size_of::<u32>()) as _,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let indirect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Indirect Argument Buffer"),
+            size: u32_offset(boids::INDIRECT_ARGS_LEN),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+            mapped_at_creation: false,
+        });
+
+        let counts_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particle_counts::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::indirect_args::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::emit_params::LAYOUT
+                    },
+                ],
+                label: Some("counts"),
+            });
+        let counts_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("counts"),
+                bind_group_layouts: &[&counts_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // create the compute pipelines for the default workgroup size, or the largest one the device can run
+
+        let limits = device.limits();
+        let workgroup_sizes: Vec<_> = BOIDS_CS_ENTRIES
+            .iter()
+            .map(|&(workgroup_size, _)| workgroup_size)
+            .filter(|&workgroup_size| {
+                workgroup_size <= limits.max_compute_invocations_per_workgroup
+                    && workgroup_size <= limits.max_compute_workgroup_size_x
+            })
+            .collect();
+        let sized_pipelines = SizedPipelines::new(
+            device,
+            &shader,
+            pipeline_cache.as_deref(),
+            &compute_pipeline_layout,
+            &counts_pipeline_layout,
+            supported_workgroup_size(&workgroup_sizes, DEFAULT_WORKGROUP_SIZE),
+        );
+        let counts_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &counts_bind_group_layout,
+            entries: &[
+                boids::params::bind_group_entry(sim_param_buffer.as_entire_buffer_binding()),
+                boids::particle_counts::bind_group_entry(counts_buffer.as_entire_buffer_binding()),
+                boids::indirect_args::bind_group_entry(indirect_buffer.as_entire_buffer_binding()),
+                boids::emit_params::bind_group_entry(emit_param_buffer.as_entire_buffer_binding()),
+            ],
+            label: Some("counts"),
+        });
+
+        // create two bind groups, one for each buffer as the src
+        // where the alternate buffer is used as the dst
+
+        let mut particle_bind_groups = Vec::<wgpu::BindGroup>::new();
+        for i in 0..2 {
+            particle_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &compute_bind_group_layout,
+                entries: &[
+                    boids::params::bind_group_entry(sim_param_buffer.as_entire_buffer_binding()),
+                    boids::particles_src::bind_group_entry(
+                        particle_buffers[i].as_entire_buffer_binding(),
+                    ),
+                    boids::particles_dst::bind_group_entry(
+                        particle_buffers[(i + 1) % 2].as_entire_buffer_binding(),
+                    ),
+                    boids::particle_info::bind_group_entry(
+                        particle_info_buffer.as_entire_buffer_binding(),
+                    ),
+                    boids::particle_counts::bind_group_entry(
+                        counts_buffer.as_entire_buffer_binding(),
+                    ),
+                    boids::emit_params::bind_group_entry(
+                        emit_param_buffer.as_entire_buffer_binding(),
+                    ),
+                ],
+                label: None,
+            }));
+        }
+
+        // flock statistics are reduced in two passes, first per workgroup and then over all of the workgroups
+
+        let stats_partials_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Stats Partials Buffer"),
+            size: (MAX_PARTICLES.div_ceil(boids::STATS_PARTIAL_CS_WORKGROUP_SIZE[0] as usize)
+                * size_of::<boids::StatsAccumulator>()) as _,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let flock_stats_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Flock Stats Buffer"),
+            size: size_of::<boids::FlockStats>() as _,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let stats_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particles_src::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particle_info::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::stats_partials::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::flock_stats::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particle_counts::LAYOUT
+                    },
+                ],
+                label: Some("stats"),
+            });
+        let stats_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("stats"),
+                bind_group_layouts: &[&stats_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let stats_partial_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(boids::ENTRY_STATS_PARTIAL_CS),
+                layout: Some(&stats_pipeline_layout),
+                module: &shader,
+                entry_point: boids::ENTRY_STATS_PARTIAL_CS,
+                compilation_options: Default::default(),
+                cache: pipeline_cache.as_deref(),
+            });
+        let stats_final_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(boids::ENTRY_STATS_FINAL_CS),
+                layout: Some(&stats_pipeline_layout),
+                module: &shader,
+                entry_point: boids::ENTRY_STATS_FINAL_CS,
+                compilation_options: Default::default(),
+                cache: pipeline_cache.as_deref(),
+            });
+
+        // the statistics are always computed from the most recent particles, so there is one bind group for each
+        // particle buffer
+
+        let stats_bind_groups = particle_buffers
+            .iter()
+            .map(|particle_buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &stats_bind_group_layout,
+                    entries: &[
+                        boids::params::bind_group_entry(
+                            sim_param_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::particles_src::bind_group_entry(
+                            particle_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::particle_info::bind_group_entry(
+                            particle_info_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::stats_partials::bind_group_entry(
+                            stats_partials_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::flock_stats::bind_group_entry(
+                            flock_stats_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::particle_counts::bind_group_entry(
+                            counts_buffer.as_entire_buffer_binding(),
+                        ),
+                    ],
+                    label: Some("stats"),
+                })
+            })
+            .collect();
+
+        // picking and inspecting a single boid, also from the most recent particles
+
+        let inspect_param_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Inspect Parameter Buffer"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: size_of::<boids::InspectParams>() as _,
+            mapped_at_creation: false,
+        });
+        let pick_result_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Result Buffer"),
+            size: size_of::<u32>() as _,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let inspected_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Inspected Boid Buffer"),
+            size: size_of::<boids::InspectedBoid>() as _,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let inspect_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particles_src::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::inspect_params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::pick_result::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::inspected::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ..boids::particle_counts::LAYOUT
+                    },
+                ],
+                label: Some("inspect"),
+            });
+        let inspect_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("inspect"),
+                bind_group_layouts: &[&inspect_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pick_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(boids::ENTRY_PICK_CS),
+            layout: Some(&inspect_pipeline_layout),
+            module: &shader,
+            entry_point: boids::ENTRY_PICK_CS,
+            compilation_options: Default::default(),
+            cache: pipeline_cache.as_deref(),
+        });
+        let inspect_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(boids::ENTRY_INSPECT_CS),
+            layout: Some(&inspect_pipeline_layout),
+            module: &shader,
+            entry_point: boids::ENTRY_INSPECT_CS,
+            compilation_options: Default::default(),
+            cache: pipeline_cache.as_deref(),
+        });
+        let inspect_bind_groups = particle_buffers
+            .iter()
+            .map(|particle_buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &inspect_bind_group_layout,
+                    entries: &[
+                        boids::params::bind_group_entry(
+                            sim_param_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::particles_src::bind_group_entry(
+                            particle_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::inspect_params::bind_group_entry(
+                            inspect_param_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::pick_result::bind_group_entry(
+                            pick_result_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::inspected::bind_group_entry(
+                            inspected_buffer.as_entire_buffer_binding(),
+                        ),
+                        boids::particle_counts::bind_group_entry(
+                            counts_buffer.as_entire_buffer_binding(),
+                        ),
+                    ],
+                    label: Some("inspect"),
+                })
+            })
+            .collect();
+
+        GpuSimulation {
+            shader,
+            pipeline_cache,
+            particle_bind_groups,
+            compute_pipeline_layout,
+            counts_pipeline_layout,
+            workgroup_sizes,
+            sized_pipelines,
+            tiled_pipeline: None,
+            emit_param_buffer,
+            compact_pipeline,
+            emit_pipeline,
+            counts_buffer,
+            indirect_buffer,
+            counts_bind_group,
+            emitting: false,
+            stats_bind_groups,
+            stats_partial_pipeline,
+            stats_final_pipeline,
+            stats_readback: ReadbackQueue::new(
+                "Flock Stats Readback",
+                flock_stats_buffer.size(),
+                MAX_READBACKS_IN_FLIGHT,
+            ),
+            flock_stats_buffer,
+            inspect_param_buffer,
+            pick_readback: ReadbackQueue::new(
+                "Pick Result Readback",
+                pick_result_buffer.size(),
+                MAX_READBACKS_IN_FLIGHT,
+            ),
+            pick_result_buffer,
+            inspect_readback: ReadbackQueue::new(
+                "Inspected Boid Readback",
+                inspected_buffer.size(),
+                MAX_READBACKS_IN_FLIGHT,
+            ),
+            inspected_buffer,
+            inspect_bind_groups,
+            pick_pipeline,
+            inspect_pipeline,
+            // a snapshot is a rare, large readback of every particle
+            snapshot_readback: ReadbackQueue::new(
+                "Snapshot Readback",
+                particle_buffers[0].size(),
+                1,
+            ),
+        }
+    }
+
+    /// Creates the pipelines for `kernel` and `workgroup_size` if they don't exist yet, see `boids_pipeline`.
+    fn prepare_boids_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        kernel: BoidsKernel,
+        workgroup_size: u32,
+    ) {
+        let workgroup_size = supported_workgroup_size(&self.workgroup_sizes, workgroup_size);
+        if self.sized_pipelines.workgroup_size != workgroup_size {
+            self.sized_pipelines = SizedPipelines::new(
+                device,
+                &self.shader,
+                self.pipeline_cache.as_deref(),
+                &self.compute_pipeline_layout,
+                &self.counts_pipeline_layout,
+                workgroup_size,
+            );
+        }
+
+        let BoidsKernel::Tiled { tile_size } = kernel else {
+            return;
+        };
+        let tile_size = tile_size.clamp(1, MAX_TILE_SIZE);
+        if !matches!(self.tiled_pipeline, Some((size, _)) if size == tile_size) {
+            let constants = boids::OverrideConstants {
+                boids_workgroup_size: None,
+                tile_size: Some(tile_size),
+            }
+            .constants();
+            let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(boids::ENTRY_BOIDS_TILED_CS),
+                layout: Some(&self.compute_pipeline_layout),
+                module: &self.shader,
+                entry_point: boids::ENTRY_BOIDS_TILED_CS,
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+                cache: self.pipeline_cache.as_deref(),
+            });
+            self.tiled_pipeline = Some((tile_size, pipeline));
+        }
+    }
+
+    /// The pipeline that simulates the boids with `kernel`, once `prepare_boids_pipeline` was called for it.
+    fn boids_pipeline(&self, kernel: BoidsKernel) -> &wgpu::ComputePipeline {
+        match (kernel, &self.tiled_pipeline) {
+            (BoidsKernel::Tiled { .. }, Some((_, tiled_pipeline))) => tiled_pipeline,
+            _ => &self.sized_pipelines.boids,
+        }
+    }
+
+    /// Where the dispatch arguments of `boids_pipeline` are in `indirect_buffer`.
+    fn boids_dispatch_args(kernel: BoidsKernel) -> u32 {
+        match kernel {
+            BoidsKernel::AllPairs => boids::INDIRECT_DISPATCH_BOIDS,
+            BoidsKernel::Tiled { .. } => boids::INDIRECT_DISPATCH,
+        }
+    }
+}
+
+/// The largest of `workgroup_sizes` that isn't larger than `workgroup_size`, or the smallest one.
+fn supported_workgroup_size(workgroup_sizes: &[u32], workgroup_size: u32) -> u32 {
+    workgroup_sizes
+        .iter()
+        .copied()
+        .filter(|&size| size <= workgroup_size)
+        .max()
+        .or_else(|| workgroup_sizes.first().copied())
+        .unwrap_or(boids::INDIRECT_WORKGROUP_SIZE)
+}
+
+impl SizedPipelines {
+    fn new(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        compute_pipeline_layout: &wgpu::PipelineLayout,
+        counts_pipeline_layout: &wgpu::PipelineLayout,
+        workgroup_size: u32,
+    ) -> Self {
+        let (_, boids_entry) = BOIDS_CS_ENTRIES
+            .into_iter()
+            .find(|&(size, _)| size == workgroup_size)
+            .expect("unknown workgroup size");
+        let constants = boids::OverrideConstants {
+            boids_workgroup_size: Some(workgroup_size),
+            tile_size: None,
+        }
+        .constants();
+        let create_pipeline = |entry_point, layout| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(layout),
+                module: shader,
+                entry_point,
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+                cache: pipeline_cache,
+            })
+        };
+
+        Self {
+            workgroup_size,
+            boids: create_pipeline(boids_entry, compute_pipeline_layout),
+            indirect_args: create_pipeline(boids::ENTRY_INDIRECT_ARGS_CS, counts_pipeline_layout),
+            finish_step: create_pipeline(boids::ENTRY_FINISH_STEP_CS, counts_pipeline_layout),
+        }
+    }
+}
+
+impl ParticleSystem {
+    /// Creates the buffers and pipelines. The boids are drawn into render passes with a single `color_format`
+    /// attachment. Every pipeline is created with `pipeline_cache`, including the ones that are only created once
+    /// they are used, so it should be saved after those too.
+    pub fn init(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_format: wgpu::TextureFormat,
+        pipeline_cache: Option<Arc<wgpu::PipelineCache>>,
+    ) -> Self {
+        let shader = boids::create_shader_module(device);
+        // the overrides only affect compute shaders
+        let render_overrides = boids::OverrideConstants {
+            boids_workgroup_size: None,
+            tile_size: None,
+        };
+
+        // buffer for simulation parameters uniform
+
+        let sim_param_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Simulation Parameter Buffer"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: size_of::<SimParams>() as _,
+            mapped_at_creation: false,
+        });
+
+        // create render pipeline, which needs the camera, selection and the gradient that boids are colored with
+
+        let render_param_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Render Parameter Buffer"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: size_of::<boids::RenderParams>() as _,
+            mapped_at_creation: false,
+        });
+        // filled in by `set_color_lut`
+        let color_lut_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Color LUT"),
+            size: wgpu::Extent3d {
+                width: LUT_WIDTH,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            // WebGL2 doesn't have 1D textures
+            dimension: wgpu::TextureDimension::D2,
+            // egui usually renders to a non-sRGB target and blends in gamma space, in which case the sRGB bytes of the
+            // gradient have to go through unchanged
+            format: if color_format.is_srgb() {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let color_lut_view = color_lut_texture.create_view(&Default::default());
+        let color_lut_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Color LUT"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let render_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::render_params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::color_lut::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::color_lut_sampler::LAYOUT
+                    },
+                ],
+                label: Some("render"),
+            });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &render_bind_group_layout,
+            entries: &[
+                boids::render_params::bind_group_entry(
+                    render_param_buffer.as_entire_buffer_binding(),
+                ),
+                boids::color_lut::bind_group_entry(&color_lut_view),
+                boids::color_lut_sampler::bind_group_entry(&color_lut_sampler),
+            ],
+            label: Some("render"),
+        });
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render"),
+                bind_group_layouts: &[&render_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&render_pipeline_layout),
+            vertex: boids::vertex_state(
+                &shader,
+                &boids::boids_vs_entry(
+                    wgpu::VertexStepMode::Instance,
+                    wgpu::VertexStepMode::Instance,
+                    &render_overrides,
+                ),
+            ),
+            fragment: Some(boids::fragment_state(
+                &shader,
+                &boids::boids_fs_entry([Some(color_format.into())], &render_overrides),
+            )),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: pipeline_cache.as_deref(),
+        });
+
+        // wireframes draw the same triangles as lines, which not every device supports
+
+        let wireframe_pipeline = device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+            .then(|| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("wireframe"),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: boids::vertex_state(
+                        &shader,
+                        &boids::boids_vs_entry(
+                            wgpu::VertexStepMode::Instance,
+                            wgpu::VertexStepMode::Instance,
+                            &render_overrides,
+                        ),
+                    ),
+                    fragment: Some(boids::fragment_state(
+                        &shader,
+                        &boids::boids_fs_entry([Some(color_format.into())], &render_overrides),
+                    )),
+                    primitive: wgpu::PrimitiveState {
+                        polygon_mode: wgpu::PolygonMode::Line,
+                        ..Default::default()
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                    cache: pipeline_cache.as_deref(),
+                })
+            });
+
+        // debug lines are drawn on top of the boids, and also need the perception distances
+
+        let debug_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::render_params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::color_lut::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::color_lut_sampler::LAYOUT
+                    },
+                ],
+                label: Some("debug"),
+            });
+        let debug_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &debug_bind_group_layout,
+            entries: &[
+                boids::params::bind_group_entry(sim_param_buffer.as_entire_buffer_binding()),
+                boids::render_params::bind_group_entry(
+                    render_param_buffer.as_entire_buffer_binding(),
+                ),
+                boids::color_lut::bind_group_entry(&color_lut_view),
+                boids::color_lut_sampler::bind_group_entry(&color_lut_sampler),
+            ],
+            label: Some("debug"),
+        });
+        let debug_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("debug"),
+                bind_group_layouts: &[&debug_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let line_list = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        };
+        let velocity_vectors_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("velocity vectors"),
+                layout: Some(&debug_pipeline_layout),
+                vertex: boids::vertex_state(
+                    &shader,
+                    &boids::velocity_vectors_vs_entry(
+                        wgpu::VertexStepMode::Instance,
+                        wgpu::VertexStepMode::Instance,
+                        &render_overrides,
+                    ),
+                ),
+                fragment: Some(boids::fragment_state(
+                    &shader,
+                    &boids::boids_fs_entry([Some(color_format.into())], &render_overrides),
+                )),
+                primitive: line_list,
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: pipeline_cache.as_deref(),
+            });
+        let perception_radii_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("perception radii"),
+                layout: Some(&debug_pipeline_layout),
+                vertex: boids::vertex_state(
+                    &shader,
+                    &boids::perception_radii_vs_entry(
+                        wgpu::VertexStepMode::Instance,
+                        &render_overrides,
+                    ),
+                ),
+                // the circles overlap a lot, so they are translucent
+                fragment: Some(boids::fragment_state(
+                    &shader,
+                    &boids::boids_fs_entry(
+                        [Some(wgpu::ColorTargetState {
+                            format: color_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        &render_overrides,
+                    ),
+                )),
+                primitive: line_list,
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: pipeline_cache.as_deref(),
+            });
+
+        // sprites additionally need a texture, and are blended since they are usually partially transparent
+
+        let image_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Image"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let sprite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::render_params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::color_lut::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::color_lut_sampler::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ..boids::sprite_texture::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ..boids::image_sampler::LAYOUT
+                    },
+                ],
+                label: Some("sprites"),
+            });
+        let sprite_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("sprites"),
+                bind_group_layouts: &[&sprite_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let sprite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sprites"),
+            layout: Some(&sprite_pipeline_layout),
+            vertex: boids::vertex_state(
+                &shader,
+                &boids::sprites_vs_entry(
+                    wgpu::VertexStepMode::Instance,
+                    wgpu::VertexStepMode::Instance,
+                    &render_overrides,
+                ),
+            ),
+            fragment: Some(boids::fragment_state(
+                &shader,
+                &boids::sprites_fs_entry(
+                    [Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    &render_overrides,
+                ),
+            )),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: pipeline_cache.as_deref(),
+        });
+
+        // the background only needs the camera and its image
+
+        let background_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ..boids::render_params::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ..boids::background_texture::LAYOUT
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ..boids::image_sampler::LAYOUT
+                    },
+                ],
+                label: Some("background"),
+            });
+        let background_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("background"),
+                bind_group_layouts: &[&background_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let background_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("background"),
+            layout: Some(&background_pipeline_layout),
+            vertex: boids::vertex_state(&shader, &boids::background_vs_entry(&render_overrides)),
+            fragment: Some(boids::fragment_state(
+                &shader,
+                &boids::background_fs_entry([Some(color_format.into())], &render_overrides),
+            )),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: pipeline_cache.as_deref(),
+        });
+
+        // buffer for all particles
+
+        let initial_particle_data = initial_particles(DEFAULT_FLOCK_SEED);
+
+        // without compute shaders, particles are simulated on the CPU and copied into the buffers instead
+        let use_compute = GpuSimulation::is_supported(device);
+        let storage_usage = if use_compute {
+            wgpu::BufferUsages::STORAGE
+        } else {
+            log::warn!("The device can't run compute shaders, simulating on the CPU");
+            wgpu::BufferUsages::empty()
+        };
+
+        // creates two buffers of particle data each of size max_particles
+        // the two buffers alternate as dst and src for each frame
+
+        let mut particle_buffers = Vec::<wgpu::Buffer>::new();
+        for i in 0..2 {
+            particle_buffers.push(
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Particle Buffer {i}")),
+                    contents: bytemuck::cast_slice(&initial_particle_data),
+                    usage: wgpu::BufferUsages::VERTEX
+                        | storage_usage
+                        | wgpu::BufferUsages::COPY_SRC
+                        | wgpu::BufferUsages::COPY_DST,
+                }),
+            );
+        }
+
+        // per-particle values that are only needed for statistics, debugging and coloring
+
+        let particle_info_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Info Buffer"),
+            size: (MAX_PARTICLES * size_of::<boids::ParticleInfo>()) as _,
+            usage: storage_usage | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let simulation = if use_compute {
+            Simulation::Gpu(Box::new(GpuSimulation::new(
+                device,
+                shader,
+                pipeline_cache,
+                &sim_param_buffer,
+                &particle_buffers,
+                &particle_info_buffer,
+            )))
+        } else {
+            Simulation::Cpu(CpuSimulation::new(initial_particle_data))
+        };
+
+        let sprite_texture = texture::load_ktx2(device, queue, "Built-in Sprite", BUILT_IN_SPRITE)
+            .expect("the built-in sprite should be a valid texture");
+        let sprite_bind_group = Self::create_sprite_bind_group(
+            device,
+            &sprite_bind_group_layout,
+            &render_param_buffer,
+            &color_lut_view,
+            &color_lut_sampler,
+            &sprite_texture,
+            &image_sampler,
+        );
+
+        ParticleSystem {
+            sim_param_buffer,
+            particle_buffers,
+            particle_info_buffer,
+            simulation,
+            render_param_buffer,
+            color_format,
+            color_lut_texture,
+            color_lut_view,
+            color_lut_sampler,
+            image_sampler,
+            render_bind_group,
+            render_pipeline,
+            wireframe_pipeline,
+            debug_bind_group,
+            velocity_vectors_pipeline,
+            perception_radii_pipeline,
+            sprite_bind_group_layout,
+            sprite_bind_group,
+            sprite_pipeline,
+            background_bind_group_layout,
+            background_bind_group: None,
+            background_pipeline,
+            stats_samples: Vec::new(),
+            pick_results: Vec::new(),
+            pending_pick: None,
+            inspected: None,
+            frame_num: 0,
+            sim_time: 0.,
+            reported_sim_time: 0.,
+            profiler: GpuProfiler::new(GpuProfilerSettings {
+                // the device is only created with timer queries if the adapter supports them
+                enable_timer_queries: device.features().contains(wgpu::Features::TIMESTAMP_QUERY),
+                ..Default::default()
+            })
+            .unwrap(),
+            pending_frames: VecDeque::new(),
+            gpu_time_per_step_ms: None,
+            kernel: BoidsKernel::default(),
+            autotune: None,
+            autotuned_workgroup_size: None,
+            gpu_frame_traces: VecDeque::new(),
+            trace_gpu_frames: false,
+            num_particles: 0,
+            flock_seed: DEFAULT_FLOCK_SEED,
+            pending_particles: None,
+            snapshot_requested: false,
+            snapshot: None,
+        }
+    }
+
+    fn create_sprite_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        render_param_buffer: &wgpu::Buffer,
+        color_lut_view: &wgpu::TextureView,
+        color_lut_sampler: &wgpu::Sampler,
+        sprite_texture: &wgpu::Texture,
+        image_sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                boids::render_params::bind_group_entry(
+                    render_param_buffer.as_entire_buffer_binding(),
+                ),
+                boids::color_lut::bind_group_entry(color_lut_view),
+                boids::color_lut_sampler::bind_group_entry(color_lut_sampler),
+                boids::sprite_texture::bind_group_entry(&texture::first_layer_view(sprite_texture)),
+                boids::image_sampler::bind_group_entry(image_sampler),
+            ],
+            label: Some("sprites"),
+        })
+    }
+
+    /// Whether the boids are simulated on the CPU, since the device can't run compute shaders.
+    pub fn simulates_on_cpu(&self) -> bool {
+        matches!(self.simulation, Simulation::Cpu(_))
+    }
+
+    pub fn supports_wireframe(&self) -> bool {
+        self.wireframe_pipeline.is_some()
+    }
+
+    /// Draws sprites with the first layer of `texture` from now on.
+    pub fn set_sprite_texture(&mut self, device: &wgpu::Device, texture: &wgpu::Texture) {
+        self.sprite_bind_group = Self::create_sprite_bind_group(
+            device,
+            &self.sprite_bind_group_layout,
+            &self.render_param_buffer,
+            &self.color_lut_view,
+            &self.color_lut_sampler,
+            texture,
+            &self.image_sampler,
+        );
+    }
+
+    /// Draws the first layer of `texture` behind the boids from now on.
+    pub fn set_background_texture(&mut self, device: &wgpu::Device, texture: &wgpu::Texture) {
+        self.background_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.background_bind_group_layout,
+            entries: &[
+                boids::render_params::bind_group_entry(
+                    self.render_param_buffer.as_entire_buffer_binding(),
+                ),
+                boids::background_texture::bind_group_entry(&texture::first_layer_view(texture)),
+                boids::image_sampler::bind_group_entry(&self.image_sampler),
+            ],
+            label: Some("background"),
+        }));
+    }
+
+    pub fn clear_background_texture(&mut self) {
+        self.background_bind_group = None;
+    }
+
+    /// Returns the flock statistics that were read back since the last call, oldest first.
+    pub fn take_stats_samples(&mut self) -> Vec<FlockSample> {
+        std::mem::take(&mut self.stats_samples)
+    }
+
+    /// Returns the results of every pick that finished since the last call, oldest first. The result is `None` if
+    /// there weren't any boids to pick.
+    pub fn take_pick_results(&mut self) -> Vec<Option<PickedBoid>> {
+        std::mem::take(&mut self.pick_results)
+    }
+
+    /// The most recent data of an inspected boid, together with its index.
+    pub fn inspected(&self) -> Option<(u32, boids::InspectedBoid)> {
+        self.inspected
+    }
+
+    /// Simulation time that the flock has advanced by since the last call. Steps that are still running on a worker
+    /// only count once they have finished.
+    pub fn take_finished_sim_time(&mut self) -> f64 {
+        let finished = self.sim_time - self.reported_sim_time;
+        self.reported_sim_time = self.sim_time;
+        finished
+    }
+
+    /// Smoothed GPU time of a single simulation step, or `None` if it hasn't been measured (yet). When simulating on
+    /// the CPU, this is the CPU time instead.
+    pub fn gpu_time_per_step_ms(&self) -> Option<f32> {
+        self.gpu_time_per_step_ms
+    }
+
+    /// Workgroup sizes of [`BoidsKernel::AllPairs`] that the device can run, from small to large. Empty when
+    /// simulating on the CPU.
+    pub fn workgroup_sizes(&self) -> &[u32] {
+        match &self.simulation {
+            Simulation::Gpu(gpu) => &gpu.workgroup_sizes,
+            Simulation::Cpu(_) => &[],
+        }
+    }
+
+    /// The workgroup size that the last `record` simulated with, or `None` when simulating on the CPU.
+    pub fn workgroup_size(&self) -> Option<u32> {
+        match &self.simulation {
+            Simulation::Gpu(gpu) => Some(gpu.sized_pipelines.workgroup_size),
+            Simulation::Cpu(_) => None,
+        }
+    }
+
+    /// Whether [`ParticleSystem::start_autotune`] can measure anything, which needs the GPU simulation and timer
+    /// queries.
+    pub fn can_autotune(&self) -> bool {
+        !self.simulates_on_cpu()
+            && self.profiler.settings().enable_timer_queries
+            && self.workgroup_sizes().len() > 1
+    }
+
+    /// Simulates every frame from now on with one of the [`ParticleSystem::workgroup_sizes`] after the other, and
+    /// [`BoidsKernel::AllPairs`], until the fastest one was found. Only frames that simulate at least one step count.
+    /// The result is returned by [`ParticleSystem::take_autotuned_workgroup_size`].
+    pub fn start_autotune(&mut self) {
+        if self.can_autotune() {
+            self.autotune = Some(Autotune::new(self.workgroup_sizes().to_vec()));
+        }
+    }
+
+    /// How far along autotuning is, from 0 to 1, or `None` if it isn't running.
+    pub fn autotune_progress(&self) -> Option<f32> {
+        self.autotune.as_ref().map(Autotune::progress)
+    }
+
+    /// The fastest workgroup size, once autotuning finished.
+    pub fn take_autotuned_workgroup_size(&mut self) -> Option<u32> {
+        self.autotuned_workgroup_size.take()
+    }
+
+    /// Starts or stops collecting the timer queries of every frame for [`ParticleSystem::take_gpu_frame_traces`], like
+    /// for [`write_chrome_trace`](crate::sim::write_chrome_trace).
+    pub fn set_trace_gpu_frames(&mut self, trace: bool) {
+        self.trace_gpu_frames = trace;
+        if !trace {
+            self.gpu_frame_traces.clear();
+        }
+    }
+
+    /// The GPU frames whose timer queries arrived since the last call, while tracing them.
+    pub fn take_gpu_frame_traces(&mut self) -> Vec<GpuFrameTrace> {
+        self.gpu_frame_traces.drain(..).collect()
+    }
+
+    /// Starts the fixed flock over with the boids that `seed` places at random, in the next `record` that simulates
+    /// without emitters. The flock also starts over with this seed whenever emitters are turned off.
+    pub fn reset_flock(&mut self, seed: u32) {
+        self.flock_seed = seed;
+        self.pending_particles = Some(initial_particles(seed));
+    }
+
+    /// Replaces the first boids of the fixed flock with `particles`, e.g. the ones of a snapshot, in the next `record`
+    /// that simulates without emitters. At most [`MAX_PARTICLES`] are used.
+    pub fn load_particles(&mut self, mut particles: Vec<boids::Particle>) {
+        particles.truncate(MAX_PARTICLES);
+        self.pending_particles = Some(particles);
+    }
+
+    /// Reads back the boids that the next `record` simulated, see [`ParticleSystem::take_snapshot`]. Only the fixed
+    /// flock can be snapshotted, emitted boids would be mixed up with dead ones.
+    pub fn request_snapshot(&mut self) {
+        self.snapshot_requested = true;
+    }
+
+    pub fn take_snapshot(&mut self) -> Option<Vec<boids::Particle>> {
+        self.snapshot.take()
+    }
+
+    /// Colors the boids with `color_lut` from now on, which is `LUT_WIDTH` sRGB colors.
+    pub fn set_color_lut(&self, queue: &wgpu::Queue, color_lut: &[[u8; 4]]) {
+        queue.write_texture(
+            self.color_lut_texture.as_image_copy(),
+            bytemuck::cast_slice(color_lut),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * LUT_WIDTH),
+                rows_per_image: None,
+            },
+            self.color_lut_texture.size(),
+        );
+    }
+
+    /// Writes the parameters of this frame, and records the simulation steps and everything that is read back
+    /// afterwards into `encoder`. Call `after_submit` once the encoder was submitted.
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &FrameInput,
+    ) {
+        let frame = &FrameInput {
+            pick_position: frame.pick_position.or(self.pending_pick.take()),
+            ..*frame
+        };
+        // there's nothing new to compute statistics for if the simulation didn't move
+        let compute_stats = frame.compute_stats && frame.num_steps > 0;
+        let mut cpu_step_time_ms = None;
+        self.num_particles = frame.sim_params.num_particles;
+        let (kernel, workgroup_size) = match &self.autotune {
+            Some(autotune) => (BoidsKernel::AllPairs, autotune.workgroup_size()),
+            None => (frame.kernel, frame.workgroup_size),
+        };
+        if let Simulation::Gpu(gpu) = &mut self.simulation {
+            let previous_workgroup_size = gpu.sized_pipelines.workgroup_size;
+            gpu.prepare_boids_pipeline(device, kernel, workgroup_size);
+            if kernel != self.kernel
+                || gpu.sized_pipelines.workgroup_size != previous_workgroup_size
+            {
+                // don't mix up the step times of different kernels
+                self.kernel = kernel;
+                self.gpu_time_per_step_ms = None;
+            }
+        }
+        let autotune_sample = self
+            .autotune
+            .as_mut()
+            .filter(|_| frame.num_steps > 0)
+            .and_then(|autotune| autotune.frame_recorded().then(|| autotune.workgroup_size()));
+
+        {
+            let mut encoder = self
+                .profiler
+                .scope(current_function_name!(), encoder, device);
+            // update uniforms
+            queue.write_buffer(
+                &self.sim_param_buffer,
+                0,
+                bytemuck::bytes_of(&frame.sim_params),
+            );
+            queue.write_buffer(
+                &self.render_param_buffer,
+                0,
+                bytemuck::bytes_of(&frame.render_params),
+            );
+
+            match &mut self.simulation {
+                Simulation::Gpu(gpu) => {
+                    queue.write_buffer(
+                        &gpu.inspect_param_buffer,
+                        0,
+                        bytemuck::bytes_of(&boids::InspectParams {
+                            pick_position: frame.pick_position.unwrap_or_default(),
+                            selected_index: frame.inspect_index.unwrap_or(boids::NO_SELECTION),
+                            _padding: 0,
+                        }),
+                    );
+                    if frame.pick_position.is_some() {
+                        queue.write_buffer(
+                            &gpu.pick_result_buffer,
+                            0,
+                            bytemuck::bytes_of(&u32::MAX),
+                        );
+                    }
+
+                    let emitting = frame.emitters.is_some();
+                    if emitting != gpu.emitting {
+                        gpu.emitting = emitting;
+                        // emitters start out with an empty field, and the fixed flock starts over
+                        if !emitting {
+                            let initial_particle_data = initial_particles(self.flock_seed);
+                            for particle_buffer in &self.particle_buffers {
+                                queue.write_buffer(
+                                    particle_buffer,
+                                    0,
+                                    bytemuck::cast_slice(&initial_particle_data),
+                                );
+                            }
+                        }
+                        queue.write_buffer(
+                            &gpu.counts_buffer,
+                            0,
+                            bytemuck::cast_slice(&[0u32; boids::NUM_COUNTS as usize]),
+                        );
+                    }
+                    let emit_params = match frame.emitters {
+                        Some(emit_params) => boids::EmitParams {
+                            enabled: 1,
+                            ..emit_params
+                        },
+                        None => bytemuck::Zeroable::zeroed(),
+                    };
+                    queue.write_buffer(&gpu.emit_param_buffer, 0, bytemuck::bytes_of(&emit_params));
+                    if let Some(particles) = self.pending_particles.take_if(|_| !emitting) {
+                        for particle_buffer in &self.particle_buffers {
+                            queue.write_buffer(
+                                particle_buffer,
+                                0,
+                                bytemuck::cast_slice(&particles),
+                            );
+                        }
+                    }
+                    if !emitting {
+                        // without emitters, the size of the flock is only changed from here
+                        queue.write_buffer(
+                            &gpu.counts_buffer,
+                            u32_offset(boids::COUNT_LIVE),
+                            bytemuck::bytes_of(&frame.sim_params.num_particles),
+                        );
+                    }
+
+                    // compute pass
+                    {
+                        let mut cpass =
+                            encoder.scoped_compute_pass(self.kernel.scope_label(), device);
+                        // the live count may have changed since the last step
+                        cpass.set_pipeline(&gpu.sized_pipelines.indirect_args);
+                        cpass.set_bind_group(0, &gpu.counts_bind_group, &[]);
+                        cpass.dispatch_workgroups(1, 1, 1);
+
+                        // every emitter spawns at most one boid more than its rate, because of the fraction it carries
+                        // over from the previous step
+                        let max_spawn_count = emit_params.emitters
+                            [..emit_params.num_emitters as usize]
+                            .iter()
+                            .map(|emitter| {
+                                (emitter.rate * frame.sim_params.delta_time).ceil() as u32 + 1
+                            })
+                            .max()
+                            .unwrap_or_default();
+
+                        for _ in 0..frame.num_steps {
+                            cpass.set_pipeline(gpu.boids_pipeline(self.kernel));
+                            cpass.set_bind_group(
+                                0,
+                                &gpu.particle_bind_groups[self.frame_num % 2],
+                                &[],
+                            );
+                            cpass.dispatch_workgroups_indirect(
+                                &gpu.indirect_buffer,
+                                u32_offset(GpuSimulation::boids_dispatch_args(self.kernel)),
+                            );
+                            if emitting {
+                                cpass.set_pipeline(&gpu.compact_pipeline);
+                                cpass.dispatch_workgroups(1, 1, 1);
+                                if max_spawn_count > 0 {
+                                    cpass.set_pipeline(&gpu.emit_pipeline);
+                                    cpass.dispatch_workgroups(
+                                        max_spawn_count.div_ceil(boids::EMIT_CS_WORKGROUP_SIZE[0]),
+                                        emit_params.num_emitters,
+                                        1,
+                                    );
+                                }
+                                cpass.set_pipeline(&gpu.sized_pipelines.finish_step);
+                                cpass.set_bind_group(0, &gpu.counts_bind_group, &[]);
+                                cpass.dispatch_workgroups(1, 1, 1);
+                            }
+                            self.frame_num += 1;
+                        }
+                    }
+                    self.sim_time += frame.sim_params.delta_time as f64 * frame.num_steps as f64;
+
+                    if compute_stats {
+                        let mut cpass = encoder.scoped_compute_pass("flock stats", device);
+                        cpass.set_bind_group(0, &gpu.stats_bind_groups[self.frame_num % 2], &[]);
+                        cpass.set_pipeline(&gpu.stats_partial_pipeline);
+                        cpass.dispatch_workgroups_indirect(
+                            &gpu.indirect_buffer,
+                            u32_offset(boids::INDIRECT_DISPATCH),
+                        );
+                        cpass.set_pipeline(&gpu.stats_final_pipeline);
+                        cpass.dispatch_workgroups(1, 1, 1);
+                    }
+
+                    if frame.pick_position.is_some() || frame.inspect_index.is_some() {
+                        let mut cpass = encoder.scoped_compute_pass("inspect", device);
+                        cpass.set_bind_group(0, &gpu.inspect_bind_groups[self.frame_num % 2], &[]);
+                        if frame.pick_position.is_some() {
+                            cpass.set_pipeline(&gpu.pick_pipeline);
+                            cpass.dispatch_workgroups_indirect(
+                                &gpu.indirect_buffer,
+                                u32_offset(boids::INDIRECT_DISPATCH),
+                            );
+                        }
+                        if frame.inspect_index.is_some() {
+                            cpass.set_pipeline(&gpu.inspect_pipeline);
+                            cpass.dispatch_workgroups(1, 1, 1);
+                        }
+                    }
+                }
+                Simulation::Cpu(cpu) => {
+                    if let Some(particles) = self.pending_particles.take() {
+                        cpu.set_particles(&particles);
+                    }
+                    // only steps that finished count, so the buffer that is uploaded into below is always the one
+                    // that `draw` draws, even while the steps are still running in the worker
+                    if let Some(finished) =
+                        cpu.advance(&frame.sim_params, frame.num_steps, frame.max_steps)
+                    {
+                        self.frame_num += finished.num_steps as usize;
+                        self.sim_time += finished.sim_time;
+                        cpu_step_time_ms = Some(finished.step_time_ms);
+                    }
+
+                    let num_particles = frame.sim_params.num_particles as usize;
+                    if let Some((particles, info)) = cpu.take_changed() {
+                        // the same buffer that `draw` draws
+                        queue.write_buffer(
+                            &self.particle_buffers[self.frame_num % 2],
+                            0,
+                            bytemuck::cast_slice(&particles[..num_particles]),
+                        );
+                        queue.write_buffer(
+                            &self.particle_info_buffer,
+                            0,
+                            bytemuck::cast_slice(&info[..num_particles]),
+                        );
+                    }
+
+                    if compute_stats {
+                        self.stats_samples.push(FlockSample {
+                            step: self.frame_num as u64,
+                            sim_time: self.sim_time,
+                            stats: cpu.flock_stats(frame.sim_params.num_particles),
+                        });
+                    }
+                    if let Some(pick_position) = frame.pick_position {
+                        self.pick_results
+                            .push(cpu.pick(pick_position, frame.sim_params.num_particles));
+                    }
+                    if let Some(index) = frame.inspect_index {
+                        if let Some(inspected) = cpu.inspect(&frame.sim_params, index) {
+                            self.inspected = Some((index, inspected));
+                        }
+                    }
+                    if std::mem::take(&mut self.snapshot_requested) {
+                        self.snapshot = Some(cpu.particles()[..num_particles].to_vec());
+                    }
+                }
+            }
+        }
+
+        if let Simulation::Gpu(gpu) = &mut self.simulation {
+            if compute_stats {
+                gpu.stats_readback.copy_from(
+                    device,
+                    encoder,
+                    &gpu.flock_stats_buffer,
+                    0,
+                    (self.frame_num as u64, self.sim_time),
+                );
+            }
+            // picked again next frame while the previous picks are still being read back
+            if frame.pick_position.is_some()
+                && !gpu
+                    .pick_readback
+                    .copy_from(device, encoder, &gpu.pick_result_buffer, 0, ())
+            {
+                self.pending_pick = frame.pick_position;
+            }
+            if let Some(index) = frame.inspect_index {
+                gpu.inspect_readback
+                    .copy_from(device, encoder, &gpu.inspected_buffer, 0, index);
+            }
+            // stays requested while the previous snapshot is still being read back
+            if self.snapshot_requested
+                && gpu.snapshot_readback.copy_from(
+                    device,
+                    encoder,
+                    &self.particle_buffers[self.frame_num % 2],
+                    0,
+                    frame.sim_params.num_particles,
+                )
+            {
+                self.snapshot_requested = false;
+            }
+        }
+
+        self.profiler.resolve_queries(encoder);
+        self.pending_frames.push_back(PendingFrame {
+            num_steps: frame.num_steps,
+            recorded_ns: puffin::now_ns(),
+            autotune_sample,
+        });
+        if let Some(step_time_ms) = cpu_step_time_ms {
+            self.smooth_step_time(step_time_ms);
+        }
+    }
+
+    /// Starts reading back what the last `record` copied, and collects everything that finished since. Has to be
+    /// called after the encoder passed to `record` was submitted, since buffers can't be mapped before that.
+    pub fn after_submit(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.profiler.end_frame().unwrap();
+
+        if let Simulation::Gpu(gpu) = &mut self.simulation {
+            gpu.stats_readback.map_submitted();
+            gpu.pick_readback.map_submitted();
+            gpu.inspect_readback.map_submitted();
+            gpu.snapshot_readback.map_submitted();
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if let Simulation::Gpu(gpu) = &mut self.simulation {
+            while let Some(((step, sim_time), data)) = gpu.stats_readback.try_recv() {
+                self.stats_samples.push(FlockSample {
+                    step,
+                    sim_time,
+                    stats: bytemuck::pod_read_unaligned(&data),
+                });
+            }
+            while let Some(((), data)) = gpu.pick_readback.try_recv() {
+                let pick_result = bytemuck::pod_read_unaligned(&data);
+                self.pick_results.push(PickedBoid::unpack(pick_result));
+            }
+            while let Some((index, data)) = gpu.inspect_readback.try_recv() {
+                self.inspected = Some((index, bytemuck::pod_read_unaligned(&data)));
+            }
+            while let Some((num_particles, data)) = gpu.snapshot_readback.try_recv() {
+                let size = num_particles as usize * size_of::<boids::Particle>();
+                self.snapshot = Some(
+                    data[..size]
+                        .chunks_exact(size_of::<boids::Particle>())
+                        .map(bytemuck::pod_read_unaligned)
+                        .collect(),
+                );
+            }
+        }
+
+        let latest_profiler_results = self
+            .profiler
+            .process_finished_frame(queue.get_timestamp_period());
+        let pending = latest_profiler_results
+            .as_ref()
+            .and_then(|_| self.pending_frames.pop_front());
+        if let (Some(results), Some(pending)) = (&latest_profiler_results, &pending) {
+            self.record_step_time(pending, results);
+        }
+
+        let mut gpu_profiler = PUFFIN_GPU_PROFILER.lock().unwrap();
+        gpu_profiler.new_frame();
+        profiler::output_frame_to_puffin(
+            &mut gpu_profiler,
+            latest_profiler_results.as_deref().unwrap_or_default(),
+        );
+        drop(gpu_profiler);
+
+        if let (true, Some(queries), Some(pending)) =
+            (self.trace_gpu_frames, latest_profiler_results, pending)
+        {
+            if self.gpu_frame_traces.len() == MAX_GPU_FRAME_TRACES {
+                self.gpu_frame_traces.pop_front();
+            }
+            self.gpu_frame_traces.push_back(GpuFrameTrace {
+                recorded_ns: pending.recorded_ns,
+                queries,
+            });
+        }
+    }
+
+    /// Renders the boids as they were after the last `record` into an image, in tiles if it is larger than a texture
+    /// can be. Every row of tiles is passed on to `write_rows` from the top, as the rows of pixels it covers in 8 bit
+    /// RGBA. Waits for the GPU, and overwrites the render parameters until the next `record`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &ImageRequest,
+        write_rows: impl FnMut(&[u8]) -> std::io::Result<()>,
+    ) -> Result<(), ExportError> {
+        export::render_tiled(
+            device,
+            queue,
+            self.color_format,
+            [image.width, image.height],
+            image.background,
+            |tile, rpass| {
+                let render_params = boids::RenderParams {
+                    clip_scale: tile.clip_scale,
+                    clip_offset: tile.clip_offset,
+                    ..image.render_params
+                };
+                // every tile is submitted on its own, after this is written
+                queue.write_buffer(
+                    &self.render_param_buffer,
+                    0,
+                    bytemuck::bytes_of(&render_params),
+                );
+                self.draw(rpass, &image.draw_options);
+            },
+            write_rows,
+        )
+    }
+
+    /// Draws the boids as they were after the last `record` into `rpass`.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass<'_>, options: &DrawOptions) {
+        if let Some(background_bind_group) = self
+            .background_bind_group
+            .as_ref()
+            .filter(|_| options.background)
+        {
+            rpass.set_pipeline(&self.background_pipeline);
+            rpass.set_bind_group(0, background_bind_group, &[]);
+            rpass.draw(0..6, 0..1);
+        }
+
+        let wireframe_pipeline = self
+            .wireframe_pipeline
+            .as_ref()
+            .filter(|_| options.style == BoidStyle::Wireframe);
+        let (num_vertices, indirect_args) = if let Some(wireframe_pipeline) = wireframe_pipeline {
+            rpass.set_pipeline(wireframe_pipeline);
+            rpass.set_bind_group(0, &self.render_bind_group, &[]);
+            (3, boids::INDIRECT_DRAW_TRIANGLES)
+        } else if options.style == BoidStyle::Sprites {
+            rpass.set_pipeline(&self.sprite_pipeline);
+            rpass.set_bind_group(0, &self.sprite_bind_group, &[]);
+            // the two triangles of a sprite's quad
+            (6, boids::INDIRECT_DRAW_SPRITES)
+        } else {
+            rpass.set_pipeline(&self.render_pipeline);
+            rpass.set_bind_group(0, &self.render_bind_group, &[]);
+            // the three instance-local vertices
+            (3, boids::INDIRECT_DRAW_TRIANGLES)
+        };
+        // render the particles of the most recent step, which the live count belongs to
+        rpass.set_vertex_buffer(0, self.particle_buffers[self.frame_num % 2].slice(..));
+        rpass.set_vertex_buffer(1, self.particle_info_buffer.slice(..));
+        self.draw_boids(rpass, num_vertices, indirect_args);
+
+        match options.overlay {
+            Overlay::VelocityVectors => {
+                rpass.set_pipeline(&self.velocity_vectors_pipeline);
+                rpass.set_bind_group(0, &self.debug_bind_group, &[]);
+                self.draw_boids(rpass, 2, boids::INDIRECT_DRAW_VELOCITY_VECTORS);
+            }
+            Overlay::PerceptionRadii => {
+                rpass.set_pipeline(&self.perception_radii_pipeline);
+                rpass.set_bind_group(0, &self.debug_bind_group, &[]);
+                self.draw_boids(
+                    rpass,
+                    boids::PERCEPTION_RADII_VERTEX_COUNT,
+                    boids::INDIRECT_DRAW_PERCEPTION_RADII,
+                );
+            }
+            Overlay::None => {}
+        }
+    }
+
+    /// Draws an instance with `num_vertices` vertices for every boid. The GPU simulation draws as many as are alive
+    /// with the arguments at `indirect_args`, which have the same number of vertices.
+    fn draw_boids(&self, rpass: &mut wgpu::RenderPass<'_>, num_vertices: u32, indirect_args: u32) {
+        match &self.simulation {
+            Simulation::Gpu(gpu) => {
+                rpass.draw_indirect(&gpu.indirect_buffer, u32_offset(indirect_args));
+            }
+            Simulation::Cpu(_) => rpass.draw(0..num_vertices, 0..self.num_particles),
+        }
+    }
+
+    fn record_step_time(&mut self, pending: &PendingFrame, query_result: &[GpuTimerQueryResult]) {
+        let Some(time) = profiler::find_query(query_result, self.kernel.scope_label())
+            .and_then(|query| query.time.clone())
+        else {
+            return;
+        };
+        if pending.num_steps == 0 {
+            return;
+        }
+
+        let step_time_ms = ((time.end - time.start) * 1e3) as f32 / pending.num_steps as f32;
+        self.smooth_step_time(step_time_ms);
+        if let (Some(autotune), Some(workgroup_size)) =
+            (&mut self.autotune, pending.autotune_sample)
+        {
+            if let Some(fastest) = autotune.add_sample(workgroup_size, step_time_ms) {
+                self.autotune = None;
+                self.autotuned_workgroup_size = Some(fastest);
+            }
+        }
+    }
+
+    fn smooth_step_time(&mut self, step_time_ms: f32) {
+        self.gpu_time_per_step_ms = Some(match self.gpu_time_per_step_ms {
+            Some(smoothed) => smoothed + STEP_TIME_SMOOTHING * (step_time_ms - smoothed),
+            None => step_time_ms,
+        });
+    }
+}
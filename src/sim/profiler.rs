@@ -0,0 +1,218 @@
+use puffin::{GlobalProfiler, NanoSecond, ScopeDetails, StreamInfo, ThreadInfo};
+
+use wgpu_profiler::GpuTimerQueryResult;
+
+/// Visualize the query results in a `puffin::GlobalProfiler`.
+pub fn output_frame_to_puffin(profiler: &mut GlobalProfiler, query_result: &[GpuTimerQueryResult]) {
+    let mut stream_info = StreamInfo::default();
+    collect_stream_info_recursive(profiler, &mut stream_info, query_result, 0);
+
+    profiler.report_user_scopes(
+        ThreadInfo {
+            start_time_ns: None,
+            name: "GPU".to_string(),
+        },
+        &stream_info.as_stream_into_ref(),
+    );
+}
+
+fn collect_stream_info_recursive(
+    profiler: &mut GlobalProfiler,
+    stream_info: &mut StreamInfo,
+    query_result: &[GpuTimerQueryResult],
+    depth: usize,
+) {
+    let details: Vec<_> = query_result
+        .iter()
+        .map(|query: &GpuTimerQueryResult| ScopeDetails::from_scope_name(query.label.clone()))
+        .collect();
+    let ids = profiler.register_user_scopes(&details);
+    for (query, id) in query_result.iter().zip(ids) {
+        if let Some(time) = &query.time {
+            let start = (time.start * 1e9) as NanoSecond;
+            let end = (time.end * 1e9) as NanoSecond;
+
+            stream_info.depth = stream_info.depth.max(depth);
+            stream_info.num_scopes += 1;
+            stream_info.range_ns.0 = stream_info.range_ns.0.min(start);
+            stream_info.range_ns.1 = stream_info.range_ns.0.max(end);
+
+            let (offset, _) = stream_info.stream.begin_scope(|| start, id, "");
+            collect_stream_info_recursive(profiler, stream_info, &query.nested_queries, depth + 1);
+            stream_info.stream.end_scope(offset, end as NanoSecond);
+        }
+    }
+}
+
+/// Find the first query with the given label, searching depth-first through the nested queries.
+pub fn find_query<'a>(
+    query_result: &'a [GpuTimerQueryResult],
+    label: &str,
+) -> Option<&'a GpuTimerQueryResult> {
+    query_result.iter().find_map(|query| {
+        if query.label == label {
+            Some(query)
+        } else {
+            find_query(&query.nested_queries, label)
+        }
+    })
+}
+
+/// The GPU timer queries of a frame, together with the CPU time it was recorded at.
+#[derive(Clone, Debug)]
+pub struct GpuFrameTrace {
+    /// `puffin::now_ns` when the frame was recorded
+    pub recorded_ns: NanoSecond,
+    pub queries: Vec<GpuTimerQueryResult>,
+}
+
+/// Process ids of the Chrome trace, so the CPU and GPU show up as separate processes.
+const CPU_PID: u32 = 0;
+const GPU_PID: u32 = 1;
+
+/// Writes the `cpu_frames` of `scopes` and the `gpu_frames` as Chrome trace-event JSON, which Perfetto and
+/// `chrome://tracing` can open.
+///
+/// The GPU clock can't be correlated with the CPU clock, so every GPU frame is moved to start at the CPU time it was
+/// recorded at. Since the GPU only starts working once the frame was submitted, the GPU scopes show up earlier than they
+/// actually ran, but keep their durations and their order.
+pub fn write_chrome_trace<'a>(
+    write: &mut impl std::io::Write,
+    scopes: &puffin::ScopeCollection,
+    cpu_frames: impl IntoIterator<Item = &'a puffin::FrameData>,
+    gpu_frames: impl IntoIterator<Item = &'a GpuFrameTrace>,
+) -> std::io::Result<()> {
+    let mut events = vec![
+        metadata_event("process_name", CPU_PID, 0, "CPU"),
+        metadata_event("process_name", GPU_PID, 0, "GPU"),
+        metadata_event("thread_name", GPU_PID, 0, "Queue"),
+    ];
+
+    let mut threads = Vec::new();
+    for frame in cpu_frames {
+        // frames that fail to unpack are left out, like puffin_egui does
+        let Some(frame) = frame.unpacked().ok() else {
+            continue;
+        };
+        for (thread, stream_info) in &frame.thread_streams {
+            let tid = match threads.iter().position(|name| *name == thread.name) {
+                Some(tid) => tid,
+                None => {
+                    threads.push(thread.name.clone());
+                    events.push(metadata_event(
+                        "thread_name",
+                        CPU_PID,
+                        threads.len() - 1,
+                        &thread.name,
+                    ));
+                    threads.len() - 1
+                }
+            };
+            collect_cpu_events(
+                &mut events,
+                scopes,
+                &stream_info.stream,
+                puffin::Reader::from_start(&stream_info.stream),
+                tid,
+            );
+        }
+    }
+
+    for frame in gpu_frames {
+        let Some(gpu_start) = frame
+            .queries
+            .iter()
+            .filter_map(|query| query.time.as_ref())
+            .map(|time| time.start)
+            .reduce(f64::min)
+        else {
+            continue;
+        };
+        collect_gpu_events(
+            &mut events,
+            &frame.queries,
+            frame.recorded_ns as f64 / 1e3 - gpu_start * 1e6,
+        );
+    }
+
+    write!(write, "{{\"traceEvents\":[")?;
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            write!(write, ",")?;
+        }
+        write!(write, "\n{event}")?;
+    }
+    write!(write, "\n],\"displayTimeUnit\":\"ms\"}}")
+}
+
+/// Names a process or a thread.
+fn metadata_event(name: &str, pid: u32, tid: usize, value: &str) -> String {
+    format!(
+        r#"{{"name":"{name}","ph":"M","pid":{pid},"tid":{tid},"args":{{"name":{}}}}}"#,
+        json_string(value)
+    )
+}
+
+/// A scope that lasted `duration_us`, starting at `start_us`.
+fn complete_event(name: &str, pid: u32, tid: usize, start_us: f64, duration_us: f64) -> String {
+    format!(
+        r#"{{"name":{},"ph":"X","pid":{pid},"tid":{tid},"ts":{start_us:.3},"dur":{duration_us:.3}}}"#,
+        json_string(name)
+    )
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn collect_cpu_events(
+    events: &mut Vec<String>,
+    scopes: &puffin::ScopeCollection,
+    stream: &puffin::Stream,
+    reader: puffin::Reader<'_>,
+    tid: usize,
+) {
+    for scope in reader.flatten() {
+        let name = scopes
+            .fetch_by_id(&scope.id)
+            .map_or("unknown scope", |details| details.name().as_ref());
+        events.push(complete_event(
+            name,
+            CPU_PID,
+            tid,
+            scope.record.start_ns as f64 / 1e3,
+            scope.record.duration_ns as f64 / 1e3,
+        ));
+        if let Ok(children) = puffin::Reader::with_offset(stream, scope.child_begin_position) {
+            collect_cpu_events(events, scopes, stream, children, tid);
+        }
+    }
+}
+
+/// `offset_us` moves the GPU timestamps onto the CPU timeline.
+fn collect_gpu_events(events: &mut Vec<String>, queries: &[GpuTimerQueryResult], offset_us: f64) {
+    for query in queries {
+        if let Some(time) = &query.time {
+            events.push(complete_event(
+                &query.label,
+                GPU_PID,
+                0,
+                time.start * 1e6 + offset_us,
+                (time.end - time.start) * 1e6,
+            ));
+        }
+        collect_gpu_events(events, &query.nested_queries, offset_us);
+    }
+}
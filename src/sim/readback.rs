@@ -0,0 +1,109 @@
+//! Copying small amounts of data from the GPU back to the CPU without stalling the frame.
+//!
+//! Instead of waiting on `map_async`, the staging buffers are checked again on later frames. Each buffer can only be
+//! mapped once at a time, so [`ReadbackQueue`] keeps a few of them around and simply skips a readback if they are all
+//! still in flight.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, OnceLock},
+};
+
+type MapResult = Arc<OnceLock<Result<(), wgpu::BufferAsyncError>>>;
+
+struct InFlight<T> {
+    buffer: wgpu::Buffer,
+    /// `None` until the copy was submitted and `map_async` was called.
+    map_result: Option<MapResult>,
+    tag: T,
+}
+
+/// A FIFO of staging buffers that are being copied into and mapped. `T` is whatever the caller needs to remember
+/// about each readback, e.g. the simulation step it was taken at.
+pub struct ReadbackQueue<T> {
+    label: &'static str,
+    size: wgpu::BufferAddress,
+    max_in_flight: usize,
+    free: Vec<wgpu::Buffer>,
+    in_flight: VecDeque<InFlight<T>>,
+}
+
+impl<T> ReadbackQueue<T> {
+    pub fn new(label: &'static str, size: wgpu::BufferAddress, max_in_flight: usize) -> Self {
+        Self {
+            label,
+            size,
+            max_in_flight,
+            free: Vec::new(),
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// Records a copy of `size` bytes from `source` into a free staging buffer. Returns `false` if every staging
+    /// buffer is still waiting to be read.
+    pub fn copy_from(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        tag: T,
+    ) -> bool {
+        let buffer = match self.free.pop() {
+            Some(buffer) => buffer,
+            None if self.in_flight.len() < self.max_in_flight => {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(self.label),
+                    size: self.size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            }
+            None => return false,
+        };
+
+        encoder.copy_buffer_to_buffer(source, offset, &buffer, 0, self.size);
+        self.in_flight.push_back(InFlight {
+            buffer,
+            map_result: None,
+            tag,
+        });
+        true
+    }
+
+    /// Starts mapping every buffer that was copied into. Must be called after the copies have been submitted.
+    pub fn map_submitted(&mut self) {
+        for in_flight in self
+            .in_flight
+            .iter_mut()
+            .filter(|in_flight| in_flight.map_result.is_none())
+        {
+            let map_result = in_flight.map_result.insert(MapResult::default()).clone();
+            in_flight
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    _ = map_result.set(result);
+                });
+        }
+    }
+
+    /// Returns the oldest readback if it has finished mapping, in the order they were copied.
+    pub fn try_recv(&mut self) -> Option<(T, Vec<u8>)> {
+        let result = self.in_flight.front()?.map_result.as_ref()?.get()?.clone();
+        let InFlight { buffer, tag, .. } = self.in_flight.pop_front()?;
+        match result {
+            Ok(()) => {
+                let data = buffer.slice(..).get_mapped_range().to_vec();
+                buffer.unmap();
+                self.free.push(buffer);
+                Some((tag, data))
+            }
+            Err(err) => {
+                // The buffer is dropped and a new one gets created on demand
+                log::warn!("Failed to read back {}: {err}", self.label);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Saves a fixed flock to a file and loads it again, so a run can start from a known state.
+//!
+//! A snapshot is a small header followed by the particles exactly as they are in the particle buffers:
+//! - the magic bytes `BOIDSNAP`
+//! - the format version, a little-endian `u32`
+//! - the number of particles, a little-endian `u32`
+//! - that many `boids::Particle`s, with little-endian floats
+
+use std::fmt;
+
+use crate::{shaders::boids, sim::MAX_PARTICLES};
+
+const MAGIC: &[u8; 8] = b"BOIDSNAP";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = MAGIC.len() + 2 * size_of::<u32>();
+
+// every target that wgpu runs on is little-endian, so the particles can be copied as they are
+#[cfg(target_endian = "big")]
+compile_error!("snapshots assume a little-endian target");
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The file doesn't start with the magic bytes
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    TooManyParticles(usize),
+    /// The size of the file doesn't match the number of particles in its header
+    WrongSize {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotASnapshot => write!(f, "not a boids snapshot"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {version}")
+            }
+            Self::TooManyParticles(count) => {
+                write!(f, "{count} boids exceed the limit of {MAX_PARTICLES} boids")
+            }
+            Self::WrongSize { expected, actual } => write!(
+                f,
+                "the snapshot is {actual} bytes, but should be {expected} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Writes `particles` into a snapshot.
+pub fn encode_snapshot(particles: &[boids::Particle]) -> Vec<u8> {
+    let particle_bytes: &[u8] = bytemuck::cast_slice(particles);
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + particle_bytes.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(particles.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(particle_bytes);
+    bytes
+}
+
+/// Reads the particles of a snapshot that `encode_snapshot` wrote.
+pub fn decode_snapshot(bytes: &[u8]) -> Result<Vec<boids::Particle>, SnapshotError> {
+    let header = bytes
+        .get(..HEADER_SIZE)
+        .filter(|header| header.starts_with(MAGIC))
+        .ok_or(SnapshotError::NotASnapshot)?;
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes(
+            header[offset..offset + size_of::<u32>()]
+                .try_into()
+                .unwrap(),
+        )
+    };
+
+    let version = read_u32(MAGIC.len());
+    if version != VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let count = read_u32(MAGIC.len() + size_of::<u32>()) as usize;
+    if count > MAX_PARTICLES {
+        return Err(SnapshotError::TooManyParticles(count));
+    }
+    let expected = HEADER_SIZE + count * size_of::<boids::Particle>();
+    if bytes.len() != expected {
+        return Err(SnapshotError::WrongSize {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    Ok(bytes[HEADER_SIZE..]
+        .chunks_exact(size_of::<boids::Particle>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect())
+}
@@ -0,0 +1,223 @@
+//! Loads textures from KTX2 containers.
+
+use std::fmt;
+
+use ktx2::{Format, SupercompressionScheme};
+use wgpu::util::DeviceExt;
+
+#[derive(Debug)]
+pub enum TextureError {
+    Parse(ktx2::ParseError),
+    /// Supercompressed levels would have to be decompressed before uploading, which isn't implemented
+    UnsupportedSupercompression(SupercompressionScheme),
+    UnsupportedFormat(Option<Format>),
+    /// The format is supported by wgpu, but the device was created without the features it needs
+    MissingFeatures(wgpu::TextureFormat, wgpu::Features),
+    /// 3D textures aren't supported
+    UnsupportedDimension,
+    /// Only cube maps have more than one face, and they have 6
+    UnsupportedFaceCount(u32),
+    TooLarge {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    /// There are more array layers and cube faces together than the device supports
+    TooManyLayers {
+        count: u64,
+        max: u32,
+    },
+    /// There are more mip levels than a texture of this size can have
+    TooManyLevels(u32),
+    /// The size of a mip level doesn't match its dimensions and format
+    BadLevelSize {
+        level: usize,
+        expected: u64,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "invalid KTX2 file: {err}"),
+            Self::UnsupportedSupercompression(scheme) => {
+                write!(f, "unsupported supercompression scheme {scheme:?}")
+            }
+            Self::UnsupportedFormat(Some(format)) => write!(f, "unsupported format {format:?}"),
+            Self::UnsupportedFormat(None) => write!(f, "the format is undefined"),
+            Self::MissingFeatures(format, features) => {
+                write!(f, "{format:?} needs the {features:?} features")
+            }
+            Self::UnsupportedDimension => write!(f, "3D textures aren't supported"),
+            Self::UnsupportedFaceCount(count) => {
+                write!(f, "textures have 1 or 6 faces, but this one has {count}")
+            }
+            Self::TooLarge { width, height, max } => {
+                write!(f, "{width}x{height} exceeds the limit of {max}x{max}")
+            }
+            Self::TooManyLayers { count, max } => {
+                write!(f, "{count} layers exceed the limit of {max}")
+            }
+            Self::TooManyLevels(count) => write!(f, "too many mip levels ({count})"),
+            Self::BadLevelSize {
+                level,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "mip level {level} is {actual} bytes, but should be {expected} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<ktx2::ParseError> for TextureError {
+    fn from(err: ktx2::ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+/// The wgpu format that matches a KTX2 (Vulkan) format, if it's one that boids can be textured with.
+fn wgpu_format(format: Format) -> Option<wgpu::TextureFormat> {
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat as Tf};
+
+    Some(match format {
+        Format::R8_UNORM => Tf::R8Unorm,
+        Format::R8G8_UNORM => Tf::Rg8Unorm,
+        Format::R8G8B8A8_UNORM => Tf::Rgba8Unorm,
+        Format::R8G8B8A8_SRGB => Tf::Rgba8UnormSrgb,
+        Format::B8G8R8A8_UNORM => Tf::Bgra8Unorm,
+        Format::B8G8R8A8_SRGB => Tf::Bgra8UnormSrgb,
+        Format::R16G16B16A16_SFLOAT => Tf::Rgba16Float,
+        Format::BC1_RGBA_UNORM_BLOCK => Tf::Bc1RgbaUnorm,
+        Format::BC1_RGBA_SRGB_BLOCK => Tf::Bc1RgbaUnormSrgb,
+        Format::BC3_UNORM_BLOCK => Tf::Bc3RgbaUnorm,
+        Format::BC3_SRGB_BLOCK => Tf::Bc3RgbaUnormSrgb,
+        Format::BC7_UNORM_BLOCK => Tf::Bc7RgbaUnorm,
+        Format::BC7_SRGB_BLOCK => Tf::Bc7RgbaUnormSrgb,
+        Format::ETC2_R8G8B8A8_UNORM_BLOCK => Tf::Etc2Rgba8Unorm,
+        Format::ETC2_R8G8B8A8_SRGB_BLOCK => Tf::Etc2Rgba8UnormSrgb,
+        Format::ASTC_4x4_UNORM_BLOCK => Tf::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::Unorm,
+        },
+        Format::ASTC_4x4_SRGB_BLOCK => Tf::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::UnormSrgb,
+        },
+        _ => return None,
+    })
+}
+
+/// Uploads a KTX2 file with all of its mip levels. Array layers and cube faces become array layers of the texture.
+pub fn load_ktx2(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    bytes: &[u8],
+) -> Result<wgpu::Texture, TextureError> {
+    let reader = ktx2::Reader::new(bytes)?;
+    let header = reader.header();
+
+    if let Some(scheme) = header.supercompression_scheme {
+        return Err(TextureError::UnsupportedSupercompression(scheme));
+    }
+    let format = header
+        .format
+        .and_then(wgpu_format)
+        .ok_or(TextureError::UnsupportedFormat(header.format))?;
+    let missing_features = format.required_features() - device.features();
+    if !missing_features.is_empty() {
+        return Err(TextureError::MissingFeatures(format, missing_features));
+    }
+    if header.pixel_depth > 1 {
+        return Err(TextureError::UnsupportedDimension);
+    }
+    if header.face_count != 1 && header.face_count != 6 {
+        return Err(TextureError::UnsupportedFaceCount(header.face_count));
+    }
+
+    let limits = device.limits();
+    let width = header.pixel_width;
+    let height = header.pixel_height.max(1);
+    let max = limits.max_texture_dimension_2d;
+    if width > max || height > max {
+        return Err(TextureError::TooLarge { width, height, max });
+    }
+    let num_layers = u64::from(header.layer_count.max(1)) * u64::from(header.face_count);
+    if num_layers > u64::from(limits.max_texture_array_layers) {
+        return Err(TextureError::TooManyLayers {
+            count: num_layers,
+            max: limits.max_texture_array_layers,
+        });
+    }
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: num_layers as u32,
+    };
+    // a level count of 0 asks the loader to generate mipmaps, which isn't supported, so there's only the base level
+    let num_levels = reader.levels().len() as u32;
+    if num_levels > size.max_mips(wgpu::TextureDimension::D2) {
+        return Err(TextureError::TooManyLevels(num_levels));
+    }
+
+    // check every level up front, since wgpu panics if there isn't enough data
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format
+        .block_copy_size(None)
+        .ok_or(TextureError::UnsupportedFormat(header.format))?;
+    let mut data = Vec::new();
+    for (level, level_data) in reader.levels().enumerate() {
+        let level_width = (width >> level).max(1);
+        let level_height = (height >> level).max(1);
+        // the sizes come straight from the file, so the product must not overflow
+        let expected = [
+            level_height.div_ceil(block_height),
+            block_size,
+            size.depth_or_array_layers,
+        ]
+        .into_iter()
+        .try_fold(
+            u64::from(level_width.div_ceil(block_width)),
+            |product, factor| product.checked_mul(u64::from(factor)),
+        )
+        .ok_or(TextureError::TooLarge { width, height, max })?;
+        if level_data.len() as u64 != expected {
+            return Err(TextureError::BadLevelSize {
+                level,
+                expected,
+                actual: level_data.len(),
+            });
+        }
+        data.extend_from_slice(level_data);
+    }
+
+    Ok(device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: num_levels,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::MipMajor,
+        &data,
+    ))
+}
+
+/// A 2D view of the first layer or face of `texture`.
+pub fn first_layer_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        array_layer_count: Some(1),
+        ..Default::default()
+    })
+}